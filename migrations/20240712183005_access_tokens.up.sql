@@ -0,0 +1,7 @@
+CREATE TABLE access_tokens (
+	token_id	BIGSERIAL PRIMARY KEY NOT NULL UNIQUE,
+	token_hash	TEXT NOT NULL UNIQUE, --sha256 of the bearer token, the token itself is never stored
+	uid			BIGINT NOT NULL REFERENCES internal_users(uid) ON DELETE CASCADE,
+	created		BIGINT NOT NULL, --timestamp in milis
+	expires		BIGINT NULL --timestamp in milis, never expires when null
+);
@@ -0,0 +1,6 @@
+ALTER TABLE access_tokens
+	DROP COLUMN app_id,
+	DROP COLUMN scopes;
+
+DROP TABLE oauth_authorization_codes;
+DROP TABLE oauth_apps;
@@ -0,0 +1,25 @@
+CREATE TABLE oauth_apps (
+	app_id			BIGSERIAL PRIMARY KEY NOT NULL UNIQUE,
+	client_id		TEXT NOT NULL UNIQUE,
+	client_secret	TEXT NOT NULL, --sha256 of the secret, it is only shown once at registration
+	name			TEXT NOT NULL,
+	website			TEXT NULL,
+	redirect_uris	TEXT NOT NULL, --newline separated
+	scopes			TEXT NOT NULL, --space separated
+	created			BIGINT NOT NULL --timestamp in milis
+);
+
+CREATE TABLE oauth_authorization_codes (
+	code_hash		TEXT PRIMARY KEY NOT NULL UNIQUE,
+	app_id			BIGINT NOT NULL REFERENCES oauth_apps(app_id) ON DELETE CASCADE,
+	uid				BIGINT NOT NULL REFERENCES internal_users(uid) ON DELETE CASCADE,
+	redirect_uri	TEXT NOT NULL,
+	scopes			TEXT NOT NULL,
+	code_challenge			TEXT NULL, --PKCE
+	code_challenge_method	TEXT NULL,
+	expires			BIGINT NOT NULL --timestamp in milis
+);
+
+ALTER TABLE access_tokens
+	ADD COLUMN app_id	BIGINT NULL REFERENCES oauth_apps(app_id) ON DELETE CASCADE, --null for tokens issued by /login
+	ADD COLUMN scopes	TEXT NOT NULL DEFAULT 'read write follow';
@@ -0,0 +1,2 @@
+ALTER TABLE activitypub_users
+	DROP COLUMN created_at;
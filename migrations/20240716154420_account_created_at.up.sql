@@ -0,0 +1,2 @@
+ALTER TABLE activitypub_users
+	ADD COLUMN created_at	BIGINT NOT NULL DEFAULT (extract(epoch from now()) * 1000)::BIGINT; --timestamp in milis
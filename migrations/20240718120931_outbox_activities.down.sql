@@ -0,0 +1,7 @@
+ALTER TABLE activity_objects
+	ADD CONSTRAINT activity_objects_in_reply_to_fkey FOREIGN KEY (in_reply_to) REFERENCES objects(id);
+
+DROP TABLE tombstones;
+DROP TABLE announces;
+DROP TABLE likes;
+DROP TABLE activities;
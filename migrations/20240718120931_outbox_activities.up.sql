@@ -0,0 +1,34 @@
+CREATE TABLE activities (
+	act_id		BIGSERIAL PRIMARY KEY NOT NULL UNIQUE,
+	id			TEXT NULL UNIQUE,
+	type_field	TEXT NOT NULL,
+	actor		TEXT NOT NULL REFERENCES activitypub_users(id) ON DELETE CASCADE,
+	object		TEXT NOT NULL, --id of whatever the activity acts on
+	body		TEXT NULL, --the activity as it was delivered
+	published	BIGINT NOT NULL --timestamp in milis
+);
+
+CREATE TABLE likes (
+	actor		TEXT NOT NULL REFERENCES activitypub_users(id) ON DELETE CASCADE,
+	object		TEXT NOT NULL, --not a reference, the liked object may be remote and never fetched
+	activity_id	TEXT NOT NULL UNIQUE,
+	published	BIGINT NOT NULL,
+	PRIMARY KEY (actor, object)
+);
+
+CREATE TABLE announces (
+	actor		TEXT NOT NULL REFERENCES activitypub_users(id) ON DELETE CASCADE,
+	object		TEXT NOT NULL,
+	activity_id	TEXT NOT NULL UNIQUE,
+	published	BIGINT NOT NULL,
+	PRIMARY KEY (actor, object)
+);
+
+CREATE TABLE tombstones (
+	id			TEXT PRIMARY KEY NOT NULL UNIQUE,
+	former_type	TEXT NOT NULL,
+	deleted		BIGINT NOT NULL --timestamp in milis
+);
+
+-- replies can point at objects we don't have or that were deleted
+ALTER TABLE activity_objects DROP CONSTRAINT activity_objects_in_reply_to_fkey;
@@ -0,0 +1,18 @@
+CREATE TABLE home_timeline (
+	ap_user_id	BIGINT NOT NULL REFERENCES activitypub_users(ap_user_id) ON DELETE CASCADE, --whose feed this is
+	obj_id		BIGINT NOT NULL REFERENCES objects(obj_id) ON DELETE CASCADE,
+	inserted	BIGINT NOT NULL, --timestamp in milis
+	PRIMARY KEY (ap_user_id, obj_id)
+);
+
+-- fill the feeds of existing local users with what is already stored
+INSERT INTO home_timeline (ap_user_id, obj_id, inserted)
+SELECT objects.ap_user_id, objects.obj_id, objects.published FROM objects
+	INNER JOIN internal_users ON internal_users.activitypub_actor = objects.ap_user_id
+UNION
+SELECT internal_users.activitypub_actor, objects.obj_id, objects.published FROM objects
+	INNER JOIN activitypub_users owner ON objects.ap_user_id = owner.ap_user_id
+	INNER JOIN following ON following.following = owner.id
+	INNER JOIN activitypub_users follower ON following.actor = follower.id
+	INNER JOIN internal_users ON internal_users.activitypub_actor = follower.ap_user_id
+ON CONFLICT DO NOTHING;
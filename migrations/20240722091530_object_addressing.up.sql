@@ -0,0 +1,12 @@
+CREATE TABLE object_recipients (
+	obj_id		BIGINT NOT NULL REFERENCES objects(obj_id) ON DELETE CASCADE,
+	field		TEXT NOT NULL, --to or cc
+	recipient	TEXT NOT NULL, --an actor or collection id, or as:Public
+	PRIMARY KEY (obj_id, field, recipient)
+);
+
+CREATE INDEX object_recipients_recipient_idx ON object_recipients (recipient);
+
+-- everything stored so far was forced to be public
+INSERT INTO object_recipients (obj_id, field, recipient)
+SELECT obj_id, 'to', 'https://www.w3.org/ns/activitystreams#Public' FROM objects;
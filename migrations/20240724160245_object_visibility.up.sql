@@ -0,0 +1,19 @@
+ALTER TABLE objects
+	ADD COLUMN visibility TEXT NOT NULL DEFAULT '"public"'; --serialized Visibility
+
+UPDATE objects SET visibility = CASE
+	WHEN EXISTS (
+		SELECT 1 FROM object_recipients r
+		WHERE r.obj_id = objects.obj_id AND r.field = 'to' AND r.recipient = 'https://www.w3.org/ns/activitystreams#Public'
+	) THEN '"public"'
+	WHEN EXISTS (
+		SELECT 1 FROM object_recipients r
+		WHERE r.obj_id = objects.obj_id AND r.field = 'cc' AND r.recipient = 'https://www.w3.org/ns/activitystreams#Public'
+	) THEN '"unlisted"'
+	WHEN EXISTS (
+		SELECT 1 FROM object_recipients r
+		INNER JOIN activitypub_users owner ON owner.ap_user_id = objects.ap_user_id
+		WHERE r.obj_id = objects.obj_id AND r.recipient = owner.followers
+	) THEN '"private"'
+	ELSE '"direct"'
+END;
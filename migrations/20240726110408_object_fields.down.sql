@@ -0,0 +1,16 @@
+ALTER TABLE activity_objects
+	DROP COLUMN summary,
+	DROP COLUMN sensitive,
+	DROP COLUMN media_type,
+	DROP COLUMN generator,
+	DROP COLUMN replies,
+	DROP COLUMN attachment,
+	DROP COLUMN start_time,
+	DROP COLUMN end_time,
+	DROP COLUMN updated,
+	DROP COLUMN tag,
+	DROP COLUMN url,
+	DROP COLUMN icon,
+	DROP COLUMN image,
+	DROP COLUMN duration,
+	DROP COLUMN preview;
@@ -0,0 +1,17 @@
+-- fields that are objects or arrays are stored as json
+ALTER TABLE activity_objects
+	ADD COLUMN summary		TEXT NULL,
+	ADD COLUMN sensitive	BOOLEAN NULL,
+	ADD COLUMN media_type	TEXT NULL, --serialized MediaType
+	ADD COLUMN generator	TEXT NULL,
+	ADD COLUMN replies		TEXT NULL, --json
+	ADD COLUMN attachment	TEXT NULL, --json
+	ADD COLUMN start_time	TEXT NULL,
+	ADD COLUMN end_time		TEXT NULL,
+	ADD COLUMN updated		TEXT NULL,
+	ADD COLUMN tag			TEXT NULL, --json
+	ADD COLUMN url			TEXT NULL, --json
+	ADD COLUMN icon			TEXT NULL,
+	ADD COLUMN image		TEXT NULL,
+	ADD COLUMN duration		TEXT NULL,
+	ADD COLUMN preview		TEXT NULL; --json
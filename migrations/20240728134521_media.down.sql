@@ -0,0 +1,15 @@
+ALTER TABLE attachments
+	DROP COLUMN position,
+	DROP CONSTRAINT attachments_obj_id_fkey,
+	ADD CONSTRAINT attachments_obj_id_fkey FOREIGN KEY (obj_id) REFERENCES activity_objects(obj_id) ON DELETE CASCADE;
+
+ALTER TABLE files
+	DROP COLUMN storage_key,
+	DROP COLUMN media_type,
+	DROP COLUMN size,
+	DROP COLUMN width,
+	DROP COLUMN height,
+	DROP COLUMN blurhash,
+	DROP COLUMN description,
+	DROP COLUMN uploader,
+	DROP COLUMN created;
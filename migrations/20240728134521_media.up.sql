@@ -0,0 +1,16 @@
+ALTER TABLE files
+	ADD COLUMN storage_key	TEXT NOT NULL UNIQUE,
+	ADD COLUMN media_type	TEXT NOT NULL, --mime type
+	ADD COLUMN size			BIGINT NOT NULL, --in bytes
+	ADD COLUMN width		INTEGER NULL,
+	ADD COLUMN height		INTEGER NULL,
+	ADD COLUMN blurhash		TEXT NULL,
+	ADD COLUMN description	TEXT NULL, --alt text
+	ADD COLUMN uploader		BIGINT NULL REFERENCES activitypub_users(ap_user_id) ON DELETE CASCADE,
+	ADD COLUMN created		BIGINT NOT NULL; --timestamp in milis
+
+-- attachments point at objects, not just activity_objects, and keep their order
+ALTER TABLE attachments
+	DROP CONSTRAINT attachments_obj_id_fkey,
+	ADD CONSTRAINT attachments_obj_id_fkey FOREIGN KEY (obj_id) REFERENCES objects(obj_id) ON DELETE CASCADE,
+	ADD COLUMN position		INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- files fetched through the media proxy remember where they came from
+ALTER TABLE files
+	ADD COLUMN remote_url	TEXT NULL UNIQUE;
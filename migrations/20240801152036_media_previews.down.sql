@@ -0,0 +1,14 @@
+ALTER TABLE attachments
+	DROP COLUMN media_type,
+	DROP COLUMN width,
+	DROP COLUMN height,
+	DROP COLUMN blurhash,
+	DROP COLUMN preview_url,
+	DROP COLUMN preview_width,
+	DROP COLUMN preview_height;
+
+ALTER TABLE files
+	DROP COLUMN preview_key,
+	DROP COLUMN preview_media_type,
+	DROP COLUMN preview_width,
+	DROP COLUMN preview_height;
@@ -0,0 +1,15 @@
+ALTER TABLE files
+	ADD COLUMN preview_key			TEXT NULL UNIQUE,
+	ADD COLUMN preview_media_type	TEXT NULL,
+	ADD COLUMN preview_width		INTEGER NULL,
+	ADD COLUMN preview_height		INTEGER NULL;
+
+-- what gets published for an attachment, so it doesn't depend on the file
+ALTER TABLE attachments
+	ADD COLUMN media_type		TEXT NULL,
+	ADD COLUMN width			INTEGER NULL,
+	ADD COLUMN height			INTEGER NULL,
+	ADD COLUMN blurhash			TEXT NULL,
+	ADD COLUMN preview_url		TEXT NULL,
+	ADD COLUMN preview_width	INTEGER NULL,
+	ADD COLUMN preview_height	INTEGER NULL;
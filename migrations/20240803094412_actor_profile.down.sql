@@ -0,0 +1,11 @@
+ALTER TABLE activitypub_users
+	DROP COLUMN url,
+	DROP COLUMN icon,
+	DROP COLUMN image,
+	DROP COLUMN featured,
+	DROP COLUMN featured_tags,
+	DROP COLUMN manually_approves_followers,
+	DROP COLUMN discoverable,
+	DROP COLUMN indexable,
+	DROP COLUMN memorial,
+	DROP COLUMN attachment;
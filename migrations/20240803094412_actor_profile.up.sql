@@ -0,0 +1,15 @@
+ALTER TABLE activitypub_users
+	ADD COLUMN url							TEXT NULL, --json
+	ADD COLUMN icon							TEXT NULL, --json, avatar
+	ADD COLUMN image						TEXT NULL, --json, header
+	ADD COLUMN featured						TEXT NULL,
+	ADD COLUMN featured_tags				TEXT NULL,
+	ADD COLUMN manually_approves_followers	BOOLEAN NOT NULL DEFAULT false,
+	ADD COLUMN discoverable					BOOLEAN NULL,
+	ADD COLUMN indexable					BOOLEAN NULL,
+	ADD COLUMN memorial						BOOLEAN NOT NULL DEFAULT false,
+	ADD COLUMN attachment					TEXT NULL; --json, profile fields
+
+-- local users get a link to their profile page
+UPDATE activitypub_users SET url = '"https://' || domain || '/@' || preferred_username || '"'
+	WHERE ap_user_id IN (SELECT activitypub_actor FROM internal_users);
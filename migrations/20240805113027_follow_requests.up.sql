@@ -0,0 +1,10 @@
+-- follows waiting on the followed actor to accept them
+CREATE TABLE follow_requests (
+	actor		TEXT NOT NULL REFERENCES activitypub_users(id) ON DELETE CASCADE,
+	target		TEXT NOT NULL REFERENCES activitypub_users(id) ON DELETE CASCADE,
+	activity_id	TEXT NOT NULL UNIQUE, --id of the Follow
+	published	BIGINT NOT NULL,
+	PRIMARY KEY (actor, target)
+);
+
+CREATE INDEX follow_requests_target ON follow_requests (target);
@@ -0,0 +1,4 @@
+DROP INDEX activitypub_users_webfinger;
+
+ALTER TABLE activitypub_users
+	DROP COLUMN webfinger;
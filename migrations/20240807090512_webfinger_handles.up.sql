@@ -0,0 +1,5 @@
+-- the handle an actor was found by, it can be on a different domain than the actor
+ALTER TABLE activitypub_users
+	ADD COLUMN webfinger	TEXT NULL; --user@domain
+
+CREATE INDEX activitypub_users_webfinger ON activitypub_users (LOWER(webfinger));
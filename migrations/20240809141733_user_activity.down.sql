@@ -0,0 +1,2 @@
+ALTER TABLE internal_users
+	DROP COLUMN last_active;
@@ -0,0 +1,3 @@
+-- for the active user counts in nodeinfo
+ALTER TABLE internal_users
+	ADD COLUMN last_active	BIGINT NULL; --timestamp in milis, updated at most hourly
@@ -0,0 +1,8 @@
+DROP TABLE invites;
+
+DROP INDEX internal_users_preferred_username_lower;
+
+ALTER TABLE internal_users
+	DROP COLUMN approved,
+	DROP COLUMN email,
+	DROP COLUMN registration_reason;
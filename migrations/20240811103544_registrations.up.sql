@@ -0,0 +1,18 @@
+-- accounts signed up while approval is required can't log in until they're approved
+ALTER TABLE internal_users
+	ADD COLUMN approved		BOOLEAN NOT NULL DEFAULT TRUE,
+	ADD COLUMN email		TEXT NULL,
+	ADD COLUMN registration_reason	TEXT NULL; --why they want to join, shown when approving
+
+CREATE UNIQUE INDEX internal_users_preferred_username_lower ON internal_users (LOWER(preferred_username));
+
+CREATE TABLE invites (
+	code		TEXT PRIMARY KEY NOT NULL,
+	uid			BIGINT NOT NULL REFERENCES internal_users(uid) ON DELETE CASCADE, --who made the invite
+	max_uses	INT NULL, --unlimited when null
+	uses		INT NOT NULL DEFAULT 0,
+	created		BIGINT NOT NULL, --timestamp in milis
+	expires		BIGINT NULL --timestamp in milis, never expires when null
+);
+
+CREATE INDEX invites_uid ON invites (uid);
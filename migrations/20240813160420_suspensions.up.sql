@@ -0,0 +1,3 @@
+-- suspended accounts keep their data but can't log in
+ALTER TABLE internal_users
+	ADD COLUMN suspended	BOOLEAN NOT NULL DEFAULT FALSE;
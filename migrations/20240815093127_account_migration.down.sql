@@ -0,0 +1,3 @@
+ALTER TABLE activitypub_users
+	DROP COLUMN also_known_as,
+	DROP COLUMN moved_to;
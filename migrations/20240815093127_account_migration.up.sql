@@ -0,0 +1,4 @@
+-- accounts can say they're the same person as other accounts and move to one of them
+ALTER TABLE activitypub_users
+	ADD COLUMN also_known_as	TEXT NULL, --json, aliases
+	ADD COLUMN moved_to			TEXT NULL;
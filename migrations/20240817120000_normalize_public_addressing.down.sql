@@ -0,0 +1 @@
+-- the compacted forms aren't restored
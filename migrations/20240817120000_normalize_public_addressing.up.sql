@@ -0,0 +1,22 @@
+-- as:Public and Public are the same collection as its full id
+DELETE FROM object_recipients r
+WHERE r.recipient IN ('as:Public', 'Public') AND EXISTS (
+	SELECT 1 FROM object_recipients full_id
+	WHERE full_id.obj_id = r.obj_id AND full_id.field = r.field
+	AND full_id.recipient = 'https://www.w3.org/ns/activitystreams#Public'
+);
+
+UPDATE object_recipients SET recipient = 'https://www.w3.org/ns/activitystreams#Public'
+WHERE recipient IN ('as:Public', 'Public');
+
+UPDATE objects SET visibility = CASE
+	WHEN EXISTS (
+		SELECT 1 FROM object_recipients r
+		WHERE r.obj_id = objects.obj_id AND r.field = 'to' AND r.recipient = 'https://www.w3.org/ns/activitystreams#Public'
+	) THEN '"public"'
+	ELSE '"unlisted"'
+END
+WHERE visibility IN ('"private"', '"direct"') AND EXISTS (
+	SELECT 1 FROM object_recipients r
+	WHERE r.obj_id = objects.obj_id AND r.recipient = 'https://www.w3.org/ns/activitystreams#Public'
+);
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use super::{core_types::*, object::Object};
+use super::{core_types::*, object::Document};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
@@ -58,6 +58,40 @@ impl From<String> for PublicKey {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum PropertyValueType {
+    PropertyValue,
+}
+
+/// a name and value shown on a profile, the value is html
+///
+/// https://docs.joinmastodon.org/spec/activitypub/#PropertyValue
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PropertyValue {
+    #[serde(rename = "type")]
+    pub type_field: PropertyValueType,
+    pub name: String,
+    pub value: String,
+}
+
+impl PropertyValue {
+    pub fn new(name: String, value: String) -> PropertyValue {
+        PropertyValue {
+            type_field: PropertyValueType::PropertyValue,
+            name,
+            value,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+/// actor attachments, anything other than profile fields is kept as is
+pub enum ActorAttachment {
+    PropertyValue(PropertyValue),
+    Other(serde_json::Value),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 /// summary, id, and name are inherited from [`Object`]
@@ -75,6 +109,48 @@ pub struct Actor {
     pub followers: String,
     pub following: String,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// display name
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// bio, as html
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// profile page
+    pub url: Option<LinkOrArray>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// avatar
+    pub icon: Option<Document>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// header
+    pub image: Option<Document>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// collection of pinned posts
+    pub featured: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub featured_tags: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// if follows have to be accepted by the actor, a locked account
+    pub manually_approves_followers: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// ok to be suggested in directories
+    pub discoverable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// ok for posts to show up in search
+    pub indexable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// the account belonged to someone who has died
+    pub memorial: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// profile fields
+    pub attachment: Option<Vec<ActorAttachment>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// other accounts of the same person, an account can only be moved to one that lists it here
+    pub also_known_as: Option<SimpleLinkOrArray>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// the account this one moved to
+    pub moved_to: Option<Url>,
+
     #[serde(skip)]
     pub ap_user_id: Option<i64>,
     #[serde(skip)]
@@ -83,6 +159,38 @@ pub struct Actor {
     pub liked: Option<String>,
 }
 
+/// terms used by profiles that aren't in the activitystreams context
+pub fn actor_context() -> Context {
+    let mut terms: HashMap<String, ContextMapItem> = HashMap::new();
+    let mut term = |name: &str, value: &str| {
+        terms.insert(name.to_string(), ContextMapItem::String(value.to_string()));
+    };
+    term("manuallyApprovesFollowers", "as:manuallyApprovesFollowers");
+    term("toot", "http://joinmastodon.org/ns#");
+    term("discoverable", "toot:discoverable");
+    term("indexable", "toot:indexable");
+    term("memorial", "toot:memorial");
+    term("schema", "http://schema.org#");
+    term("PropertyValue", "schema:PropertyValue");
+    term("value", "schema:value");
+    let mut id_term = |name: &str, value: &str| {
+        let mut item = HashMap::new();
+        item.insert("@id".to_string(), value.to_string());
+        item.insert("@type".to_string(), "@id".to_string());
+        terms.insert(name.to_string(), ContextMapItem::Map(item));
+    };
+    id_term("featured", "toot:featured");
+    id_term("featuredTags", "toot:featuredTags");
+    id_term("alsoKnownAs", "as:alsoKnownAs");
+    id_term("movedTo", "as:movedTo");
+
+    Context::Array(vec![
+        ContextItem::String("https://www.w3.org/ns/activitystreams".to_owned()),
+        ContextItem::String("https://w3id.org/security/v1".to_owned()),
+        ContextItem::Map(terms),
+    ])
+}
+
 impl Actor {
     pub fn to_activitystream(self) -> ActivityStream {
         // let mut test: HashMap<String, ContextMapItem> = HashMap::new();
@@ -93,11 +201,7 @@ impl Actor {
         // test.insert("manuallyApprovesFollowers".to_string(), ContextMapItem::String("as:manuallyApprovesFollowers".to_string()));
         ActivityStream {
             content: ContextWrap {
-                context: Context::Array(vec![
-                    ContextItem::String("https://www.w3.org/ns/activitystreams".to_owned()),
-                    ContextItem::String("https://w3id.org/security/v1".to_owned()),
-                    // ContextItem::Map(test)
-                ]),
+                context: actor_context(),
                 // activity_stream: RangeLinkExtendsObject::Object(ExtendsObject::Actor(Box::new(
                 //     self,
                 // ))),
@@ -120,10 +224,7 @@ impl From<Box<Actor>> for ActivityStream {
     fn from(value: Box<Actor>) -> ActivityStream {
         ActivityStream {
             content: ContextWrap {
-                context: Context::Array(vec![
-                    ContextItem::String("https://www.w3.org/ns/activitystreams".to_owned()),
-                    ContextItem::String("https://w3id.org/security/v1".to_owned()),
-                ]),
+                context: actor_context(),
                 // activity_stream: RangeLinkExtendsObject::Object(ExtendsObject::Actor(value)),
                 activity_stream: ExtendsObject::Actor(value),
             },
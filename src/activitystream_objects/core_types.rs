@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use actix_web::web::Data;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use url::Url;
 
 use crate::{
@@ -186,13 +186,72 @@ pub enum LinkOrArray {
     Multiple(Vec<LinkSimpleOrExpanded>),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl LinkOrArray {
+    pub fn to_vec(&self) -> Vec<LinkSimpleOrExpanded> {
+        match self {
+            LinkOrArray::Single(x) => vec![*x.clone()],
+            LinkOrArray::Multiple(x) => x.clone(),
+        }
+    }
+}
+
+/// addressing an object to this makes it public
+pub const PUBLIC_COLLECTION: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// the compacted forms of the public collection are the same as the full id
+///
+/// https://www.w3.org/TR/activitypub/#public-addressing
+pub fn normalize_public(link: &str) -> &str {
+    match link {
+        "as:Public" | "Public" => PUBLIC_COLLECTION,
+        x => x,
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum SimpleLinkOrArray {
     Single(Url),
     Multiple(Vec<Url>),
 }
 
+impl<'de> Deserialize<'de> for SimpleLinkOrArray {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Single(String),
+            Multiple(Vec<String>),
+        }
+
+        let parse = |x: String| Url::parse(normalize_public(&x)).map_err(serde::de::Error::custom);
+        match Raw::deserialize(deserializer)? {
+            Raw::Single(x) => Ok(SimpleLinkOrArray::Single(parse(x)?)),
+            Raw::Multiple(x) => Ok(SimpleLinkOrArray::Multiple(
+                x.into_iter().map(parse).collect::<Result<_, _>>()?,
+            )),
+        }
+    }
+}
+
+impl SimpleLinkOrArray {
+    pub fn to_vec(&self) -> Vec<Url> {
+        match self {
+            SimpleLinkOrArray::Single(x) => vec![x.clone()],
+            SimpleLinkOrArray::Multiple(x) => x.clone(),
+        }
+    }
+    pub fn contains(&self, link: &str) -> bool {
+        match self {
+            SimpleLinkOrArray::Single(x) => x.as_str().eq(link),
+            SimpleLinkOrArray::Multiple(x) => x.iter().any(|x| x.as_str().eq(link)),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum RangeLinkObjOrArray {
@@ -8,6 +8,10 @@ pub enum LinkType {
     ///
     /// https://www.w3.org/TR/activitystreams-vocabulary/#dfn-mention
     Mention,
+    /// not part of the spec but used by mastodon and most others for tags
+    ///
+    /// https://docs.joinmastodon.org/spec/activitypub/#Hashtag
+    Hashtag,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -17,13 +21,20 @@ pub struct Link {
     pub type_field: LinkType,
 
     pub href: Url,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub hreflang: Option<String>,
-    pub media_type: String,
-    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub preview: Option<String>, //TODO
-    pub rel: Option<String>,     //TODO
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rel: Option<String>, //TODO
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -5,10 +5,11 @@ use url::Url;
 use super::{
     activities::{Activity, ExtendsIntransitive},
     actors::RangeLinkActor,
+    link::Link,
     collections::ExtendsCollection,
     core_types::{
         ActivityStream, Context, ContextWrap, ExtendsObject, LinkOrArray, RangeLinkExtendsObject,
-        RangeLinkObjOrArray, SimpleLinkOrArray,
+        SimpleLinkOrArray, PUBLIC_COLLECTION,
     },
 };
 
@@ -52,7 +53,52 @@ pub enum MediaType {
     Html,
     #[serde(rename = "text/markdown")]
     Markdown,
+    /// anything else, mostly the media type of attachments like `image/png`
+    #[serde(untagged)]
+    Other(String),
 }
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+/// who an object is addressed to, mapped onto `to` and `cc` the same way mastodon does
+pub enum Visibility {
+    /// to as:Public, cc followers and mentions. shown on public timelines
+    #[default]
+    Public,
+    /// to followers, cc as:Public and mentions. anyone can see it but it stays off public timelines
+    Unlisted,
+    /// to followers, cc mentions
+    Private,
+    /// to mentions only
+    Direct,
+}
+
+impl Visibility {
+    /// works out the visibility of an object from its addressing
+    pub fn from_addressing(
+        to: Option<&SimpleLinkOrArray>,
+        cc: Option<&SimpleLinkOrArray>,
+        followers: &str,
+    ) -> Visibility {
+        let to_contains = |x: &str| to.is_some_and(|to| to.contains(x));
+        let cc_contains = |x: &str| cc.is_some_and(|cc| cc.contains(x));
+
+        if to_contains(PUBLIC_COLLECTION) {
+            return Visibility::Public;
+        }
+        if cc_contains(PUBLIC_COLLECTION) {
+            return Visibility::Unlisted;
+        }
+        if to_contains(followers) || cc_contains(followers) {
+            return Visibility::Private;
+        }
+        Visibility::Direct
+    }
+    /// if anyone can read it without being a recipient
+    pub fn is_public(&self) -> bool {
+        matches!(self, Visibility::Public | Visibility::Unlisted)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub enum ObjectType {
     #[default]
@@ -81,6 +127,47 @@ pub enum ObjectType {
     Tombstone, // adds formerType | deleted
 }
 
+/// a file attached to an object, mastodon sends these as `Document` regardless of what they are
+///
+/// https://www.w3.org/TR/activitystreams-vocabulary/#dfn-document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    #[serde(rename = "type")]
+    pub type_field: ObjectType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<MediaType>,
+    pub url: Url,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// alt text
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blurhash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// a smaller version for timelines, with its own dimensions
+    pub preview: Option<Box<Link>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum AttachmentOrArray {
+    Single(Box<Document>),
+    Multiple(Vec<Document>),
+}
+
+impl AttachmentOrArray {
+    pub fn to_vec(&self) -> Vec<Document> {
+        match self {
+            AttachmentOrArray::Single(x) => vec![*x.clone()],
+            AttachmentOrArray::Multiple(x) => x.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct ObjectWrapper {
@@ -157,9 +244,8 @@ pub struct Object {
     pub to: Option<SimpleLinkOrArray>,
 
 
-    //TODO
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub attachment: Option<String>,
+    pub attachment: Option<AttachmentOrArray>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_time: Option<String>,
@@ -172,7 +258,12 @@ pub struct Object {
     pub summary: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tag: Option<SimpleLinkOrArray>,
+    /// mentions and hashtags
+    pub tag: Option<LinkOrArray>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// if the content should be hidden behind the summary, from mastodon's `as:sensitive`
+    pub sensitive: Option<bool>,
 
     
 
@@ -257,14 +348,33 @@ impl Object {
         self.published = Some(time);
         self
     }
+    pub fn updated_milis(mut self, updated: i64) -> Self {
+        let time = DateTime::from_timestamp_millis(updated).unwrap();
+        self.updated = Some(time.to_rfc3339_opts(SecondsFormat::Secs, true));
+        self
+    }
+    /// sets `to` and `cc` for the visibility. `followers` is the followers collection
+    /// of the author and `mentions` are the actors mentioned in it
+    pub fn visibility(mut self, visibility: Visibility, followers: Url, mentions: Vec<Url>) -> Self {
+        let public = Url::parse(PUBLIC_COLLECTION).unwrap();
+        let (to, cc) = match visibility {
+            Visibility::Public => (vec![public], [vec![followers], mentions].concat()),
+            Visibility::Unlisted => (vec![followers], [vec![public], mentions].concat()),
+            Visibility::Private => (vec![followers], mentions),
+            Visibility::Direct => (mentions, Vec::new()),
+        };
+        self.to = (!to.is_empty()).then_some(SimpleLinkOrArray::Multiple(to));
+        self.cc = (!cc.is_empty()).then_some(SimpleLinkOrArray::Multiple(cc));
+        self
+    }
     pub fn to_public(mut self) -> Self {
-        self.to = Some(SimpleLinkOrArray::Multiple(vec![Url::parse("https://www.w3.org/ns/activitystreams#Public").unwrap()]));
+        self.to = Some(SimpleLinkOrArray::Multiple(vec![Url::parse(PUBLIC_COLLECTION).unwrap()]));
         self
     }
     pub fn wrap(self, obj_type: ObjectType) -> ObjectWrapper {
         ObjectWrapper {
             type_field: obj_type,
-            object: self.to_public(),
+            object: self,
         }
     }
     pub fn to_activitystream(self, obj_type: ObjectType) -> ActivityStream {
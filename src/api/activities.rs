@@ -1,9 +1,74 @@
 use actix_web::{
+    error::ErrorNotFound,
     get,
-    web::{self},
-    HttpResponse, Result,
+    web::{self, Data},
+    HttpRequest, HttpResponse, Result,
 };
 
+use crate::{
+    api::{authentication::AuthenticatedUser, objects::can_request_view},
+    cache_and_fetch::Cache,
+    db::{
+        activities::{get_activity_by_db_id, get_activity_by_fedi_id, StoredActivity},
+        conn::DbConn,
+    },
+};
+
+fn activity_response(activity: Option<StoredActivity>, actor: &str) -> Result<HttpResponse> {
+    let body = match activity {
+        Some(x) if x.actor.eq(actor) => x.body,
+        _ => None,
+    };
+    let Some(body) = body else {
+        return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/activity+json; charset=utf-8")
+        .body(body))
+}
+
+#[get("/users/{preferred_username}/activities/{id}")]
+pub async fn get_activity(
+    path: web::Path<(String, i64)>,
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+) -> Result<HttpResponse> {
+    let (preferred_username, act_id) = path.into_inner();
+    let actor = format!(
+        "https://{}/users/{}",
+        &state.instance_domain, &preferred_username
+    );
+
+    let activity = get_activity_by_db_id(&conn.db, act_id).await.unwrap();
+    activity_response(activity, &actor)
+}
+
+/// the create activity for a status, its id is the status id with /activity on the end
+#[get("/users/{preferred_username}/statuses/{id}/activity")]
+pub async fn get_create_activity(
+    request: HttpRequest,
+    path: web::Path<(String, i64)>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    state: Data<crate::config::Config>,
+    user: Option<AuthenticatedUser>,
+) -> Result<HttpResponse> {
+    let (preferred_username, obj_id) = path.into_inner();
+    let actor = format!(
+        "https://{}/users/{}",
+        &state.instance_domain, &preferred_username
+    );
+    let id = format!("{}/statuses/{}/activity", &actor, obj_id);
+
+    if !can_request_view(&request, &conn, &cache, user.as_ref(), obj_id).await {
+        return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+    }
+
+    let activity = get_activity_by_fedi_id(&conn.db, &id).await.unwrap();
+    activity_response(activity, &actor)
+}
+
 // const TEST_USER: &str = "test";
 
 // pub const ACTIVITY: &str = r#"
@@ -1,7 +1,7 @@
 use std::time::SystemTime;
 
 use actix_web::{
-    error::ErrorNotFound,
+    error::{ErrorForbidden, ErrorNotFound},
     get,
     web::{self, Data},
     HttpRequest, HttpResponse, Result,
@@ -15,11 +15,19 @@ use openssl::{
 
 use crate::{
     activitystream_objects::core_types::ActivityStream,
-    api::activities,
+    api::{
+        activities,
+        mastodon::entities::{account_from_db, status_from_db},
+        objects::tombstone_response,
+        pages::{negotiate, profile_page, Representation, PROFILE_PAGE_STATUSES},
+    },
     cache_and_fetch::Cache,
     db::{
-        account_creation::create_internal_actor, actor_utilities::get_ap_actor_by_db_id,
-        conn::DbConn, internal_actor::get_actor_id_from_internal,
+        actor_utilities::get_ap_actor_by_db_id,
+        conn::DbConn,
+        internal_actor::{get_actor_id_from_internal, is_suspended},
+        objects::get_tombstone,
+        timelines::get_actor_public_timeline,
     },
     protocol::verification::{generate_digest, post_to_inbox},
 };
@@ -45,51 +53,90 @@ pub async fn get_instance_actor(
     //     .body(serde_json::to_string(&cache.instance_actor.string_rep.as_ref().unwrap()).unwrap()))
 }
 
-#[get("/users/{preferred_username}")]
-pub async fn get_actor(
-    path: web::Path<String>,
-    conn: Data<DbConn>,
-    request: HttpRequest,
-    body: web::Bytes,
+/// the actor as AS2, or their profile page for browsers
+async fn actor_response(
+    preferred_username: &str,
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    request: &HttpRequest,
 ) -> Result<HttpResponse> {
-    println!("getting the actor");
-
-    dbg!(request);
-    dbg!(&body);
-    dbg!(String::from_utf8(body.to_vec()));
-
-    let preferred_username = path.into_inner();
-
-    let val = get_actor_id_from_internal(&conn.db, &preferred_username).await;
+    let val = get_actor_id_from_internal(&conn.db, preferred_username).await;
 
     let id = match val.unwrap() {
         Some(x) => x,
         None => {
-            return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+            //deleted accounts answer with a tombstone
+            let id = format!(
+                "https://{}/users/{}",
+                &cache.state.instance_domain, preferred_username
+            );
+            let Some(tombstone) = get_tombstone(&conn.db, &id).await.unwrap() else {
+                return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+            };
+            return tombstone_response(tombstone, negotiate(request));
         }
     };
+    if is_suspended(&conn.db, preferred_username).await.unwrap() {
+        return Err(ErrorForbidden(r#"{"error":"This account is suspended"}"#));
+    }
+
+    let actor = get_ap_actor_by_db_id(id, conn).await;
+
+    if negotiate(request) == Representation::Html {
+        let Some(account) = account_from_db(conn, cache, id).await? else {
+            return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+        };
+        let ids = get_actor_public_timeline(&conn.db, id, PROFILE_PAGE_STATUSES)
+            .await
+            .unwrap();
+        let mut statuses = Vec::with_capacity(ids.len());
+        for obj_id in ids {
+            if let Some(x) = status_from_db(conn, cache, obj_id).await? {
+                statuses.push(x);
+            }
+        }
+        return Ok(profile_page(
+            &cache.state,
+            &account,
+            actor.id.as_str(),
+            &statuses,
+        ));
+    }
 
-    let actor = get_ap_actor_by_db_id(id, &conn).await;
     let actor = actor.to_activitystream();
 
     Ok(HttpResponse::Ok()
         .content_type("application/activity+json; charset=utf-8")
+        .insert_header(("Vary", "Accept"))
         .body(serde_json::to_string(&actor).unwrap()))
 }
 
-#[get("/create_test/{preferred_username}")]
-pub async fn create_test(
+#[get("/users/{preferred_username}")]
+pub async fn get_actor(
     path: web::Path<String>,
-    state: Data<crate::config::Config>,
     conn: Data<DbConn>,
+    cache: Data<Cache>,
+    request: HttpRequest,
+    body: web::Bytes,
 ) -> Result<HttpResponse> {
-    let preferred_username = path.into_inner();
+    println!("getting the actor");
 
-    let x = create_internal_actor(state, conn, preferred_username.clone(), preferred_username)
-        .await
-        .unwrap();
+    dbg!(&request);
+    dbg!(&body);
+    dbg!(String::from_utf8(body.to_vec()));
+
+    actor_response(&path.into_inner(), &conn, &cache, &request).await
+}
 
-    Ok(HttpResponse::Ok().body(format!("{x}")))
+/// the mastodon style alias of `/users/{preferred_username}`
+#[get("/@{preferred_username}")]
+pub async fn get_actor_alias(
+    path: web::Path<String>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    request: HttpRequest,
+) -> Result<HttpResponse> {
+    actor_response(&path.into_inner(), &conn, &cache, &request).await
 }
 
 // #[get("/post_test")]
@@ -0,0 +1,248 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{
+    dev::Payload,
+    error::{ErrorForbidden, ErrorInternalServerError, InternalError},
+    post,
+    web::{self, Data},
+    Error, FromRequest, HttpRequest, HttpResponse, Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{
+    access_tokens::{
+        generate_token, get_token_owner, insert_access_token, now_milis, revoke_access_token,
+        FULL_SCOPES, TOKEN_LIFETIME_MILIS,
+    },
+    conn::DbConn,
+    internal_actor::{touch_last_active, verify_password},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct LoginForm {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    /// seconds until the token expires
+    pub expires_in: i64,
+}
+
+fn unauthorized() -> Error {
+    InternalError::from_response(
+        "Unauthorized",
+        HttpResponse::Unauthorized()
+            .insert_header(("WWW-Authenticate", "Bearer"))
+            .content_type("application/json; charset=utf-8")
+            .body(r#"{"error":"Unauthorized"}"#),
+    )
+    .into()
+}
+
+/// gets the token out of an `Authorization: Bearer` header
+pub fn bearer_token(request: &HttpRequest) -> Option<String> {
+    let header = request.headers().get("Authorization")?;
+    let header = header.to_str().ok()?;
+    let (scheme, token) = header.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("Bearer") {
+        return None;
+    }
+    Some(token.trim().to_string())
+}
+
+/// the top level scopes, each can be narrowed with a suffix like `write:statuses`
+const TOP_LEVEL_SCOPES: [&str; 3] = ["read", "write", "follow"];
+
+/// the set of oauth scopes a token was granted
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scopes(Vec<String>);
+
+impl Scopes {
+    /// parses a space separated list of scopes, returns none if any are unknown
+    pub fn parse(scopes: &str) -> Option<Scopes> {
+        let mut parsed = Vec::new();
+        for scope in scopes.split_whitespace() {
+            let top_level = match scope.split_once(':') {
+                Some((top_level, specific)) => {
+                    if specific.is_empty() {
+                        return None;
+                    }
+                    top_level
+                }
+                None => scope,
+            };
+            if !TOP_LEVEL_SCOPES.contains(&top_level) {
+                return None;
+            }
+            if !parsed.iter().any(|x: &String| x.eq(scope)) {
+                parsed.push(scope.to_string());
+            }
+        }
+        Some(Scopes(parsed))
+    }
+    /// `write` allows `write:statuses` but not the other way around.
+    /// `follow` is the legacy scope for managing relationships
+    pub fn allows(&self, required: &str) -> bool {
+        self.0.iter().any(|granted| {
+            if granted.eq(required) {
+                return true;
+            }
+            if let Some((top_level, _)) = required.split_once(':') {
+                if granted.eq(top_level) {
+                    return true;
+                }
+            }
+            granted.eq("follow")
+                && matches!(
+                    required,
+                    "read:follows" | "write:follows" | "read:blocks" | "write:blocks"
+                )
+        })
+    }
+    /// true if every scope in `other` is allowed by this set
+    pub fn contains_all(&self, other: &Scopes) -> bool {
+        other.0.iter().all(|x| self.allows(x))
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Display for Scopes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.join(" "))
+    }
+}
+
+/// a local user that presented a valid bearer token. add it as a handler
+/// argument to require authentication for an endpoint, this is what every
+/// write endpoint uses whether the token came from /login or oauth
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub uid: i64,
+    pub preferred_username: String,
+    pub scopes: Scopes,
+    pub app_id: Option<i64>,
+}
+
+impl AuthenticatedUser {
+    pub fn require_scope(&self, scope: &str) -> Result<()> {
+        if self.scopes.allows(scope) {
+            return Ok(());
+        }
+        Err(InternalError::from_response(
+            "insufficient scope",
+            HttpResponse::Forbidden()
+                .insert_header((
+                    "WWW-Authenticate",
+                    format!(r#"Bearer error="insufficient_scope", scope="{scope}""#),
+                ))
+                .content_type("application/json; charset=utf-8")
+                .body(r#"{"error":"This action is outside the authorized scopes"}"#),
+        )
+        .into())
+    }
+    /// used by endpoints under `/users/{preferred_username}` so a user can only
+    /// act as themselves
+    pub fn require_user(&self, preferred_username: &str) -> Result<()> {
+        if self.preferred_username.eq(preferred_username) {
+            return Ok(());
+        }
+        Err(ErrorForbidden(r#"{"error":"Forbidden"}"#))
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let conn = req.app_data::<Data<DbConn>>().cloned();
+        let token = bearer_token(req);
+
+        Box::pin(async move {
+            let Some(conn) = conn else {
+                return Err(ErrorInternalServerError("no database connection"));
+            };
+            let Some(token) = token else {
+                return Err(unauthorized());
+            };
+
+            let owner = get_token_owner(&conn.db, &token).await;
+
+            if let Ok(Some(x)) = &owner {
+                touch_last_active(&conn.db, x.uid).await.unwrap();
+            }
+
+            match owner {
+                Ok(Some(x)) => Ok(AuthenticatedUser {
+                    uid: x.uid,
+                    preferred_username: x.preferred_username,
+                    scopes: Scopes::parse(&x.scopes).unwrap_or(Scopes(Vec::new())),
+                    app_id: x.app_id,
+                }),
+                Ok(None) => Err(unauthorized()),
+                Err(x) => {
+                    eprintln!("failed to look up token owner: {}", x);
+                    Err(ErrorInternalServerError(
+                        r#"{"error":"Internal Server Error"}"#,
+                    ))
+                }
+            }
+        })
+    }
+}
+
+#[post("/login")]
+pub async fn login(conn: Data<DbConn>, form: web::Json<LoginForm>) -> Result<HttpResponse> {
+    let form = form.into_inner();
+
+    let uid = verify_password(&conn.db, &form.username, &form.password)
+        .await
+        .unwrap();
+
+    let Some(uid) = uid else {
+        return Err(unauthorized());
+    };
+
+    let token = generate_token();
+    insert_access_token(
+        &conn.db,
+        uid,
+        &token,
+        Some(now_milis() + TOKEN_LIFETIME_MILIS),
+        None,
+        FULL_SCOPES,
+    )
+    .await
+    .unwrap();
+
+    let response = TokenResponse {
+        access_token: token,
+        token_type: "Bearer".to_string(),
+        expires_in: TOKEN_LIFETIME_MILIS / 1000,
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .body(serde_json::to_string(&response).unwrap()))
+}
+
+#[post("/logout")]
+pub async fn logout(conn: Data<DbConn>, request: HttpRequest) -> Result<HttpResponse> {
+    let Some(token) = bearer_token(&request) else {
+        return Err(unauthorized());
+    };
+
+    let revoked = revoke_access_token(&conn.db, &token).await.unwrap();
+
+    if !revoked {
+        return Err(unauthorized());
+    }
+
+    Ok(HttpResponse::Ok().body("OK"))
+}
@@ -0,0 +1,75 @@
+/// escapes text for use in html bodies and quoted attributes
+pub fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// turns plain text from a client into html content, blank lines
+/// separate paragraphs and single newlines become line breaks
+pub fn text_to_html(input: &str) -> String {
+    input
+        .replace("\r\n", "\n")
+        .split("\n\n")
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| format!("<p>{}</p>", escape_html(x.trim()).replace('\n', "<br>")))
+        .collect()
+}
+
+/// handles mentioned in plain text from a client like `@user` or `@user@domain`,
+/// returned without the leading `@` in the order they first appear
+pub fn find_mentions(input: &str) -> Vec<String> {
+    let is_username = |x: char| x.is_ascii_alphanumeric() || x == '_';
+    let is_domain = |x: char| x.is_ascii_alphanumeric() || x == '.' || x == '-';
+
+    let mut mentions: Vec<String> = Vec::new();
+    for word in input.split(|x: char| !(is_username(x) || is_domain(x) || x == '@')) {
+        //a trailing . or - is punctuation after the mention
+        let Some(handle) = word.trim_end_matches(['.', '-']).strip_prefix('@') else {
+            continue;
+        };
+        let (username, domain) = match handle.split_once('@') {
+            Some((username, domain)) => (username, Some(domain)),
+            None => (handle, None),
+        };
+        if username.is_empty() || !username.chars().all(is_username) {
+            continue;
+        }
+        if domain.is_some_and(|x| x.is_empty() || !x.chars().all(is_domain)) {
+            continue;
+        }
+        if !mentions.iter().any(|x| x.eq_ignore_ascii_case(handle)) {
+            mentions.push(handle.to_string());
+        }
+    }
+    mentions
+}
+
+/// the reverse of [`text_to_html`], good enough for content we made ourselves
+pub fn html_to_text(input: &str) -> String {
+    let input = input
+        .replace("</p><p>", "\n\n")
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n");
+
+    let mut text = String::with_capacity(input.len());
+    let mut in_tag = false;
+    for c in input.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            x if !in_tag => text.push(x),
+            _ => {}
+        }
+    }
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
@@ -13,7 +13,17 @@ use actix_web::{
     Result,
 };
 
-use crate::{cache_and_fetch::Cache, db::conn::DbConn, protocol::verification::verify_incoming};
+use crate::{
+    api::authentication::AuthenticatedUser,
+    cache_and_fetch::Cache,
+    db::{
+        conn::DbConn,
+        internal_actor::{get_actor_id_from_internal, is_suspended},
+        objects::{get_object_by_db_id, DbObject},
+        timelines::{count_home_timeline, get_home_timeline, Page},
+    },
+    protocol::{inbox::handle_incoming, verification::verify_incoming},
+};
 pub struct Inbox {
     pub inbox: Mutex<Vec<String>>,
 }
@@ -52,6 +62,8 @@ pub async fn shared_inbox(
         Ok(x) => {
             println!("{}", &x);
 
+            handle_incoming(&conn, &cache, &x).await;
+
             let mut guard = inbox.inbox.lock().unwrap();
             let data = &mut *guard;
             data.push(x);
@@ -92,7 +104,10 @@ pub async fn private_inbox(
     // let x = request.cookie("example");
 
     // dbg!(&request);
-    
+
+    if is_suspended(&conn.db, &preferred_username).await.unwrap() {
+        return Ok(HttpResponse::Forbidden().body("account is suspended"));
+    }
 
     let x = verify_incoming(&cache, &conn, request, body, &path, &state.instance_domain).await;
 
@@ -102,6 +117,8 @@ pub async fn private_inbox(
         Ok(x) => {
             println!("{}", &x);
 
+            handle_incoming(&conn, &cache, &x).await;
+
             let mut guard = inbox.inbox.lock().unwrap();
             let data = &mut *guard;
             data.push(x);
@@ -116,3 +133,95 @@ pub async fn private_inbox(
         }
     }
 }
+
+#[derive(serde::Deserialize, Debug)]
+pub struct InboxQuery {
+    pub page: Option<bool>,
+    pub max_id: Option<i64>,
+    pub min_id: Option<i64>,
+}
+
+/// the owner's home timeline as an OrderedCollection of create activities
+///
+/// https://www.w3.org/TR/activitypub/#inbox
+#[get("/users/{preferred_username}/inbox")]
+pub async fn inbox_collection(
+    path: web::Path<String>,
+    query: web::Query<InboxQuery>,
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let preferred_username = path.into_inner();
+    user.require_user(&preferred_username)?;
+    user.require_scope("read:statuses")?;
+
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &preferred_username)
+        .await
+        .unwrap()
+        .expect("token belongs to a user without an actor");
+
+    let inbox = format!(
+        "https://{}/users/{}/inbox",
+        &state.instance_domain, &preferred_username
+    );
+
+    if !query.page.unwrap_or(false) {
+        let total_items = count_home_timeline(&conn.db, ap_user_id).await.unwrap();
+        let collection = serde_json::json!({
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "id": &inbox,
+            "type": "OrderedCollection",
+            "totalItems": total_items,
+            "first": format!("{}?page=true", &inbox),
+            "last": format!("{}?page=true&min_id=0", &inbox),
+        });
+        return Ok(HttpResponse::Ok()
+            .content_type("application/activity+json; charset=utf-8")
+            .body(collection.to_string()));
+    }
+
+    let page = Page::new(query.max_id, None, query.min_id, None);
+    let ids = get_home_timeline(&conn.db, ap_user_id, &page)
+        .await
+        .unwrap();
+
+    let mut items = Vec::with_capacity(ids.len());
+    for obj_id in &ids {
+        let Some(DbObject::Object(object)) =
+            get_object_by_db_id(*obj_id, conn.db.begin().await.unwrap()).await
+        else {
+            continue;
+        };
+        let mut activity = serde_json::to_value(object.to_create_activitystream()).unwrap();
+        activity.as_object_mut().unwrap().remove("@context");
+        items.push(activity);
+    }
+
+    let mut collection_page = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}?{}", &inbox, query_string(&query)),
+        "type": "OrderedCollectionPage",
+        "partOf": &inbox,
+        "orderedItems": items,
+    });
+    if let (Some(first), Some(last)) = (ids.first(), ids.last()) {
+        collection_page["next"] = format!("{}?page=true&max_id={}", &inbox, last).into();
+        collection_page["prev"] = format!("{}?page=true&min_id={}", &inbox, first).into();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/activity+json; charset=utf-8")
+        .body(collection_page.to_string()))
+}
+
+fn query_string(query: &InboxQuery) -> String {
+    let mut out = "page=true".to_string();
+    if let Some(x) = query.max_id {
+        out.push_str(&format!("&max_id={}", x));
+    }
+    if let Some(x) = query.min_id {
+        out.push_str(&format!("&min_id={}", x));
+    }
+    out
+}
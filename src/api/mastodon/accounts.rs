@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+
+use actix_multipart::Multipart;
+use actix_web::{
+    error::{ErrorNotFound, ErrorUnprocessableEntity},
+    get, patch, post,
+    web::{self, Data},
+    Either, HttpResponse, Result,
+};
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use crate::{
+    activitystream_objects::{
+        activities::ActivityType,
+        actors::{Actor, ActorAttachment, ActorType, PropertyValue},
+        core_types::SimpleLinkOrArray,
+        object::{Document, MediaType, ObjectType},
+    },
+    api::{
+        authentication::AuthenticatedUser,
+        html::{escape_html, html_to_text, text_to_html},
+        media::{media_url, read_multipart, store_upload, MultipartForm},
+    },
+    cache_and_fetch::{fetch_actor, resolve_handle, Cache},
+    db::{
+        activities::get_latest_activity,
+        actor_utilities::{get_ap_actor_by_db_id, get_ap_actor_id_by_fedi_id},
+        conn::DbConn,
+        following::count_pending_followers,
+        internal_actor::{get_actor_id_from_internal, verify_password},
+    },
+    protocol::outbox::{post_outbox, profile_update},
+};
+
+use super::{
+    entities::{account_from_db, relationship_from_db, Account, AccountSource, CredentialAccount},
+    json_or_form, json_response, outbox_error,
+};
+
+/// profiles can't have more fields than this
+pub const MAX_PROFILE_FIELDS: usize = 4;
+
+async fn credential_account(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    ap_user_id: i64,
+) -> Result<CredentialAccount> {
+    let account = account_from_db(conn, cache, ap_user_id).await?.unwrap();
+    let actor = get_ap_actor_by_db_id(ap_user_id, conn).await;
+    let follow_requests_count = count_pending_followers(&conn.db, actor.id.as_str())
+        .await
+        .unwrap();
+
+    let fields = account
+        .fields
+        .iter()
+        .map(|x| {
+            json!({
+                "name": x["name"],
+                "value": html_to_text(x["value"].as_str().unwrap_or_default()),
+            })
+        })
+        .collect();
+
+    Ok(CredentialAccount {
+        source: AccountSource {
+            note: html_to_text(&account.note),
+            fields,
+            privacy: "public".to_string(),
+            sensitive: false,
+            language: String::new(),
+            follow_requests_count,
+        },
+        account,
+    })
+}
+
+#[get("/api/v1/accounts/verify_credentials")]
+pub async fn verify_credentials(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    user.require_scope("read:accounts")?;
+
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap()
+        .expect("token belongs to a user without an actor");
+
+    Ok(json_response(
+        &credential_account(&conn, &cache, ap_user_id).await?,
+    ))
+}
+
+/// mastodon sends booleans in forms as strings
+fn parse_bool(value: &str) -> bool {
+    matches!(value, "true" | "1" | "on")
+}
+
+/// profile fields come in as `fields_attributes[0][name]` and `fields_attributes[0][value]`
+fn parse_fields(text: &HashMap<String, String>) -> Option<Vec<(String, String)>> {
+    let mut found = false;
+    let mut fields = Vec::new();
+    for i in 0..MAX_PROFILE_FIELDS {
+        let name = text.get(&format!("fields_attributes[{i}][name]"));
+        let value = text.get(&format!("fields_attributes[{i}][value]"));
+        if name.is_some() || value.is_some() {
+            found = true;
+        }
+        let name = name.map(|x| x.trim()).unwrap_or_default();
+        let value = value.map(|x| x.trim()).unwrap_or_default();
+        if !name.is_empty() || !value.is_empty() {
+            fields.push((name.to_string(), value.to_string()));
+        }
+    }
+    found.then_some(fields)
+}
+
+#[patch("/api/v1/accounts/update_credentials")]
+pub async fn update_credentials(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+    form: Either<Multipart, web::Form<Vec<(String, String)>>>,
+) -> Result<HttpResponse> {
+    user.require_scope("write:accounts")?;
+
+    let mut form = match form {
+        Either::Left(x) => {
+            read_multipart(x, cache.state.max_upload_size, &["avatar", "header"]).await?
+        }
+        Either::Right(x) => MultipartForm {
+            text: x.into_inner().into_iter().collect(),
+            files: HashMap::new(),
+        },
+    };
+
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap()
+        .expect("token belongs to a user without an actor");
+    let mut actor = get_ap_actor_by_db_id(ap_user_id, &conn).await;
+
+    if let Some(x) = form.text.get("display_name") {
+        actor.name = Some(x.trim().to_string()).filter(|x| !x.is_empty());
+    }
+    if let Some(x) = form.text.get("note") {
+        actor.summary = Some(text_to_html(x)).filter(|x| !x.is_empty());
+    }
+    if let Some(x) = form.text.get("locked") {
+        actor.manually_approves_followers = Some(parse_bool(x));
+    }
+    if let Some(x) = form.text.get("bot") {
+        actor.type_field = match parse_bool(x) {
+            true => ActorType::Service,
+            false => ActorType::Person,
+        };
+    }
+    if let Some(x) = form.text.get("discoverable") {
+        actor.discoverable = Some(parse_bool(x));
+    }
+    if let Some(x) = form.text.get("indexable") {
+        actor.indexable = Some(parse_bool(x));
+    }
+    if let Some(fields) = parse_fields(&form.text) {
+        actor.attachment = Some(
+            fields
+                .into_iter()
+                .map(|(name, value)| {
+                    ActorAttachment::PropertyValue(PropertyValue::new(
+                        escape_html(&name),
+                        escape_html(&value),
+                    ))
+                })
+                .collect(),
+        )
+        .filter(|x: &Vec<ActorAttachment>| !x.is_empty());
+    }
+
+    for field in ["avatar", "header"] {
+        let Some((media_type, data)) = form.files.remove(field) else {
+            continue;
+        };
+        if !media_type.starts_with("image/") {
+            return Err(ErrorUnprocessableEntity(
+                r#"{"error":"Validation failed: Image content type is invalid"}"#,
+            ));
+        }
+        let file = store_upload(&conn, &cache, Some(ap_user_id), &media_type, data, None).await?;
+        let image = Document {
+            type_field: ObjectType::Image,
+            media_type: Some(MediaType::Other(file.media_type.clone())),
+            url: media_url(&cache.state.instance_domain, file.file_id),
+            name: None,
+            blurhash: file.blurhash.clone(),
+            width: None,
+            height: None,
+            preview: None,
+        };
+        match field {
+            "avatar" => actor.icon = Some(image),
+            _ => actor.image = Some(image),
+        }
+    }
+
+    let activity = profile_update(&actor);
+
+    if let Err(x) = post_outbox(&conn, &cache, user.uid, &user.preferred_username, activity).await {
+        return Err(outbox_error(x));
+    }
+
+    Ok(json_response(
+        &credential_account(&conn, &cache, ap_user_id).await?,
+    ))
+}
+
+#[get("/api/v1/accounts/{id}")]
+pub async fn get_account(
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+) -> Result<HttpResponse> {
+    let ap_user_id = path.into_inner();
+
+    let Some(account) = account_from_db(&conn, &cache, ap_user_id).await? else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+
+    Ok(json_response(&account))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LookupQuery {
+    /// `username` for local accounts, `username@domain` for remote ones
+    pub acct: String,
+}
+
+/// finds an account by its handle, remote accounts we haven't seen yet are looked up with webfinger
+#[get("/api/v1/accounts/lookup")]
+pub async fn lookup_account(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    query: web::Query<LookupQuery>,
+) -> Result<HttpResponse> {
+    let Ok(actor) = resolve_handle(&query.acct, &cache, &conn).await else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+    let Some(ap_user_id) = get_ap_actor_id_by_fedi_id(&conn.db, actor.id.as_str())
+        .await
+        .unwrap()
+    else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+    let Some(account) = account_from_db(&conn, &cache, ap_user_id).await? else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+
+    Ok(json_response(&account))
+}
+
+#[get("/api/v1/accounts/relationships")]
+pub async fn relationships(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+    query: web::Query<Vec<(String, String)>>,
+) -> Result<HttpResponse> {
+    user.require_scope("read:follows")?;
+
+    let user_id = format!(
+        "https://{}/users/{}",
+        cache.state.instance_domain, user.preferred_username
+    );
+
+    //serde_urlencoded can't put repeated keys in a Vec so collect them by hand
+    let ids = query
+        .iter()
+        .filter(|(key, _)| key.eq("id[]") || key.eq("id"))
+        .filter_map(|(_, value)| value.parse::<i64>().ok());
+
+    let mut relationships = Vec::new();
+    for ap_user_id in ids {
+        if let Some(x) = relationship_from_db(&conn, &user_id, ap_user_id).await? {
+            relationships.push(x);
+        }
+    }
+
+    Ok(json_response(&relationships))
+}
+
+/// follows are sent through the outbox, remote accounts and locked local ones
+/// show up as requested until they accept
+#[post("/api/v1/accounts/{id}/follow")]
+pub async fn follow_account(
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    user.require_scope("write:follows")?;
+    let ap_user_id = path.into_inner();
+
+    let user_id = format!(
+        "https://{}/users/{}",
+        cache.state.instance_domain, user.preferred_username
+    );
+    let Some(relationship) = relationship_from_db(&conn, &user_id, ap_user_id).await? else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+    if relationship.following || relationship.requested {
+        return Ok(json_response(&relationship));
+    }
+
+    let target = get_ap_actor_by_db_id(ap_user_id, &conn).await;
+    let activity = json!({
+        "type": "Follow",
+        "actor": &user_id,
+        "object": target.id.as_str(),
+    });
+    if let Err(x) = post_outbox(&conn, &cache, user.uid, &user.preferred_username, activity).await {
+        return Err(outbox_error(x));
+    }
+
+    let relationship = relationship_from_db(&conn, &user_id, ap_user_id)
+        .await?
+        .unwrap();
+    Ok(json_response(&relationship))
+}
+
+/// undoes the newest follow of the account, which also withdraws a pending request
+#[post("/api/v1/accounts/{id}/unfollow")]
+pub async fn unfollow_account(
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    user.require_scope("write:follows")?;
+    let ap_user_id = path.into_inner();
+
+    let user_id = format!(
+        "https://{}/users/{}",
+        cache.state.instance_domain, user.preferred_username
+    );
+    let Some(relationship) = relationship_from_db(&conn, &user_id, ap_user_id).await? else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+    if !relationship.following && !relationship.requested {
+        return Ok(json_response(&relationship));
+    }
+
+    let target = get_ap_actor_by_db_id(ap_user_id, &conn).await;
+    let follow_type = serde_json::to_string(&ActivityType::Follow).unwrap();
+    let follow = get_latest_activity(&conn.db, &follow_type, &user_id, target.id.as_str())
+        .await
+        .unwrap();
+    let Some(follow_id) = follow.and_then(|x| x.id) else {
+        return Err(ErrorUnprocessableEntity(r#"{"error":"No follow to undo"}"#));
+    };
+
+    let activity = json!({
+        "type": "Undo",
+        "actor": &user_id,
+        "object": follow_id,
+    });
+    if let Err(x) = post_outbox(&conn, &cache, user.uid, &user.preferred_username, activity).await {
+        return Err(outbox_error(x));
+    }
+
+    let relationship = relationship_from_db(&conn, &user_id, ap_user_id)
+        .await?
+        .unwrap();
+    Ok(json_response(&relationship))
+}
+
+/// finds an actor by its id or a handle like `user@domain`
+async fn resolve_account(value: &str, conn: &Data<DbConn>, cache: &Cache) -> Option<Actor> {
+    let value = value.trim();
+    match Url::parse(value) {
+        Ok(x) if x.scheme().eq("https") => fetch_actor(&x, cache, conn).await.ok(),
+        _ => resolve_handle(value, cache, conn).await.ok(),
+    }
+}
+
+/// the accounts behind a list of actor ids, ones we don't know are skipped
+async fn accounts_by_id(conn: &Data<DbConn>, cache: &Cache, ids: &[Url]) -> Result<Vec<Account>> {
+    let mut accounts = Vec::with_capacity(ids.len());
+    for id in ids {
+        let Some(ap_user_id) = get_ap_actor_id_by_fedi_id(&conn.db, id.as_str())
+            .await
+            .unwrap()
+        else {
+            continue;
+        };
+        if let Some(x) = account_from_db(conn, cache, ap_user_id).await? {
+            accounts.push(x);
+        }
+    }
+    Ok(accounts)
+}
+
+/// the other accounts the user says are theirs, which they can move to or from
+#[get("/api/v1/accounts/aliases")]
+pub async fn aliases(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    user.require_scope("read:accounts")?;
+
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap()
+        .expect("token belongs to a user without an actor");
+    let actor = get_ap_actor_by_db_id(ap_user_id, &conn).await;
+    let ids = actor.also_known_as.map(|x| x.to_vec()).unwrap_or_default();
+
+    Ok(json_response(&accounts_by_id(&conn, &cache, &ids).await?))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AliasesForm {
+    /// handles or actor ids, these replace the current aliases
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+/// replaces the user's aliases and sends their profile out again. an account moving
+/// here has to be listed first so other servers accept the move
+#[post("/api/v1/accounts/aliases")]
+pub async fn set_aliases(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+    form: Either<web::Json<AliasesForm>, web::Form<Vec<(String, String)>>>,
+) -> Result<HttpResponse> {
+    user.require_scope("write:accounts")?;
+
+    let values = match form {
+        Either::Left(x) => x.into_inner().aliases,
+        //serde_urlencoded can't put repeated keys in a Vec so collect them by hand
+        Either::Right(x) => x
+            .into_inner()
+            .into_iter()
+            .filter(|(key, _)| key.eq("aliases[]") || key.eq("aliases"))
+            .map(|(_, value)| value)
+            .collect(),
+    };
+
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap()
+        .expect("token belongs to a user without an actor");
+    let mut actor = get_ap_actor_by_db_id(ap_user_id, &conn).await;
+
+    let mut ids: Vec<Url> = Vec::with_capacity(values.len());
+    for value in values.iter().filter(|x| !x.trim().is_empty()) {
+        let Some(alias) = resolve_account(value, &conn, &cache).await else {
+            return Err(ErrorUnprocessableEntity(
+                json!({ "error": format!("Validation failed: Can't find {}", value.trim()) })
+                    .to_string(),
+            ));
+        };
+        if alias.id.eq(&actor.id) {
+            return Err(ErrorUnprocessableEntity(
+                r#"{"error":"Validation failed: An account can't be an alias of itself"}"#,
+            ));
+        }
+        if !ids.contains(&alias.id) {
+            ids.push(alias.id);
+        }
+    }
+
+    actor.also_known_as =
+        Some(SimpleLinkOrArray::Multiple(ids.clone())).filter(|_| !ids.is_empty());
+    let activity = profile_update(&actor);
+    if let Err(x) = post_outbox(&conn, &cache, user.uid, &user.preferred_username, activity).await {
+        return Err(outbox_error(x));
+    }
+
+    Ok(json_response(&accounts_by_id(&conn, &cache, &ids).await?))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MoveForm {
+    /// a handle or actor id of the account to move to
+    pub target: String,
+    pub current_password: String,
+}
+
+/// moves the user to another account. the target has to list the user as an alias,
+/// then a `Move` goes to the followers so their servers follow the new account
+///
+/// https://docs.joinmastodon.org/spec/activitypub/#Move
+#[post("/api/v1/accounts/move")]
+pub async fn move_account(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+    form: Either<web::Json<MoveForm>, web::Form<MoveForm>>,
+) -> Result<HttpResponse> {
+    user.require_scope("write:accounts")?;
+    let form = json_or_form(form);
+
+    let valid = verify_password(&conn.db, &user.preferred_username, &form.current_password)
+        .await
+        .unwrap();
+    if valid.is_none() {
+        return Err(ErrorUnprocessableEntity(
+            r#"{"error":"Validation failed: Current password is invalid"}"#,
+        ));
+    }
+    let Some(target) = resolve_account(&form.target, &conn, &cache).await else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+
+    let user_id = format!(
+        "https://{}/users/{}",
+        cache.state.instance_domain, user.preferred_username
+    );
+    let activity = json!({
+        "type": "Move",
+        "actor": &user_id,
+        "object": &user_id,
+        "target": target.id.as_str(),
+        "to": [format!("{}/followers", &user_id)],
+    });
+    if let Err(x) = post_outbox(&conn, &cache, user.uid, &user.preferred_username, activity).await {
+        return Err(outbox_error(x));
+    }
+
+    //the profile goes out again so servers that missed the move still see movedTo
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap()
+        .expect("token belongs to a user without an actor");
+    let actor = get_ap_actor_by_db_id(ap_user_id, &conn).await;
+    let activity = profile_update(&actor);
+    if let Err(x) = post_outbox(&conn, &cache, user.uid, &user.preferred_username, activity).await {
+        return Err(outbox_error(x));
+    }
+
+    Ok(json_response(
+        &credential_account(&conn, &cache, ap_user_id).await?,
+    ))
+}
@@ -0,0 +1,580 @@
+use actix_web::{error::ErrorInternalServerError, web::Data, Result};
+use chrono::{DateTime, SecondsFormat};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    activitystream_objects::{
+        actors::{ActorAttachment, ActorType},
+        link::{LinkSimpleOrExpanded, LinkType},
+        object::{Document, MediaType, ObjectType},
+    },
+    api::media::{media_preview_url, media_url},
+    cache_and_fetch::Cache,
+    db::{
+        actor_utilities::{get_actor_stats, get_ap_actor_by_db_id, get_ap_actor_id_by_fedi_id},
+        conn::DbConn,
+        files::{get_object_files, MediaFile},
+        following::{get_follow_request, is_following},
+        internal_actor::is_actor_suspended,
+        objects::{get_object_by_db_id, get_object_meta, get_object_meta_by_fedi_id, DbObject},
+    },
+    media::{processing::attachment_type, proxy::proxy_url},
+};
+
+pub fn milis_to_iso(milis: i64) -> String {
+    DateTime::from_timestamp_millis(milis)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// https://docs.joinmastodon.org/entities/Account/
+#[derive(Serialize, Debug, Clone)]
+pub struct Account {
+    pub id: String,
+    pub username: String,
+    /// the username for local accounts and username@domain for remote ones
+    pub acct: String,
+    pub display_name: String,
+    pub locked: bool,
+    pub bot: bool,
+    pub discoverable: Option<bool>,
+    pub group: bool,
+    pub created_at: String,
+    pub note: String,
+    pub url: String,
+    pub avatar: String,
+    pub avatar_static: String,
+    pub header: String,
+    pub header_static: String,
+    pub followers_count: i64,
+    pub following_count: i64,
+    pub statuses_count: i64,
+    pub last_status_at: Option<String>,
+    pub emojis: Vec<serde_json::Value>,
+    pub fields: Vec<serde_json::Value>,
+}
+
+/// https://docs.joinmastodon.org/entities/Account/#CredentialAccount
+#[derive(Serialize, Debug, Clone)]
+pub struct CredentialAccount {
+    #[serde(flatten)]
+    pub account: Account,
+    pub source: AccountSource,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AccountSource {
+    pub note: String,
+    pub fields: Vec<serde_json::Value>,
+    pub privacy: String,
+    pub sensitive: bool,
+    pub language: String,
+    pub follow_requests_count: i64,
+}
+
+/// an invite a local user made, mastodon only has these in its web ui
+#[derive(Serialize, Debug, Clone)]
+pub struct Invite {
+    pub code: String,
+    pub uses: i32,
+    pub max_uses: Option<i32>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+}
+
+pub fn invite_from_db(invite: crate::db::invites::Invite) -> Invite {
+    Invite {
+        code: invite.code,
+        uses: invite.uses,
+        max_uses: invite.max_uses,
+        created_at: milis_to_iso(invite.created),
+        expires_at: invite.expires.map(milis_to_iso),
+    }
+}
+
+/// https://docs.joinmastodon.org/entities/Relationship/
+#[derive(Serialize, Debug, Clone)]
+pub struct Relationship {
+    pub id: String,
+    pub following: bool,
+    pub showing_reblogs: bool,
+    pub notifying: bool,
+    pub languages: Vec<String>,
+    pub followed_by: bool,
+    pub blocking: bool,
+    pub blocked_by: bool,
+    pub muting: bool,
+    pub muting_notifications: bool,
+    /// `user_id` is waiting on the account to accept their follow
+    pub requested: bool,
+    /// the account is waiting on `user_id` to accept their follow
+    pub requested_by: bool,
+    pub domain_blocking: bool,
+    pub endorsed: bool,
+    pub note: String,
+}
+
+/// https://docs.joinmastodon.org/entities/Status/
+#[derive(Serialize, Debug, Clone)]
+pub struct Status {
+    pub id: String,
+    pub uri: String,
+    pub url: Option<String>,
+    pub created_at: String,
+    pub account: Account,
+    pub content: String,
+    pub visibility: String,
+    pub sensitive: bool,
+    pub spoiler_text: String,
+    pub media_attachments: Vec<MediaAttachment>,
+    pub mentions: Vec<serde_json::Value>,
+    pub tags: Vec<serde_json::Value>,
+    pub emojis: Vec<serde_json::Value>,
+    pub reblogs_count: i64,
+    pub favourites_count: i64,
+    pub replies_count: i64,
+    pub in_reply_to_id: Option<String>,
+    pub in_reply_to_account_id: Option<String>,
+    pub reblog: Option<Box<Status>>,
+    pub application: Option<serde_json::Value>,
+    pub language: Option<String>,
+    pub poll: Option<serde_json::Value>,
+    pub card: Option<serde_json::Value>,
+    pub edited_at: Option<String>,
+}
+
+/// https://docs.joinmastodon.org/entities/MediaAttachment/
+#[derive(Serialize, Debug, Clone)]
+pub struct MediaAttachment {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub url: String,
+    pub preview_url: Option<String>,
+    /// set for media that lives on another server
+    pub remote_url: Option<String>,
+    pub meta: serde_json::Value,
+    pub description: Option<String>,
+    pub blurhash: Option<String>,
+}
+
+fn media_dimensions(width: Option<u32>, height: Option<u32>) -> Option<serde_json::Value> {
+    let (Some(width), Some(height)) = (width, height) else {
+        return None;
+    };
+    Some(json!({
+        "width": width,
+        "height": height,
+        "size": format!("{width}x{height}"),
+        "aspect": width as f64 / height.max(1) as f64,
+    }))
+}
+
+/// `original` is the full size and `small` the preview
+fn media_meta(
+    original: Option<serde_json::Value>,
+    small: Option<serde_json::Value>,
+) -> serde_json::Value {
+    let mut meta = serde_json::Map::new();
+    if let Some(x) = original {
+        meta.insert("original".to_string(), x);
+    }
+    if let Some(x) = small {
+        meta.insert("small".to_string(), x);
+    }
+    serde_json::Value::Object(meta)
+}
+
+/// an attachment for a file uploaded to this server
+pub fn media_attachment_from_file(file: &MediaFile, local_domain: &str) -> MediaAttachment {
+    let url = media_url(local_domain, file.file_id).to_string();
+    let preview_url = match file.preview_key {
+        Some(_) => media_preview_url(local_domain, file.file_id).to_string(),
+        None => url.clone(),
+    };
+    MediaAttachment {
+        id: file.file_id.to_string(),
+        type_field: attachment_type(&file.media_type).to_string(),
+        preview_url: Some(preview_url),
+        url,
+        remote_url: None,
+        meta: media_meta(
+            media_dimensions(file.width.map(|x| x as u32), file.height.map(|x| x as u32)),
+            media_dimensions(
+                file.preview_width.map(|x| x as u32),
+                file.preview_height.map(|x| x as u32),
+            ),
+        ),
+        description: file.description.clone(),
+        blurhash: file.blurhash.clone(),
+    }
+}
+
+/// an attachment for a document on a remote object, `id` just has to be unique within the status.
+/// clients load it through the media proxy so they don't talk to the remote server
+fn media_attachment_from_document(
+    cache: &Cache,
+    id: String,
+    document: &Document,
+) -> MediaAttachment {
+    let proxied = proxy_url(cache, &document.url).to_string();
+    let preview_url = match &document.preview {
+        Some(x) => proxy_url(cache, &x.href).to_string(),
+        None => proxied.clone(),
+    };
+    let media_type = match &document.media_type {
+        Some(MediaType::Other(x)) => x.as_str(),
+        _ => "",
+    };
+    //fall back on the activitypub type when there's no media type to go off
+    let type_field = match attachment_type(media_type) {
+        "unknown" => match document.type_field {
+            ObjectType::Image => "image",
+            ObjectType::Video => "video",
+            ObjectType::Audio => "audio",
+            _ => "unknown",
+        },
+        x => x,
+    };
+    MediaAttachment {
+        id,
+        type_field: type_field.to_string(),
+        url: proxied.clone(),
+        preview_url: Some(preview_url),
+        remote_url: Some(document.url.to_string()),
+        meta: media_meta(
+            media_dimensions(document.width, document.height),
+            document
+                .preview
+                .as_ref()
+                .and_then(|x| media_dimensions(x.width, x.height)),
+        ),
+        description: document.name.clone(),
+        blurhash: document.blurhash.clone(),
+    }
+}
+
+/// builds an account from `activitypub_users`, none if the actor doesn't exist
+pub async fn account_from_db(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    ap_user_id: i64,
+) -> Result<Option<Account>> {
+    let local_domain = cache.state.instance_domain.as_str();
+    //suspended accounts are hidden along with their statuses
+    if is_actor_suspended(&conn.db, ap_user_id).await.unwrap() {
+        return Ok(None);
+    }
+    let stats = match get_actor_stats(&conn.db, ap_user_id).await {
+        Ok(x) => x,
+        Err(sqlx::Error::RowNotFound) => return Ok(None),
+        Err(x) => {
+            eprintln!("failed to get actor stats: {}", x);
+            return Err(ErrorInternalServerError(
+                r#"{"error":"Internal Server Error"}"#,
+            ));
+        }
+    };
+    let actor = get_ap_actor_by_db_id(ap_user_id, conn).await;
+
+    let domain = actor.domain.clone().unwrap_or_default();
+    let local = domain.eq_ignore_ascii_case(local_domain);
+
+    let (acct, url) = match local {
+        true => (
+            actor.preferred_username.clone(),
+            format!("https://{}/@{}", local_domain, &actor.preferred_username),
+        ),
+        false => (
+            format!("{}@{}", &actor.preferred_username, domain),
+            actor
+                .url
+                .as_ref()
+                .and_then(|x| x.to_vec().first().map(|x| x.get_id().to_string()))
+                .unwrap_or(actor.id.to_string()),
+        ),
+    };
+
+    //remote images go through the media proxy
+    let image_url = |image: &Option<Document>| match image {
+        Some(x) if local => x.url.to_string(),
+        Some(x) => proxy_url(cache, &x.url).to_string(),
+        None => String::new(),
+    };
+    let avatar = image_url(&actor.icon);
+    let header = image_url(&actor.image);
+
+    let fields = actor
+        .attachment
+        .iter()
+        .flatten()
+        .filter_map(|x| match x {
+            ActorAttachment::PropertyValue(x) => Some(json!({
+                "name": x.name,
+                "value": x.value,
+                "verified_at": null,
+            })),
+            ActorAttachment::Other(_) => None,
+        })
+        .collect();
+
+    Ok(Some(Account {
+        id: ap_user_id.to_string(),
+        username: actor.preferred_username.clone(),
+        acct,
+        display_name: actor.name.unwrap_or_default(),
+        locked: actor.manually_approves_followers.unwrap_or(false),
+        bot: matches!(
+            actor.type_field,
+            ActorType::Application | ActorType::Service
+        ),
+        discoverable: actor.discoverable,
+        group: matches!(actor.type_field, ActorType::Group),
+        created_at: milis_to_iso(stats.created_at),
+        note: actor.summary.unwrap_or_default(),
+        url,
+        avatar_static: avatar.clone(),
+        avatar,
+        header_static: header.clone(),
+        header,
+        followers_count: stats.followers_count,
+        following_count: stats.following_count,
+        statuses_count: stats.statuses_count,
+        last_status_at: stats
+            .last_status_at
+            .map(|x| milis_to_iso(x)[..10].to_string()),
+        emojis: Vec::new(),
+        fields,
+    }))
+}
+
+/// the relationship from the actor `user_id` to the account `ap_user_id`
+pub async fn relationship_from_db(
+    conn: &Data<DbConn>,
+    user_id: &str,
+    ap_user_id: i64,
+) -> Result<Option<Relationship>> {
+    match get_actor_stats(&conn.db, ap_user_id).await {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => return Ok(None),
+        Err(x) => {
+            eprintln!("failed to get actor stats: {}", x);
+            return Err(ErrorInternalServerError(
+                r#"{"error":"Internal Server Error"}"#,
+            ));
+        }
+    };
+    let actor = get_ap_actor_by_db_id(ap_user_id, conn).await;
+    let target = actor.id.as_str();
+
+    let following = is_following(&conn.db, user_id, target).await.unwrap();
+    Ok(Some(Relationship {
+        id: ap_user_id.to_string(),
+        following,
+        showing_reblogs: following,
+        notifying: false,
+        languages: Vec::new(),
+        followed_by: is_following(&conn.db, target, user_id).await.unwrap(),
+        blocking: false,
+        blocked_by: false,
+        muting: false,
+        muting_notifications: false,
+        requested: get_follow_request(&conn.db, user_id, target)
+            .await
+            .unwrap()
+            .is_some(),
+        requested_by: get_follow_request(&conn.db, target, user_id)
+            .await
+            .unwrap()
+            .is_some(),
+        domain_blocking: false,
+        endorsed: false,
+        note: String::new(),
+    }))
+}
+
+/// builds a status for an object, none if it doesn't exist or isn't a plain object
+pub async fn status_from_db(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    obj_id: i64,
+) -> Result<Option<Status>> {
+    let local_domain = cache.state.instance_domain.as_str();
+    let Some(meta) = get_object_meta(&conn.db, obj_id).await.unwrap() else {
+        return Ok(None);
+    };
+
+    let object = get_object_by_db_id(obj_id, conn.db.begin().await.unwrap()).await;
+    let Some(DbObject::Object(object)) = object else {
+        return Ok(None);
+    };
+    let object = object.object;
+
+    let Some(account) = account_from_db(conn, cache, meta.ap_user_id).await? else {
+        return Ok(None);
+    };
+
+    let reply = match &object.in_reply_to {
+        Some(x) => get_object_meta_by_fedi_id(&conn.db, x.get_id().as_str())
+            .await
+            .unwrap(),
+        None => None,
+    };
+
+    let local = meta.domain.eq_ignore_ascii_case(local_domain);
+
+    let url = match local {
+        true => Some(format!("{}/{}", &account.url, obj_id)),
+        false => Some(object.id.as_str().to_string()),
+    };
+
+    let mut mentions = Vec::new();
+    let mut tags = Vec::new();
+    for tag in object.tag.as_ref().map(|x| x.to_vec()).unwrap_or_default() {
+        let LinkSimpleOrExpanded::Expanded(tag) = tag else {
+            continue;
+        };
+        match tag.type_field {
+            LinkType::Mention => {
+                let Some(ap_user_id) = get_ap_actor_id_by_fedi_id(&conn.db, tag.href.as_str())
+                    .await
+                    .unwrap()
+                else {
+                    continue;
+                };
+                if let Some(x) = account_from_db(conn, cache, ap_user_id).await? {
+                    mentions.push(json!({
+                        "id": x.id,
+                        "username": x.username,
+                        "url": x.url,
+                        "acct": x.acct,
+                    }));
+                }
+            }
+            LinkType::Hashtag => {
+                let name = tag.name.unwrap_or_default();
+                tags.push(json!({
+                    "name": name.trim_start_matches('#').to_lowercase(),
+                    "url": tag.href.as_str(),
+                }));
+            }
+            LinkType::Link => {}
+        }
+    }
+
+    let media_attachments = match local {
+        true => get_object_files(&conn.db, obj_id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|x| media_attachment_from_file(x, local_domain))
+            .collect(),
+        false => object
+            .attachment
+            .as_ref()
+            .map(|x| x.to_vec())
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(i, x)| media_attachment_from_document(cache, format!("{obj_id}-{i}"), x))
+            .collect(),
+    };
+
+    Ok(Some(Status {
+        id: obj_id.to_string(),
+        uri: object.id.as_str().to_string(),
+        url,
+        created_at: milis_to_iso(meta.published),
+        account,
+        content: object.content.unwrap_or_default(),
+        visibility: serde_json::to_value(meta.visibility)
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string(),
+        sensitive: object.sensitive.unwrap_or(false),
+        spoiler_text: object.summary.unwrap_or_default(),
+        media_attachments,
+        mentions,
+        tags,
+        emojis: Vec::new(),
+        reblogs_count: 0,
+        favourites_count: 0,
+        replies_count: 0,
+        in_reply_to_id: reply.as_ref().map(|x| x.obj_id.to_string()),
+        in_reply_to_account_id: reply.as_ref().map(|x| x.ap_user_id.to_string()),
+        reblog: None,
+        application: None,
+        language: None,
+        poll: None,
+        card: None,
+        edited_at: object.updated,
+    }))
+}
+
+/// https://docs.joinmastodon.org/entities/V1_Instance/
+#[derive(Serialize, Debug, Clone)]
+pub struct Instance {
+    pub uri: String,
+    pub title: String,
+    pub short_description: String,
+    pub description: String,
+    pub email: String,
+    pub version: String,
+    pub urls: serde_json::Value,
+    pub stats: InstanceStats,
+    pub thumbnail: Option<String>,
+    pub languages: Vec<String>,
+    pub registrations: bool,
+    pub approval_required: bool,
+    pub invites_enabled: bool,
+    pub configuration: serde_json::Value,
+    pub contact_account: Option<Account>,
+    pub rules: Vec<Rule>,
+}
+
+/// https://docs.joinmastodon.org/entities/Instance/
+#[derive(Serialize, Debug, Clone)]
+pub struct InstanceV2 {
+    pub domain: String,
+    pub title: String,
+    pub version: String,
+    pub source_url: String,
+    pub description: String,
+    pub usage: serde_json::Value,
+    pub thumbnail: serde_json::Value,
+    pub languages: Vec<String>,
+    pub configuration: serde_json::Value,
+    pub registrations: InstanceRegistrations,
+    pub contact: InstanceContact,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct InstanceRegistrations {
+    pub enabled: bool,
+    pub approval_required: bool,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct InstanceContact {
+    pub email: String,
+    pub account: Option<Account>,
+}
+
+/// https://docs.joinmastodon.org/entities/Rule/
+#[derive(Serialize, Debug, Clone)]
+pub struct Rule {
+    pub id: String,
+    pub text: String,
+    pub hint: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct InstanceStats {
+    pub user_count: i64,
+    pub status_count: i64,
+    pub domain_count: i64,
+}
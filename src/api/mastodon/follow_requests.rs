@@ -0,0 +1,108 @@
+use actix_web::{
+    error::ErrorNotFound,
+    get, post,
+    web::{self, Data},
+    HttpResponse, Result,
+};
+use serde_json::json;
+
+use crate::{
+    api::authentication::AuthenticatedUser,
+    cache_and_fetch::Cache,
+    db::{
+        actor_utilities::get_ap_actor_by_db_id,
+        conn::DbConn,
+        following::{get_follow_request, get_pending_followers},
+    },
+    protocol::outbox::post_outbox,
+};
+
+use super::{
+    entities::{account_from_db, relationship_from_db, Account},
+    json_response, outbox_error,
+};
+
+#[get("/api/v1/follow_requests")]
+pub async fn follow_requests(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    user.require_scope("read:follows")?;
+
+    let user_id = format!(
+        "https://{}/users/{}",
+        cache.state.instance_domain, user.preferred_username
+    );
+
+    let ids = get_pending_followers(&conn.db, &user_id).await.unwrap();
+    let mut accounts: Vec<Account> = Vec::with_capacity(ids.len());
+    for ap_user_id in ids {
+        if let Some(x) = account_from_db(&conn, &cache, ap_user_id).await? {
+            accounts.push(x);
+        }
+    }
+
+    Ok(json_response(&accounts))
+}
+
+/// sends an `Accept` or `Reject` for the follow request from `ap_user_id`
+async fn answer_follow_request(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    user: &AuthenticatedUser,
+    ap_user_id: i64,
+    answer: &str,
+) -> Result<HttpResponse> {
+    user.require_scope("write:follows")?;
+
+    let user_id = format!(
+        "https://{}/users/{}",
+        cache.state.instance_domain, user.preferred_username
+    );
+
+    if account_from_db(conn, cache, ap_user_id).await?.is_none() {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    }
+    let follower = get_ap_actor_by_db_id(ap_user_id, conn).await;
+    let Some(request) = get_follow_request(&conn.db, follower.id.as_str(), &user_id)
+        .await
+        .unwrap()
+    else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+
+    let activity = json!({
+        "type": answer,
+        "actor": &user_id,
+        "object": &request.activity_id,
+    });
+    if let Err(x) = post_outbox(conn, cache, user.uid, &user.preferred_username, activity).await {
+        return Err(outbox_error(x));
+    }
+
+    let relationship = relationship_from_db(conn, &user_id, ap_user_id)
+        .await?
+        .unwrap();
+    Ok(json_response(&relationship))
+}
+
+#[post("/api/v1/follow_requests/{id}/authorize")]
+pub async fn authorize_follow_request(
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    answer_follow_request(&conn, &cache, &user, path.into_inner(), "Accept").await
+}
+
+#[post("/api/v1/follow_requests/{id}/reject")]
+pub async fn reject_follow_request(
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    answer_follow_request(&conn, &cache, &user, path.into_inner(), "Reject").await
+}
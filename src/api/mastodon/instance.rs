@@ -0,0 +1,129 @@
+use actix_web::{get, web::Data, HttpResponse, Result};
+use serde_json::json;
+
+use crate::{
+    config::RegistrationMode,
+    db::{conn::DbConn, instance_stats::get_instance_counts},
+};
+
+use super::{
+    entities::{Instance, InstanceContact, InstanceRegistrations, InstanceStats, InstanceV2, Rule},
+    json_response,
+    statuses::MAX_MEDIA_ATTACHMENTS,
+};
+
+/// the mastodon version we claim compatibility with, clients use it to feature detect
+pub const MASTODON_COMPAT_VERSION: &str = "4.0.0";
+
+fn instance_version() -> String {
+    format!(
+        "{MASTODON_COMPAT_VERSION} (compatible; {} {})",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// the rules from the config, ids are their position starting at 1
+fn instance_rules(state: &crate::config::Config) -> Vec<Rule> {
+    state
+        .rules
+        .iter()
+        .enumerate()
+        .map(|(i, text)| Rule {
+            id: (i + 1).to_string(),
+            text: text.clone(),
+            hint: String::new(),
+        })
+        .collect()
+}
+
+fn instance_configuration(state: &crate::config::Config) -> serde_json::Value {
+    json!({
+        "statuses": {
+            "max_characters": 5000,
+            "max_media_attachments": MAX_MEDIA_ATTACHMENTS,
+        },
+        "media_attachments": {
+            "image_size_limit": state.max_upload_size,
+            "video_size_limit": state.max_upload_size,
+        },
+    })
+}
+
+#[get("/api/v1/instance")]
+pub async fn instance(
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+) -> Result<HttpResponse> {
+    let counts = get_instance_counts(&conn.db, &state.instance_domain)
+        .await
+        .unwrap();
+
+    let instance = Instance {
+        uri: state.account_domain().to_string(),
+        title: state.instance_title().to_string(),
+        short_description: state.instance_description.clone(),
+        description: state.instance_description.clone(),
+        email: state.contact_email.clone(),
+        version: instance_version(),
+        urls: json!({}),
+        stats: InstanceStats {
+            user_count: counts.user_count,
+            status_count: counts.local_posts,
+            domain_count: counts.domain_count,
+        },
+        thumbnail: None,
+        languages: Vec::new(),
+        registrations: state.open_registrations(),
+        approval_required: state.registrations == RegistrationMode::Approval,
+        invites_enabled: state.registrations != RegistrationMode::Closed,
+        configuration: instance_configuration(&state),
+        contact_account: None,
+        rules: instance_rules(&state),
+    };
+
+    Ok(json_response(&instance))
+}
+
+#[get("/api/v2/instance")]
+pub async fn instance_v2(
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+) -> Result<HttpResponse> {
+    let counts = get_instance_counts(&conn.db, &state.instance_domain)
+        .await
+        .unwrap();
+
+    let info = InstanceV2 {
+        domain: state.account_domain().to_string(),
+        title: state.instance_title().to_string(),
+        version: instance_version(),
+        source_url: String::new(),
+        description: state.instance_description.clone(),
+        usage: json!({
+            "users": {
+                "active_month": counts.active_month,
+            },
+        }),
+        thumbnail: json!({ "url": null }),
+        languages: Vec::new(),
+        configuration: instance_configuration(&state),
+        registrations: InstanceRegistrations {
+            enabled: state.open_registrations(),
+            approval_required: state.registrations == RegistrationMode::Approval,
+            message: None,
+        },
+        contact: InstanceContact {
+            email: state.contact_email.clone(),
+            account: None,
+        },
+        rules: instance_rules(&state),
+    };
+
+    Ok(json_response(&info))
+}
+
+#[get("/api/v1/instance/rules")]
+pub async fn rules(state: Data<crate::config::Config>) -> Result<HttpResponse> {
+    Ok(json_response(&instance_rules(&state)))
+}
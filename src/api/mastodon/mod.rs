@@ -0,0 +1,68 @@
+pub mod accounts;
+pub mod entities;
+pub mod follow_requests;
+pub mod instance;
+pub mod registrations;
+pub mod statuses;
+pub mod timelines;
+
+use actix_web::{
+    error::{ErrorInternalServerError, ErrorUnprocessableEntity},
+    web, Either,
+};
+use serde_json::json;
+
+use crate::protocol::outbox::OutboxErr;
+
+/// mastodon clients send either json or form encoded bodies
+pub fn json_or_form<T>(input: Either<web::Json<T>, web::Form<T>>) -> T {
+    match input {
+        Either::Left(x) => x.into_inner(),
+        Either::Right(x) => x.into_inner(),
+    }
+}
+
+pub fn json_response<T: serde::Serialize>(value: &T) -> actix_web::HttpResponse {
+    actix_web::HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .body(serde_json::to_string(value).unwrap())
+}
+
+/// the error for a failed outbox post, a 500 when it's our fault and a 422 otherwise
+pub fn outbox_error(x: OutboxErr) -> actix_web::Error {
+    match x {
+        OutboxErr::DbErr(_) | OutboxErr::KeyErr(_) | OutboxErr::MissingKey => {
+            eprintln!("failed to post to the outbox: {}", x);
+            ErrorInternalServerError(r#"{"error":"Internal Server Error"}"#)
+        }
+        x => ErrorUnprocessableEntity(json!({ "error": x.to_string() }).to_string()),
+    }
+}
+
+/// builds a `Link` header pointing at the pages before and after `ids`,
+/// which should be sorted newest first. `extra` are query params to keep
+pub fn link_header(
+    domain: &str,
+    path: &str,
+    extra: &[(&str, String)],
+    ids: &[i64],
+) -> Option<String> {
+    let (Some(first), Some(last)) = (ids.first(), ids.last()) else {
+        return None;
+    };
+
+    let base = url::Url::parse(&format!("https://{domain}{path}")).unwrap();
+
+    let mut next = base.clone();
+    let mut prev = base;
+    for (key, value) in extra {
+        next.query_pairs_mut().append_pair(key, value);
+        prev.query_pairs_mut().append_pair(key, value);
+    }
+    next.query_pairs_mut()
+        .append_pair("max_id", &last.to_string());
+    prev.query_pairs_mut()
+        .append_pair("min_id", &first.to_string());
+
+    Some(format!(r#"<{next}>; rel="next", <{prev}>; rel="prev""#))
+}
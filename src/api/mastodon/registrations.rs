@@ -0,0 +1,187 @@
+use actix_web::{
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorUnprocessableEntity},
+    get, post,
+    web::{self, Data},
+    Either, HttpResponse, Result,
+};
+use serde::Deserialize;
+
+use crate::{
+    api::{authentication::AuthenticatedUser, oauth::OAuthTokenResponse},
+    config::RegistrationMode,
+    db::{
+        access_tokens::{
+            generate_token, insert_access_token, now_milis, FULL_SCOPES, TOKEN_LIFETIME_MILIS,
+        },
+        account_creation::{create_internal_actor, AccountCreationErr, SignUp},
+        conn::DbConn,
+        invites::{get_invites, insert_invite},
+    },
+};
+
+use super::{
+    entities::{invite_from_db, Invite},
+    json_or_form, json_response,
+};
+
+/// https://docs.joinmastodon.org/methods/accounts/#create
+#[derive(Deserialize, Debug)]
+pub struct RegistrationForm {
+    pub username: String,
+    pub email: Option<String>,
+    pub password: String,
+    pub agreement: Option<bool>,
+    pub locale: Option<String>,
+    /// shown to whoever approves the account
+    pub reason: Option<String>,
+    pub invite_code: Option<String>,
+}
+
+fn validation_error(message: &str) -> actix_web::Error {
+    ErrorUnprocessableEntity(
+        serde_json::json!({ "error": format!("Validation failed: {message}") }).to_string(),
+    )
+}
+
+/// signs up a new local account and returns a token for it. accounts that need approval
+/// get a token that only starts working once they're approved
+#[post("/api/v1/accounts")]
+pub async fn register_account(
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+    form: Either<web::Json<RegistrationForm>, web::Form<RegistrationForm>>,
+) -> Result<HttpResponse> {
+    let form = json_or_form(form);
+
+    let invite = form.invite_code.filter(|x| !x.is_empty());
+    let pending = match (state.registrations, &invite) {
+        (RegistrationMode::Closed, _) => {
+            return Err(ErrorForbidden(r#"{"error":"Registrations are closed"}"#))
+        }
+        (RegistrationMode::Invite, None) => {
+            return Err(ErrorForbidden(
+                r#"{"error":"Registrations require an invite"}"#,
+            ))
+        }
+        (RegistrationMode::Approval, None) => true,
+        _ => false,
+    };
+
+    if !form.agreement.unwrap_or(false) {
+        return Err(validation_error("Agreement must be accepted"));
+    }
+    let email = form.email.filter(|x| !x.trim().is_empty());
+    if let Some(email) = &email {
+        let valid = email
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.'));
+        if !valid {
+            return Err(validation_error("Email is invalid"));
+        }
+    }
+
+    let sign_up = SignUp {
+        email,
+        reason: form.reason.filter(|x| !x.trim().is_empty()),
+        invite,
+        pending,
+    };
+
+    let uid = match create_internal_actor(
+        state.clone(),
+        conn.clone(),
+        form.username,
+        form.password,
+        &sign_up,
+    )
+    .await
+    {
+        Ok(x) => x,
+        Err(
+            x @ (AccountCreationErr::InvalidUsername(_)
+            | AccountCreationErr::UsernameTaken
+            | AccountCreationErr::InvalidPassword(_)
+            | AccountCreationErr::InvalidInvite),
+        ) => return Err(validation_error(&x.to_string())),
+        Err(x) => {
+            eprintln!("failed to create account: {}", x);
+            return Err(ErrorInternalServerError(
+                r#"{"error":"Internal Server Error"}"#,
+            ));
+        }
+    };
+
+    let access_token = generate_token();
+    insert_access_token(
+        &conn.db,
+        uid,
+        &access_token,
+        Some(now_milis() + TOKEN_LIFETIME_MILIS),
+        None,
+        FULL_SCOPES,
+    )
+    .await
+    .unwrap();
+
+    let response = OAuthTokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        scope: FULL_SCOPES.to_string(),
+        created_at: now_milis() / 1000,
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .insert_header(("Cache-Control", "no-store"))
+        .body(serde_json::to_string(&response).unwrap()))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct InviteForm {
+    /// unlimited when not given
+    pub max_uses: Option<i32>,
+    /// seconds until the invite expires, never when not given
+    pub expires_in: Option<i64>,
+}
+
+#[get("/api/v1/invites")]
+pub async fn invites(conn: Data<DbConn>, user: AuthenticatedUser) -> Result<HttpResponse> {
+    user.require_scope("read:invites")?;
+
+    let invites: Vec<Invite> = get_invites(&conn.db, user.uid)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(invite_from_db)
+        .collect();
+
+    Ok(json_response(&invites))
+}
+
+#[post("/api/v1/invites")]
+pub async fn create_invite(
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+    user: AuthenticatedUser,
+    form: Either<web::Json<InviteForm>, web::Form<InviteForm>>,
+) -> Result<HttpResponse> {
+    user.require_scope("write:invites")?;
+    let form = json_or_form(form);
+
+    if state.registrations == RegistrationMode::Closed {
+        return Err(ErrorForbidden(r#"{"error":"Registrations are closed"}"#));
+    }
+    if form.max_uses.is_some_and(|x| x < 1) {
+        return Err(validation_error("Max uses must be greater than 0"));
+    }
+    if form.expires_in.is_some_and(|x| x < 1) {
+        return Err(validation_error("Expires in must be greater than 0"));
+    }
+
+    let expires = form.expires_in.map(|x| now_milis() + x * 1000);
+    let invite = insert_invite(&conn.db, user.uid, form.max_uses, expires)
+        .await
+        .unwrap();
+
+    Ok(json_response(&invite_from_db(invite)))
+}
@@ -0,0 +1,209 @@
+use actix_web::{
+    error::{ErrorNotFound, ErrorUnprocessableEntity},
+    get, post,
+    web::{self, Data},
+    Either, HttpRequest, HttpResponse, Result,
+};
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    activitystream_objects::{
+        core_types::{LinkOrArray, RangeLinkExtendsObject},
+        link::{Link, LinkSimpleOrExpanded, LinkType},
+        object::{AttachmentOrArray, Object, Visibility},
+    },
+    api::{
+        authentication::AuthenticatedUser,
+        html::{find_mentions, text_to_html},
+        media::{file_to_document, media_preview_url, media_url},
+        objects::can_request_view,
+        outbox::publish_note,
+    },
+    cache_and_fetch::{resolve_handle, Cache},
+    db::{
+        conn::DbConn,
+        files::{attach_file, get_file},
+        internal_actor::get_actor_id_from_internal,
+        objects::get_object_meta,
+    },
+};
+
+use super::{entities::status_from_db, json_or_form, json_response, outbox_error};
+
+#[derive(Deserialize, Debug)]
+pub struct StatusForm {
+    pub status: Option<String>,
+    pub in_reply_to_id: Option<String>,
+    pub sensitive: Option<bool>,
+    pub spoiler_text: Option<String>,
+    pub visibility: Option<String>,
+    pub language: Option<String>,
+    /// ids from `/api/v1/media`
+    pub media_ids: Option<Vec<String>>,
+}
+
+/// statuses can't have more attachments than this
+pub const MAX_MEDIA_ATTACHMENTS: usize = 4;
+
+#[post("/api/v1/statuses")]
+pub async fn post_status(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    state: Data<crate::config::Config>,
+    user: AuthenticatedUser,
+    form: Either<web::Json<StatusForm>, web::Form<StatusForm>>,
+) -> Result<HttpResponse> {
+    user.require_scope("write:statuses")?;
+    let form = json_or_form(form);
+
+    let media_ids = form.media_ids.unwrap_or_default();
+    if media_ids.len() > MAX_MEDIA_ATTACHMENTS {
+        return Err(ErrorUnprocessableEntity(
+            r#"{"error":"Validation failed: Too many media attachments"}"#,
+        ));
+    }
+
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap();
+    let mut files = Vec::new();
+    for id in &media_ids {
+        let file = match id.parse::<i64>() {
+            Ok(x) => get_file(&conn.db, x).await.unwrap(),
+            Err(_) => None,
+        };
+        match file {
+            Some(x) if x.uploader.is_some() && x.uploader == ap_user_id => files.push(x),
+            _ => return Err(ErrorNotFound(r#"{"error":"Record not found"}"#)),
+        }
+    }
+
+    let status = form.status.unwrap_or_default();
+    if status.trim().is_empty() && files.is_empty() {
+        return Err(ErrorUnprocessableEntity(
+            r#"{"error":"Validation failed: Text can't be blank"}"#,
+        ));
+    }
+
+    let in_reply_to = match &form.in_reply_to_id {
+        Some(x) => {
+            let meta = match x.parse::<i64>() {
+                Ok(x) => get_object_meta(&conn.db, x).await.unwrap(),
+                Err(_) => None,
+            };
+            let Some(reply_id) = meta.and_then(|x| x.id) else {
+                return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+            };
+            Some(RangeLinkExtendsObject::Link(Box::new(
+                LinkSimpleOrExpanded::Simple(Url::parse(&reply_id).unwrap()),
+            )))
+        }
+        None => None,
+    };
+
+    let spoiler_text = form.spoiler_text.filter(|x| !x.is_empty());
+
+    let visibility = match &form.visibility {
+        Some(x) => match serde_json::from_value::<Visibility>(serde_json::Value::String(x.clone()))
+        {
+            Ok(x) => x,
+            Err(_) => {
+                return Err(ErrorUnprocessableEntity(
+                    r#"{"error":"Validation failed: Visibility is not included in the list"}"#,
+                ))
+            }
+        },
+        None => Visibility::Public,
+    };
+
+    let mut object = Object::new(Url::parse("https://temp.com").unwrap())
+        .content(Some(text_to_html(&status)))
+        .in_reply_to(in_reply_to);
+    object.summary = spoiler_text;
+    object.sensitive = form.sensitive;
+    if !files.is_empty() {
+        object.attachment = Some(AttachmentOrArray::Multiple(
+            files
+                .iter()
+                .map(|x| file_to_document(x, &state.instance_domain))
+                .collect(),
+        ));
+    }
+
+    let mut mentions: Vec<Url> = Vec::new();
+    let mut tags = Vec::new();
+    for handle in find_mentions(&status) {
+        //mentions of accounts that can't be found stay as plain text
+        let Ok(actor) = resolve_handle(&handle, &cache, &conn).await else {
+            continue;
+        };
+        if mentions.contains(&actor.id) {
+            continue;
+        }
+        tags.push(LinkSimpleOrExpanded::Expanded(Link {
+            type_field: LinkType::Mention,
+            href: actor.id.clone(),
+            hreflang: None,
+            media_type: None,
+            name: Some(format!("@{}", handle)),
+            height: None,
+            width: None,
+            preview: None,
+            rel: None,
+        }));
+        mentions.push(actor.id);
+    }
+    if !tags.is_empty() {
+        object.tag = Some(LinkOrArray::Multiple(tags));
+    }
+
+    let obj_id =
+        match publish_note(&conn, &cache, &state, &user, object, visibility, mentions).await {
+            Ok(x) => x.obj_id.unwrap(),
+            Err(x) => return Err(outbox_error(x)),
+        };
+
+    for (position, file) in files.iter().enumerate() {
+        let url = media_url(&state.instance_domain, file.file_id);
+        let preview_url = file
+            .preview_key
+            .as_ref()
+            .map(|_| media_preview_url(&state.instance_domain, file.file_id));
+        attach_file(
+            &conn.db,
+            obj_id,
+            file,
+            url.as_str(),
+            preview_url.as_ref().map(|x| x.as_str()),
+            position as i32,
+        )
+        .await
+        .unwrap();
+    }
+
+    let status = status_from_db(&conn, &cache, obj_id).await?.unwrap();
+
+    Ok(json_response(&status))
+}
+
+#[get("/api/v1/statuses/{id}")]
+pub async fn get_status(
+    request: HttpRequest,
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: Option<AuthenticatedUser>,
+) -> Result<HttpResponse> {
+    let obj_id = path.into_inner();
+
+    if !can_request_view(&request, &conn, &cache, user.as_ref(), obj_id).await {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    }
+
+    let Some(status) = status_from_db(&conn, &cache, obj_id).await? else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+
+    Ok(json_response(&status))
+}
@@ -0,0 +1,109 @@
+use actix_web::{
+    get,
+    web::{self, Data},
+    HttpResponse, Result,
+};
+use serde::Deserialize;
+
+use crate::{
+    api::authentication::AuthenticatedUser,
+    cache_and_fetch::Cache,
+    db::{
+        conn::DbConn,
+        internal_actor::get_actor_id_from_internal,
+        timelines::{get_home_timeline, get_public_timeline, Page},
+    },
+};
+
+use super::{
+    entities::{status_from_db, Status},
+    link_header,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct TimelineQuery {
+    pub max_id: Option<i64>,
+    pub since_id: Option<i64>,
+    pub min_id: Option<i64>,
+    pub limit: Option<i64>,
+    /// only statuses from this instance
+    pub local: Option<bool>,
+    /// only statuses from other instances
+    pub remote: Option<bool>,
+}
+
+impl TimelineQuery {
+    fn page(&self) -> Page {
+        Page::new(self.max_id, self.since_id, self.min_id, self.limit)
+    }
+}
+
+async fn timeline_response(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    path: &str,
+    extra: &[(&str, String)],
+    ids: Vec<i64>,
+) -> Result<HttpResponse> {
+    let mut statuses: Vec<Status> = Vec::with_capacity(ids.len());
+    for obj_id in &ids {
+        if let Some(x) = status_from_db(conn, cache, *obj_id).await? {
+            statuses.push(x);
+        }
+    }
+
+    let mut response = HttpResponse::Ok();
+    response.content_type("application/json; charset=utf-8");
+    if let Some(link) = link_header(&cache.state.instance_domain, path, extra, &ids) {
+        response.insert_header(("Link", link));
+    }
+
+    Ok(response.body(serde_json::to_string(&statuses).unwrap()))
+}
+
+#[get("/api/v1/timelines/home")]
+pub async fn home_timeline(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+    query: web::Query<TimelineQuery>,
+) -> Result<HttpResponse> {
+    user.require_scope("read:statuses")?;
+
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap()
+        .expect("token belongs to a user without an actor");
+
+    let ids = get_home_timeline(&conn.db, ap_user_id, &query.page())
+        .await
+        .unwrap();
+
+    timeline_response(&conn, &cache, "/api/v1/timelines/home", &[], ids).await
+}
+
+#[get("/api/v1/timelines/public")]
+pub async fn public_timeline(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    query: web::Query<TimelineQuery>,
+) -> Result<HttpResponse> {
+    let local = match (query.local, query.remote) {
+        (Some(true), _) => Some(true),
+        (_, Some(true)) => Some(false),
+        _ => None,
+    };
+
+    let ids = get_public_timeline(&conn.db, &cache.state.instance_domain, local, &query.page())
+        .await
+        .unwrap();
+
+    let mut extra = Vec::new();
+    match local {
+        Some(true) => extra.push(("local", "true".to_string())),
+        Some(false) => extra.push(("remote", "true".to_string())),
+        None => {}
+    }
+
+    timeline_response(&conn, &cache, "/api/v1/timelines/public", &extra, ids).await
+}
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+
+use actix_multipart::Multipart;
+use actix_web::{
+    error::{
+        ErrorBadGateway, ErrorForbidden, ErrorInternalServerError, ErrorNotFound,
+        ErrorPayloadTooLarge, ErrorUnprocessableEntity,
+    },
+    get, post, put,
+    web::{self, Data},
+    Either, HttpRequest, HttpResponse, Result,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    activitystream_objects::{
+        link::{Link, LinkType},
+        object::{Document, MediaType, ObjectType},
+    },
+    cache_and_fetch::Cache,
+    db::{
+        access_tokens::generate_token,
+        conn::DbConn,
+        files::{
+            get_file, get_file_objects, insert_file, set_file_description, MediaFile, NewFile,
+            NewPreview,
+        },
+        internal_actor::get_actor_id_from_internal,
+    },
+    media::{
+        processing::{is_allowed_media_type, process_upload, ProcessErr},
+        proxy::{fetch_remote_media, verify_proxy_signature, ProxyErr},
+    },
+};
+
+use super::{
+    authentication::AuthenticatedUser,
+    mastodon::{entities::media_attachment_from_file, json_or_form, json_response},
+    objects::can_request_view,
+};
+
+/// where a local file is served from
+pub fn media_url(domain: &str, file_id: i64) -> Url {
+    Url::parse(&format!("https://{}/media/{}", domain, file_id)).unwrap()
+}
+
+/// where the preview of a local file is served from
+pub fn media_preview_url(domain: &str, file_id: i64) -> Url {
+    Url::parse(&format!("https://{}/media/{}/preview", domain, file_id)).unwrap()
+}
+
+/// the activitypub attachment for a local file
+pub fn file_to_document(file: &MediaFile, domain: &str) -> Document {
+    let preview = file.preview_key.as_ref().map(|_| {
+        Box::new(Link {
+            type_field: LinkType::Link,
+            href: media_preview_url(domain, file.file_id),
+            hreflang: None,
+            media_type: file.preview_media_type.clone(),
+            name: None,
+            height: file.preview_height.map(|x| x as u32),
+            width: file.preview_width.map(|x| x as u32),
+            preview: None,
+            rel: None,
+        })
+    });
+    let type_field = match file.media_type.split('/').next() {
+        Some("image") => ObjectType::Image,
+        Some("video") => ObjectType::Video,
+        Some("audio") => ObjectType::Audio,
+        _ => ObjectType::Document,
+    };
+    Document {
+        type_field,
+        media_type: Some(MediaType::Other(file.media_type.clone())),
+        url: media_url(domain, file.file_id),
+        name: file.description.clone(),
+        blurhash: file.blurhash.clone(),
+        width: file.width.map(|x| x as u32),
+        height: file.height.map(|x| x as u32),
+        preview,
+    }
+}
+
+/// the parts of a multipart form, files are `(media type, data)`
+#[derive(Debug, Default)]
+pub struct MultipartForm {
+    pub text: HashMap<String, String>,
+    pub files: HashMap<String, (String, Vec<u8>)>,
+}
+
+/// reads a whole multipart form, the parts named in `file_fields` are kept as files
+pub async fn read_multipart(
+    mut payload: Multipart,
+    max_size: usize,
+    file_fields: &[&str],
+) -> Result<MultipartForm> {
+    let mut form = MultipartForm::default();
+
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let name = field.name().to_string();
+        let media_type = field.content_type().map(|x| x.essence_str().to_string());
+
+        let mut data = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk?;
+            if data.len() + chunk.len() > max_size {
+                return Err(ErrorUnprocessableEntity(
+                    r#"{"error":"Validation failed: File is too large"}"#,
+                ));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        match file_fields.contains(&name.as_str()) {
+            true => {
+                let media_type = media_type.unwrap_or("application/octet-stream".to_string());
+                form.files.insert(name, (media_type, data));
+            }
+            false => {
+                if let Ok(x) = String::from_utf8(data) {
+                    form.text.insert(name, x);
+                }
+            }
+        }
+    }
+
+    Ok(form)
+}
+
+/// processes and stores an upload from a local user
+pub async fn store_upload(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    uploader: Option<i64>,
+    media_type: &str,
+    data: Vec<u8>,
+    description: Option<&str>,
+) -> Result<MediaFile> {
+    if !is_allowed_media_type(media_type) {
+        return Err(ErrorUnprocessableEntity(
+            r#"{"error":"Validation failed: File content type is invalid"}"#,
+        ));
+    }
+
+    let processed = match process_upload(data, media_type) {
+        Ok(x) => x,
+        Err(ProcessErr::UnsupportedType) => {
+            return Err(ErrorUnprocessableEntity(
+                r#"{"error":"Validation failed: File content type is invalid"}"#,
+            ))
+        }
+        Err(ProcessErr::InvalidImage) => {
+            return Err(ErrorUnprocessableEntity(
+                r#"{"error":"Validation failed: File could not be processed"}"#,
+            ))
+        }
+    };
+
+    let storage_key = generate_token();
+    if let Err(x) = cache.storage.put(&storage_key, &processed.data) {
+        eprintln!("failed to store upload: {}", x);
+        return Err(ErrorInternalServerError(
+            r#"{"error":"Failed to store file"}"#,
+        ));
+    }
+    let preview_key = generate_token();
+    let preview = match &processed.preview {
+        Some(x) => match cache.storage.put(&preview_key, &x.data) {
+            Ok(_) => Some(NewPreview {
+                storage_key: &preview_key,
+                media_type: &x.media_type,
+                width: x.width,
+                height: x.height,
+            }),
+            Err(x) => {
+                eprintln!("failed to store upload preview: {}", x);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let file_id = insert_file(
+        &conn.db,
+        NewFile {
+            storage_key: &storage_key,
+            media_type,
+            size: processed.data.len() as i64,
+            info: &processed.info,
+            description,
+            uploader,
+            preview,
+        },
+    )
+    .await
+    .unwrap();
+
+    Ok(get_file(&conn.db, file_id).await.unwrap().unwrap())
+}
+
+async fn upload(
+    conn: &Data<DbConn>,
+    cache: &Data<Cache>,
+    user: &AuthenticatedUser,
+    payload: Multipart,
+) -> Result<HttpResponse> {
+    user.require_scope("write:media")?;
+
+    let mut form = read_multipart(payload, cache.state.max_upload_size, &["file"]).await?;
+
+    let Some((media_type, data)) = form.files.remove("file") else {
+        return Err(ErrorUnprocessableEntity(
+            r#"{"error":"Validation failed: File can't be blank"}"#,
+        ));
+    };
+    let description = form.text.remove("description").filter(|x| !x.is_empty());
+
+    let uploader = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap();
+
+    let file = store_upload(
+        conn,
+        cache,
+        uploader,
+        &media_type,
+        data,
+        description.as_deref(),
+    )
+    .await?;
+
+    Ok(json_response(&media_attachment_from_file(
+        &file,
+        &cache.state.instance_domain,
+    )))
+}
+
+#[post("/api/v1/media")]
+pub async fn upload_media_v1(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+    payload: Multipart,
+) -> Result<HttpResponse> {
+    upload(&conn, &cache, &user, payload).await
+}
+
+/// files are processed while uploading so this is the same as v1
+#[post("/api/v2/media")]
+pub async fn upload_media_v2(
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: AuthenticatedUser,
+    payload: Multipart,
+) -> Result<HttpResponse> {
+    upload(&conn, &cache, &user, payload).await
+}
+
+/// gets an upload, only the uploader can see it before it's attached to anything
+async fn get_own_file(
+    conn: &Data<DbConn>,
+    user: &AuthenticatedUser,
+    file_id: i64,
+) -> Result<MediaFile> {
+    let Some(file) = get_file(&conn.db, file_id).await.unwrap() else {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    };
+    let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+        .await
+        .unwrap();
+    if file.uploader.is_none() || file.uploader != ap_user_id {
+        return Err(ErrorNotFound(r#"{"error":"Record not found"}"#));
+    }
+    Ok(file)
+}
+
+#[get("/api/v1/media/{id}")]
+pub async fn get_media(
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse> {
+    user.require_scope("read:statuses")?;
+    let file = get_own_file(&conn, &user, path.into_inner()).await?;
+    Ok(json_response(&media_attachment_from_file(
+        &file,
+        &state.instance_domain,
+    )))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MediaUpdateForm {
+    pub description: Option<String>,
+}
+
+#[put("/api/v1/media/{id}")]
+pub async fn update_media(
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+    user: AuthenticatedUser,
+    form: Either<web::Json<MediaUpdateForm>, web::Form<MediaUpdateForm>>,
+) -> Result<HttpResponse> {
+    user.require_scope("write:media")?;
+    let form = json_or_form(form);
+    let file = get_own_file(&conn, &user, path.into_inner()).await?;
+
+    let description = form.description.filter(|x| !x.is_empty());
+    set_file_description(&conn.db, file.file_id, description.as_deref())
+        .await
+        .unwrap();
+
+    let file = get_file(&conn.db, file.file_id).await.unwrap().unwrap();
+    Ok(json_response(&media_attachment_from_file(
+        &file,
+        &state.instance_domain,
+    )))
+}
+
+/// a local file the request is allowed to see. that's one attached to an object it can
+/// view, files that haven't been posted yet are only visible to their uploader
+async fn get_visible_file(
+    request: &HttpRequest,
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    user: Option<&AuthenticatedUser>,
+    file_id: i64,
+) -> Result<MediaFile> {
+    let Some(file) = get_file(&conn.db, file_id).await.unwrap() else {
+        return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+    };
+
+    let objects = match get_file_objects(&conn.db, file.file_id).await {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("failed to get the objects of file {}: {}", file.file_id, x);
+            return Err(ErrorInternalServerError(
+                r#"{"error":"Internal Server Error"}"#,
+            ));
+        }
+    };
+    for obj_id in objects {
+        if can_request_view(request, conn, cache, user, obj_id).await {
+            return Ok(file);
+        }
+    }
+
+    if let Some(user) = user {
+        let ap_user_id = get_actor_id_from_internal(&conn.db, &user.preferred_username)
+            .await
+            .unwrap();
+        if file.uploader.is_some() && file.uploader == ap_user_id {
+            return Ok(file);
+        }
+    }
+    Err(ErrorNotFound(r#"{"error":"Not Found"}"#))
+}
+
+/// serves the bytes of an uploaded file
+#[get("/media/{id}")]
+pub async fn serve_media(
+    request: HttpRequest,
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: Option<AuthenticatedUser>,
+) -> Result<HttpResponse> {
+    let file = get_visible_file(&request, &conn, &cache, user.as_ref(), path.into_inner()).await?;
+    serve_file(&cache, &file.storage_key, &file.media_type)
+}
+
+/// serves the preview of an uploaded image
+#[get("/media/{id}/preview")]
+pub async fn serve_media_preview(
+    request: HttpRequest,
+    path: web::Path<i64>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    user: Option<AuthenticatedUser>,
+) -> Result<HttpResponse> {
+    let file = get_visible_file(&request, &conn, &cache, user.as_ref(), path.into_inner()).await?;
+    let Some((key, media_type)) = file.preview_key.zip(file.preview_media_type) else {
+        return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+    };
+    serve_file(&cache, &key, &media_type)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProxyQuery {
+    pub url: String,
+}
+
+/// serves remote media from our storage, fetching it the first time.
+/// links are made with [`crate::media::proxy::proxy_url`]
+#[get("/proxy/{signature}")]
+pub async fn proxy_media(
+    path: web::Path<String>,
+    query: web::Query<ProxyQuery>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+) -> Result<HttpResponse> {
+    if !verify_proxy_signature(&cache, &query.url, &path.into_inner()) {
+        return Err(ErrorForbidden(r#"{"error":"Invalid signature"}"#));
+    }
+    let Ok(remote) = Url::parse(&query.url) else {
+        return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+    };
+
+    let file = match fetch_remote_media(&conn, &cache, &remote).await {
+        Ok(x) => x,
+        Err(ProxyErr::InvalidUrl) => return Err(ErrorNotFound(r#"{"error":"Not Found"}"#)),
+        Err(ProxyErr::TooLarge) => {
+            return Err(ErrorPayloadTooLarge(r#"{"error":"File is too large"}"#))
+        }
+        Err(x @ (ProxyErr::DbErr(_) | ProxyErr::StorageErr(_))) => {
+            eprintln!("media proxy failed: {}", x);
+            return Err(ErrorInternalServerError(
+                r#"{"error":"Internal Server Error"}"#,
+            ));
+        }
+        Err(x) => {
+            eprintln!("failed to fetch {}: {}", remote, x);
+            return Err(ErrorBadGateway(r#"{"error":"Failed to fetch media"}"#));
+        }
+    };
+
+    serve_file(&cache, &file.storage_key, &file.media_type)
+}
+
+fn serve_file(cache: &Cache, storage_key: &str, media_type: &str) -> Result<HttpResponse> {
+    let data = match cache.storage.get(storage_key) {
+        Ok(x) => x,
+        Err(x) if x.kind() == std::io::ErrorKind::NotFound => {
+            return Err(ErrorNotFound(r#"{"error":"Not Found"}"#))
+        }
+        Err(x) => {
+            eprintln!("failed to read stored media {}: {}", storage_key, x);
+            return Err(ErrorInternalServerError(r#"{"error":"Unavailable"}"#));
+        }
+    };
+
+    //files never change once uploaded. the sandbox stops anything
+    //scriptable from running on our origin
+    Ok(HttpResponse::Ok()
+        .content_type(media_type)
+        .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
+        .insert_header(("Content-Security-Policy", "default-src 'none'; sandbox"))
+        .insert_header(("X-Content-Type-Options", "nosniff"))
+        .body(data))
+}
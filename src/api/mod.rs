@@ -1,6 +1,13 @@
 pub mod activities;
 pub mod actor;
+pub mod authentication;
+pub mod html;
 pub mod inbox;
+pub mod mastodon;
+pub mod media;
+pub mod nodeinfo;
+pub mod oauth;
 pub mod objects;
 pub mod outbox;
+pub mod pages;
 pub mod webfinger;
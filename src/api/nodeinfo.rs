@@ -0,0 +1,109 @@
+use actix_web::{get, web::Data, HttpResponse, Result};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::db::{conn::DbConn, instance_stats::get_instance_counts};
+
+pub const NODEINFO_SCHEMA: &str = "http://nodeinfo.diaspora.software/ns/schema/2.1";
+
+#[derive(Serialize, Debug)]
+pub struct NodeinfoSoftware {
+    /// has to match `^[a-z0-9-]+$`
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NodeinfoServices {
+    pub inbound: Vec<String>,
+    pub outbound: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeinfoUsers {
+    pub total: i64,
+    pub active_month: i64,
+    pub active_halfyear: i64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeinfoUsage {
+    pub users: NodeinfoUsers,
+    pub local_posts: i64,
+}
+
+/// https://github.com/jhass/nodeinfo/blob/main/schemas/2.1/schema.json
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Nodeinfo {
+    pub version: String,
+    pub software: NodeinfoSoftware,
+    pub protocols: Vec<String>,
+    pub services: NodeinfoServices,
+    pub open_registrations: bool,
+    pub usage: NodeinfoUsage,
+    pub metadata: serde_json::Value,
+}
+
+/// points at the nodeinfo documents we serve
+#[get("/.well-known/nodeinfo")]
+pub async fn nodeinfo_discovery(state: Data<crate::config::Config>) -> Result<HttpResponse> {
+    let links = json!({
+        "links": [
+            {
+                "rel": NODEINFO_SCHEMA,
+                "href": format!("https://{}/nodeinfo/2.1", state.instance_domain),
+            }
+        ]
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .body(links.to_string()))
+}
+
+#[get("/nodeinfo/2.1")]
+pub async fn nodeinfo(
+    conn: Data<DbConn>,
+    state: Data<crate::config::Config>,
+) -> Result<HttpResponse> {
+    let counts = get_instance_counts(&conn.db, &state.instance_domain)
+        .await
+        .unwrap();
+
+    let nodeinfo = Nodeinfo {
+        version: "2.1".to_string(),
+        software: NodeinfoSoftware {
+            name: env!("CARGO_PKG_NAME").replace('_', "-"),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        protocols: vec!["activitypub".to_string()],
+        services: NodeinfoServices {
+            inbound: Vec::new(),
+            outbound: Vec::new(),
+        },
+        open_registrations: state.open_registrations(),
+        usage: NodeinfoUsage {
+            users: NodeinfoUsers {
+                total: counts.user_count,
+                active_month: counts.active_month,
+                active_halfyear: counts.active_half_year,
+            },
+            local_posts: counts.local_posts,
+        },
+        metadata: json!({
+            "nodeName": state.account_domain(),
+            "contactEmail": state.contact_email,
+        }),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(format!(
+            r#"application/json; profile="{NODEINFO_SCHEMA}#"; charset=utf-8"#
+        ))
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .body(serde_json::to_string(&nodeinfo).unwrap()))
+}
@@ -0,0 +1,536 @@
+use actix_web::{
+    get,
+    http::StatusCode,
+    post,
+    web::{self, Data},
+    Either, HttpResponse, Result,
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    api::{authentication::Scopes, html::escape_html, mastodon::json_or_form},
+    db::{
+        access_tokens::{
+            base64_url, generate_token, insert_access_token, now_milis, revoke_app_token,
+        },
+        conn::DbConn,
+        internal_actor::verify_password,
+        oauth::{
+            get_app_by_client_id, insert_app, insert_authorization_code, take_authorization_code,
+            AuthorizationCode, NewApp, OAuthApp, AUTHORIZATION_CODE_LIFETIME_MILIS,
+        },
+    },
+};
+
+/// redirect uri for clients that can't receive a redirect, the code is shown to the user instead
+const OOB_REDIRECT: &str = "urn:ietf:wg:oauth:2.0:oob";
+
+/// an error response in the shape described by rfc 6749
+fn oauth_error(status: StatusCode, error: &str, description: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("application/json; charset=utf-8")
+        .body(
+            serde_json::json!({
+                "error": error,
+                "error_description": description,
+            })
+            .to_string(),
+        )
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AppRegistration {
+    pub client_name: String,
+    /// whitespace separated
+    pub redirect_uris: String,
+    pub scopes: Option<String>,
+    pub website: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AppResponse {
+    pub id: String,
+    pub name: String,
+    pub website: Option<String>,
+    pub redirect_uri: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scopes: Vec<String>,
+}
+
+#[post("/api/v1/apps")]
+pub async fn register_app(
+    conn: Data<DbConn>,
+    form: Either<web::Json<AppRegistration>, web::Form<AppRegistration>>,
+) -> Result<HttpResponse> {
+    let form = json_or_form(form);
+
+    let Some(scopes) = Scopes::parse(form.scopes.as_deref().unwrap_or("read")) else {
+        return Ok(oauth_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid_scope",
+            "unknown scope requested",
+        ));
+    };
+
+    let redirect_uris: Vec<String> = form
+        .redirect_uris
+        .split_whitespace()
+        .map(|x| x.to_string())
+        .collect();
+
+    if redirect_uris.is_empty()
+        || redirect_uris
+            .iter()
+            .any(|x| x.ne(OOB_REDIRECT) && Url::parse(x).is_err())
+    {
+        return Ok(oauth_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid_request",
+            "invalid redirect uri",
+        ));
+    }
+
+    let client_id = generate_token();
+    let client_secret = generate_token();
+    let scopes = scopes.to_string();
+
+    let app_id = insert_app(
+        &conn.db,
+        NewApp {
+            client_id: &client_id,
+            client_secret: &client_secret,
+            name: &form.client_name,
+            website: form.website.as_deref(),
+            redirect_uris: &redirect_uris,
+            scopes: &scopes,
+        },
+    )
+    .await
+    .unwrap();
+
+    let response = AppResponse {
+        id: app_id.to_string(),
+        name: form.client_name,
+        website: form.website,
+        redirect_uri: redirect_uris.join("\n"),
+        client_id,
+        client_secret,
+        scopes: scopes.split(' ').map(|x| x.to_string()).collect(),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .body(serde_json::to_string(&response).unwrap()))
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AuthorizeQuery {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+}
+
+/// checks an authorization request against the registered app
+async fn validate_authorization(
+    conn: &DbConn,
+    query: &AuthorizeQuery,
+) -> Result<(OAuthApp, Scopes), HttpResponse> {
+    if query.response_type.ne("code") {
+        return Err(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_response_type",
+            "only the code response type is supported",
+        ));
+    }
+
+    let app = get_app_by_client_id(&conn.db, &query.client_id)
+        .await
+        .unwrap();
+    let Some(app) = app else {
+        return Err(oauth_error(
+            StatusCode::UNAUTHORIZED,
+            "invalid_client",
+            "unknown client",
+        ));
+    };
+
+    if !app.redirect_uris.contains(&query.redirect_uri) {
+        return Err(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+            "redirect uri does not match one registered by the app",
+        ));
+    }
+
+    let scopes = Scopes::parse(query.scope.as_deref().unwrap_or("read"));
+    let app_scopes = Scopes::parse(&app.scopes).unwrap();
+    let scopes = match scopes {
+        Some(x) if !x.is_empty() && app_scopes.contains_all(&x) => x,
+        _ => {
+            return Err(oauth_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_scope",
+                "requested scopes were not registered by the app",
+            ))
+        }
+    };
+
+    match (
+        &query.code_challenge,
+        query.code_challenge_method.as_deref(),
+    ) {
+        (None, None) | (Some(_), None | Some("S256") | Some("plain")) => {}
+        _ => {
+            return Err(oauth_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+                "unsupported code challenge method",
+            ))
+        }
+    }
+
+    Ok((app, scopes))
+}
+
+fn hidden_field(name: &str, value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!(
+            r#"<input type="hidden" name="{name}" value="{}">"#,
+            escape_html(value)
+        ),
+        None => String::new(),
+    }
+}
+
+fn authorize_page(query: &AuthorizeQuery, app: &OAuthApp, scopes: &Scopes, error: &str) -> String {
+    let fields = [
+        hidden_field("response_type", Some(&query.response_type)),
+        hidden_field("client_id", Some(&query.client_id)),
+        hidden_field("redirect_uri", Some(&query.redirect_uri)),
+        hidden_field("scope", Some(&scopes.to_string())),
+        hidden_field("state", query.state.as_deref()),
+        hidden_field("code_challenge", query.code_challenge.as_deref()),
+        hidden_field(
+            "code_challenge_method",
+            query.code_challenge_method.as_deref(),
+        ),
+    ]
+    .join("\n");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Authorize {name}</title></head>
+<body>
+<h1>{name} would like to access your account</h1>
+<p>requested permissions: {scopes}</p>
+<p>{error}</p>
+<form method="post" action="/oauth/authorize">
+{fields}
+<label>username <input type="text" name="username" autocomplete="username"></label>
+<label>password <input type="password" name="password" autocomplete="current-password"></label>
+<button type="submit">Authorize</button>
+</form>
+</body>
+</html>"#,
+        name = escape_html(&app.name),
+        scopes = escape_html(&scopes.to_string()),
+        error = escape_html(error),
+    )
+}
+
+#[get("/oauth/authorize")]
+pub async fn authorize_form(
+    conn: Data<DbConn>,
+    query: web::Query<AuthorizeQuery>,
+) -> Result<HttpResponse> {
+    let query = query.into_inner();
+
+    let (app, scopes) = match validate_authorization(&conn, &query).await {
+        Ok(x) => x,
+        Err(x) => return Ok(x),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(authorize_page(&query, &app, &scopes, "")))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuthorizeForm {
+    #[serde(flatten)]
+    pub query: AuthorizeQuery,
+    pub username: String,
+    pub password: String,
+}
+
+#[post("/oauth/authorize")]
+pub async fn authorize(conn: Data<DbConn>, form: web::Form<AuthorizeForm>) -> Result<HttpResponse> {
+    let form = form.into_inner();
+    let query = form.query;
+
+    let (app, scopes) = match validate_authorization(&conn, &query).await {
+        Ok(x) => x,
+        Err(x) => return Ok(x),
+    };
+
+    let uid = verify_password(&conn.db, &form.username, &form.password)
+        .await
+        .unwrap();
+    let Some(uid) = uid else {
+        return Ok(HttpResponse::Unauthorized()
+            .content_type("text/html; charset=utf-8")
+            .body(authorize_page(
+                &query,
+                &app,
+                &scopes,
+                "invalid username or password",
+            )));
+    };
+
+    let code = generate_token();
+    insert_authorization_code(
+        &conn.db,
+        &code,
+        &AuthorizationCode {
+            app_id: app.app_id,
+            uid,
+            redirect_uri: query.redirect_uri.clone(),
+            scopes: scopes.to_string(),
+            code_challenge: query.code_challenge.clone(),
+            code_challenge_method: query.code_challenge.as_ref().map(|_| {
+                query
+                    .code_challenge_method
+                    .as_deref()
+                    .unwrap_or("plain")
+                    .to_string()
+            }),
+            expires: now_milis() + AUTHORIZATION_CODE_LIFETIME_MILIS,
+        },
+    )
+    .await
+    .unwrap();
+
+    if query.redirect_uri.eq(OOB_REDIRECT) {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(format!(
+                r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Authorization code</title></head>
+<body>
+<p>copy this code into {name}:</p>
+<pre>{code}</pre>
+</body>
+</html>"#,
+                name = escape_html(&app.name),
+            )));
+    }
+
+    let mut redirect = Url::parse(&query.redirect_uri).unwrap();
+    redirect.query_pairs_mut().append_pair("code", &code);
+    if let Some(state) = &query.state {
+        redirect.query_pairs_mut().append_pair("state", state);
+    }
+
+    Ok(HttpResponse::Found()
+        .insert_header(("Location", redirect.as_str()))
+        .finish())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub code_verifier: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OAuthTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub scope: String,
+    pub created_at: i64,
+}
+
+/// rfc 7636 section 4.1, 43 to 128 unreserved characters
+fn valid_code_verifier(verifier: &str) -> bool {
+    (43..=128).contains(&verifier.len())
+        && verifier
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || matches!(x, '-' | '.' | '_' | '~'))
+}
+
+fn verify_pkce(authorization: &AuthorizationCode, verifier: Option<&str>) -> bool {
+    let Some(challenge) = &authorization.code_challenge else {
+        return verifier.is_none();
+    };
+    let Some(verifier) = verifier else {
+        return false;
+    };
+    if !valid_code_verifier(verifier) {
+        return false;
+    }
+    match authorization.code_challenge_method.as_deref() {
+        Some("S256") => base64_url(&openssl::sha::sha256(verifier.as_bytes())).eq(challenge),
+        Some("plain") | None => verifier.eq(challenge),
+        Some(_) => false,
+    }
+}
+
+#[post("/oauth/token")]
+pub async fn token(
+    conn: Data<DbConn>,
+    form: Either<web::Json<TokenRequest>, web::Form<TokenRequest>>,
+) -> Result<HttpResponse> {
+    let form = json_or_form(form);
+
+    if form.grant_type.ne("authorization_code") {
+        return Ok(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            "only the authorization_code grant is supported",
+        ));
+    }
+
+    let app = get_app_by_client_id(&conn.db, &form.client_id)
+        .await
+        .unwrap();
+    let Some(app) = app else {
+        return Ok(oauth_error(
+            StatusCode::UNAUTHORIZED,
+            "invalid_client",
+            "unknown client",
+        ));
+    };
+
+    if let Some(secret) = &form.client_secret {
+        if !app.check_secret(secret) {
+            return Ok(oauth_error(
+                StatusCode::UNAUTHORIZED,
+                "invalid_client",
+                "client authentication failed",
+            ));
+        }
+    }
+
+    let (Some(code), Some(redirect_uri)) = (&form.code, &form.redirect_uri) else {
+        return Ok(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+            "code and redirect_uri are required",
+        ));
+    };
+
+    let authorization = take_authorization_code(&conn.db, code).await.unwrap();
+    let Some(authorization) = authorization else {
+        return Ok(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_grant",
+            "invalid or expired authorization code",
+        ));
+    };
+
+    if authorization.app_id != app.app_id || authorization.redirect_uri.ne(redirect_uri) {
+        return Ok(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_grant",
+            "authorization code was issued to another client",
+        ));
+    }
+
+    //public clients have no secret so they have to prove possession of the code with PKCE
+    if form.client_secret.is_none() && authorization.code_challenge.is_none() {
+        return Ok(oauth_error(
+            StatusCode::UNAUTHORIZED,
+            "invalid_client",
+            "client authentication or PKCE is required",
+        ));
+    }
+
+    if !verify_pkce(&authorization, form.code_verifier.as_deref()) {
+        return Ok(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_grant",
+            "code verifier does not match the code challenge",
+        ));
+    }
+
+    let access_token = generate_token();
+    insert_access_token(
+        &conn.db,
+        authorization.uid,
+        &access_token,
+        None,
+        Some(app.app_id),
+        &authorization.scopes,
+    )
+    .await
+    .unwrap();
+
+    let response = OAuthTokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        scope: authorization.scopes,
+        created_at: now_milis() / 1000,
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .insert_header(("Cache-Control", "no-store"))
+        .body(serde_json::to_string(&response).unwrap()))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RevokeRequest {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub token: String,
+}
+
+#[post("/oauth/revoke")]
+pub async fn revoke(
+    conn: Data<DbConn>,
+    form: Either<web::Json<RevokeRequest>, web::Form<RevokeRequest>>,
+) -> Result<HttpResponse> {
+    let form = json_or_form(form);
+
+    let app = get_app_by_client_id(&conn.db, &form.client_id)
+        .await
+        .unwrap();
+    let Some(app) = app else {
+        return Ok(oauth_error(
+            StatusCode::UNAUTHORIZED,
+            "invalid_client",
+            "unknown client",
+        ));
+    };
+
+    if let Some(secret) = &form.client_secret {
+        if !app.check_secret(secret) {
+            return Ok(oauth_error(
+                StatusCode::UNAUTHORIZED,
+                "invalid_client",
+                "client authentication failed",
+            ));
+        }
+    }
+
+    //rfc 7009 says invalid tokens still get a 200
+    revoke_app_token(&conn.db, &form.token, app.app_id)
+        .await
+        .unwrap();
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .body("{}"))
+}
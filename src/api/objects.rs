@@ -1,37 +1,204 @@
-use crate::db::{conn::DbConn, objects::get_object_by_db_id};
+use crate::{
+    api::{
+        authentication::AuthenticatedUser,
+        mastodon::entities::{milis_to_iso, status_from_db},
+        pages::{negotiate, status_page, Representation},
+    },
+    cache_and_fetch::Cache,
+    db::{
+        conn::DbConn,
+        internal_actor::is_actor_suspended,
+        objects::{
+            can_actor_view_object, get_object_by_db_id, get_object_meta, get_tombstone, DbObject,
+            Tombstone,
+        },
+    },
+    protocol::verification::verify_get,
+};
 use actix_web::{
-    error::ErrorNotFound,
+    error::{ErrorGone, ErrorNotFound},
     get,
     web::{self, Data},
     HttpRequest, HttpResponse, Result,
 };
 
-#[get("/users/{preferred_username}/statuses/{id}")]
-pub async fn get_object(
-    path: web::Path<(String, i64)>,
-    conn: Data<DbConn>,
-    request: HttpRequest,
-    body: web::Bytes,
-    // state: Data<crate::config::Config>,
+/// if whoever made the request can see the object. public and unlisted objects can be seen
+/// by anyone, otherwise the request has to be signed by or carry a token for an actor who can
+pub async fn can_request_view(
+    request: &HttpRequest,
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    user: Option<&AuthenticatedUser>,
+    obj_id: i64,
+) -> bool {
+    let Some(meta) = get_object_meta(&conn.db, obj_id).await.unwrap() else {
+        return false;
+    };
+    //suspended accounts' objects are hidden from everyone until they're unsuspended
+    if is_actor_suspended(&conn.db, meta.ap_user_id).await.unwrap() {
+        return false;
+    }
+    if meta.visibility.is_public() {
+        return true;
+    }
+
+    let domain = &cache.state.instance_domain;
+    //a token only stands in for its user if it was granted read:statuses
+    let user = user.filter(|x| x.scopes.allows("read:statuses"));
+    let viewer = match (user, request.headers().contains_key("Signature")) {
+        (Some(x), _) => Some(format!(
+            "https://{}/users/{}",
+            domain, &x.preferred_username
+        )),
+        (None, true) => verify_get(cache, request, domain)
+            .await
+            .ok()
+            .map(|x| x.to_string()),
+        (None, false) => None,
+    };
+    let Some(viewer) = viewer else {
+        return false;
+    };
+
+    can_actor_view_object(&conn.db, obj_id, &viewer)
+        .await
+        .unwrap()
+}
+
+/// the 410 for something that was deleted, with a `Tombstone` in its place for AS2
+///
+/// https://www.w3.org/TR/activitypub/#delete-activity-outbox
+pub fn tombstone_response(
+    tombstone: Tombstone,
+    representation: Representation,
 ) -> Result<HttpResponse> {
-    println!("getting an object");
+    if representation == Representation::Html {
+        return Err(ErrorGone("Gone"));
+    }
+    let former_type: serde_json::Value = serde_json::from_str(&tombstone.former_type).unwrap();
 
-    dbg!(request);
-    dbg!(&body);
+    let tombstone = serde_json::json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": tombstone.id,
+        "type": "Tombstone",
+        "formerType": former_type,
+        "deleted": milis_to_iso(tombstone.deleted),
+    });
+
+    Ok(HttpResponse::Gone()
+        .content_type("application/activity+json; charset=utf-8")
+        .insert_header(("Vary", "Accept"))
+        .body(tombstone.to_string()))
+}
 
-    let (_preferred_username, object_id) = path.into_inner();
+/// the object as AS2, or its status page for browsers
+async fn object_response(
+    preferred_username: &str,
+    object_id: i64,
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    request: &HttpRequest,
+    user: Option<&AuthenticatedUser>,
+) -> Result<HttpResponse> {
+    let representation = negotiate(request);
 
     let object = get_object_by_db_id(object_id, conn.db.begin().await.unwrap()).await;
 
     let object = match object {
         Some(x) => x,
         None => {
-            return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+            let id = format!(
+                "https://{}/users/{}/statuses/{}",
+                &cache.state.instance_domain, preferred_username, object_id
+            );
+            let Some(tombstone) = get_tombstone(&conn.db, &id).await.unwrap() else {
+                return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+            };
+            return tombstone_response(tombstone, representation);
         }
     };
+
+    //the path has to name the local user the object belongs to
+    let owner = format!(
+        "https://{}/users/{}",
+        &cache.state.instance_domain, preferred_username
+    );
+    let owned = match &object {
+        DbObject::Object(x) => x
+            .object
+            .get_attributed_to()
+            .is_some_and(|x| x.as_str().eq(&owner)),
+        DbObject::Question(_) => false,
+    };
+    if !owned {
+        return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+    }
+
+    //objects the requester can't see are treated as if they don't exist
+    if !can_request_view(request, conn, cache, user, object_id).await {
+        return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+    }
+
+    if representation == Representation::Html {
+        let Some(status) = status_from_db(conn, cache, object_id).await? else {
+            return Err(ErrorNotFound(r#"{"error":"Not Found"}"#));
+        };
+        return Ok(status_page(&cache.state, &status));
+    }
+
     let object = object.to_activitystream();
 
     Ok(HttpResponse::Ok()
         .content_type("application/activity+json; charset=utf-8")
+        .insert_header(("Vary", "Accept"))
         .body(serde_json::to_string(&object).unwrap()))
 }
+
+#[get("/users/{preferred_username}/statuses/{id}")]
+pub async fn get_object(
+    path: web::Path<(String, i64)>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    request: HttpRequest,
+    body: web::Bytes,
+    user: Option<AuthenticatedUser>,
+) -> Result<HttpResponse> {
+    println!("getting an object");
+
+    dbg!(&request);
+    dbg!(&body);
+
+    let (preferred_username, object_id) = path.into_inner();
+
+    object_response(
+        &preferred_username,
+        object_id,
+        &conn,
+        &cache,
+        &request,
+        user.as_ref(),
+    )
+    .await
+}
+
+/// the mastodon style alias of `/users/{preferred_username}/statuses/{id}`
+#[get("/@{preferred_username}/{id}")]
+pub async fn get_object_alias(
+    path: web::Path<(String, i64)>,
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    request: HttpRequest,
+    user: Option<AuthenticatedUser>,
+) -> Result<HttpResponse> {
+    let (preferred_username, object_id) = path.into_inner();
+
+    object_response(
+        &preferred_username,
+        object_id,
+        &conn,
+        &cache,
+        &request,
+        user.as_ref(),
+    )
+    .await
+}
@@ -6,28 +6,46 @@
 // pub fn create_activity(conn: &Data<DbConn>, user_id: i64) {}
 
 use actix_web::{
+    error::Error,
     get,
-    http::Error,
+    http::header::LOCATION,
     post,
     web::{self, Data},
     HttpRequest, HttpResponse,
 };
-use openssl::pkey::PKey;
 use url::Url;
 
 use crate::{
-    activitystream_objects::object::{Object, ObjectType},
-    api::inbox::Inbox,
+    activitystream_objects::object::{Object, ObjectType, Visibility},
+    api::authentication::AuthenticatedUser,
     cache_and_fetch::Cache,
-    db::{
-        conn::DbConn,
-        internal_actor::get_actor_id_from_internal,
-        objects::{create_new_object, get_object_by_db_id},
-        private_key::get_private_key,
-    },
-    protocol::verification::post_to_inbox,
+    db::conn::DbConn,
+    protocol::outbox::{post_outbox, OutboxErr, OutboxResult},
 };
 
+/// stores a note written by a local user and delivers the create for it.
+/// the id and attribution are filled in by the outbox so callers only set the content
+pub async fn publish_note(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    state: &crate::config::Config,
+    user: &AuthenticatedUser,
+    object: Object,
+    visibility: Visibility,
+    mentions: Vec<Url>,
+) -> Result<OutboxResult, OutboxErr> {
+    let followers = format!(
+        "https://{}/users/{}/followers",
+        &state.instance_domain, &user.preferred_username
+    );
+
+    let object = object.visibility(visibility, Url::parse(&followers).unwrap(), mentions);
+    let object = serde_json::to_value(object.wrap(ObjectType::Note)).unwrap();
+
+    post_outbox(conn, cache, user.uid, &user.preferred_username, object).await
+}
+
+/// https://www.w3.org/TR/activitypub/#client-to-server-interactions
 #[post("/users/{preferred_username}/outbox")]
 pub async fn create_post(
     request: HttpRequest,
@@ -37,84 +55,37 @@ pub async fn create_post(
     cache: Data<Cache>,
     conn: Data<DbConn>,
     state: Data<crate::config::Config>,
+    user: AuthenticatedUser,
 ) -> Result<HttpResponse, Error> {
     let preferred_username = path.into_inner();
-    let user_id = format!(
-        "https://{}/users/{}",
-        &state.instance_domain, &preferred_username
-    );
+    user.require_user(&preferred_username)?;
+    user.require_scope("write:statuses")?;
 
-    let Ok(body) = String::from_utf8(body.to_vec()) else {
+    let Ok(body) = serde_json::from_slice::<serde_json::Value>(&body) else {
         return Ok(HttpResponse::BadRequest().body("invalid body"));
     };
 
-    dbg!(&user_id);
-
-    let mut object = Object::new(Url::parse("https://temp.com").unwrap())
-        .content(Some(body))
-        .attributed_to_link(Some(Url::parse(&user_id).unwrap()))
-        .wrap(ObjectType::Note);
+    let result = post_outbox(&conn, &cache, user.uid, &user.preferred_username, body).await;
 
-    let obj_id = create_new_object(
-        &crate::db::objects::DbObject::Object(object),
-        conn.db.begin().await.unwrap(),
-        &state.instance_domain,
-    )
-    .await;
-
-    let obj_id = match obj_id {
+    let result = match result {
         Ok(x) => x,
+        Err(OutboxErr::NotFound) => return Ok(HttpResponse::NotFound().body("NotFound")),
+        Err(OutboxErr::Forbidden) => return Ok(HttpResponse::Forbidden().body("Forbidden")),
+        Err(OutboxErr::DbErr(x)) => {
+            eprintln!("outbox db error: {}", x);
+            return Ok(HttpResponse::InternalServerError().body("Internal Server Error"));
+        }
+        Err(x @ (OutboxErr::KeyErr(_) | OutboxErr::MissingKey)) => {
+            eprintln!("failed to load the signing key: {}", x);
+            return Ok(HttpResponse::InternalServerError().body("Internal Server Error"));
+        }
         Err(x) => return Ok(HttpResponse::BadRequest().body(format!("{}", x))),
     };
 
-    let id_link = format!(
-        "https://{}/users/{}/statuses/{}",
-        &state.instance_domain, preferred_username, obj_id
-    );
-
-    let object = get_object_by_db_id(obj_id, conn.db.begin().await.unwrap())
-        .await
-        .unwrap();
-
-    // let actor_id = get_actor_id_from_internal(&conn.db, &preferred_username).await.unwrap().unwrap();
-    // let key = get_private_key(&conn.db, actor_id).await;
-    let key = sqlx::query!(
-        "SELECT * FROM  internal_users WHERE preferred_username = $1",
-        &preferred_username
-    )
-    .fetch_one(&conn.db)
-    .await
-    .unwrap();
-
-    let key = openssl::rsa::Rsa::private_key_from_pem(key.private_key.as_bytes()).unwrap();
-    let key = PKey::from_rsa(key).unwrap();
-
-    match object {
-        crate::db::objects::DbObject::Object(x) => {
-            let activity = x.to_create_activitystream();
-            let activity_str = serde_json::to_string(&activity).unwrap();
-
-            post_to_inbox(
-                &activity_str,
-                &user_id,
-                "mastodon.social",
-                "https://mastodon.social/inbox",
-                &key,
-            )
-            .await;
-            post_to_inbox(
-                &activity_str,
-                &user_id,
-                "cutie.city",
-                "https://cutie.city/inbox",
-                &key,
-            )
-            .await;
-
-            return Ok(HttpResponse::Created().body(format!("{}", activity_str)));
-        }
-        crate::db::objects::DbObject::Question(_) => todo!(),
-    }
+    Ok(HttpResponse::Created()
+        .insert_header((LOCATION, result.id.as_str()))
+        .content_type("application/activity+json; charset=utf-8")
+        .body(result.activity))
 }
 
 #[get("/users/{preferred_username}/outbox")]
@@ -0,0 +1,296 @@
+use actix_web::{HttpRequest, HttpResponse};
+
+use super::{
+    html::{escape_html, html_to_text, text_to_html},
+    mastodon::entities::{Account, Status},
+};
+
+/// how many of an actor's public statuses their profile page shows
+pub const PROFILE_PAGE_STATUSES: i64 = 20;
+
+/// what a GET for an actor or an object gets answered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    ActivityStream,
+    Html,
+}
+
+/// the q value of a media range like `text/html;q=0.9`, 1 if it isn't given
+fn quality(params: &str) -> f32 {
+    params
+        .split(';')
+        .filter_map(|x| x.trim().strip_prefix("q="))
+        .find_map(|x| x.trim().parse().ok())
+        .unwrap_or(1.0)
+}
+
+/// picks between AS2 and html from the Accept header. html is only served when it's
+/// preferred over AS2, so servers that send `*/*` or nothing at all still get AS2
+///
+/// https://www.w3.org/TR/activitypub/#retrieving-objects
+pub fn negotiate(request: &HttpRequest) -> Representation {
+    let Some(accept) = request
+        .headers()
+        .get("Accept")
+        .and_then(|x| x.to_str().ok())
+    else {
+        return Representation::ActivityStream;
+    };
+
+    let mut activitystream: f32 = 0.0;
+    let mut html: f32 = 0.0;
+    for range in accept.split(',') {
+        let (media_type, params) = range.split_once(';').unwrap_or((range, ""));
+        let media_type = media_type.trim().to_ascii_lowercase();
+        match media_type.as_str() {
+            "application/activity+json" | "application/ld+json" => {
+                activitystream = activitystream.max(quality(params))
+            }
+            "text/html" | "application/xhtml+xml" => html = html.max(quality(params)),
+            _ => {}
+        }
+    }
+
+    match html > activitystream {
+        true => Representation::Html,
+        false => Representation::ActivityStream,
+    }
+}
+
+/// pages only load images and nothing that can run, in case some html gets through
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'none'; img-src https: data:; base-uri 'none'; form-action 'none'; frame-ancestors 'none'";
+
+/// html from a client or another server as paragraphs of text. neither local nor
+/// remote content is sanitized anywhere else, so none of its markup is kept
+fn content_html(content: &str) -> String {
+    text_to_html(&html_to_text(content))
+}
+
+/// the first `max` characters of some html as plain text, for meta tags
+fn summarize(content: &str, max: usize) -> String {
+    let text = html_to_text(content);
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    match text.char_indices().nth(max) {
+        Some((i, _)) => format!("{}…", &text[..i]),
+        None => text,
+    }
+}
+
+fn page(title: &str, head: &str, body: &str) -> HttpResponse {
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{}</title>
+{head}
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        escape_html(title)
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header(("Vary", "Accept"))
+        .insert_header(("Content-Security-Policy", CONTENT_SECURITY_POLICY))
+        .body(page)
+}
+
+/// the open graph tags and the link to the AS2 representation of a page
+///
+/// https://ogp.me/
+fn head_tags(
+    og_type: &str,
+    title: &str,
+    description: &str,
+    url: &str,
+    image: Option<&str>,
+    site_name: &str,
+    activitystream: &str,
+) -> String {
+    let mut tags = vec![
+        format!(
+            r#"<meta property="og:type" content="{}">"#,
+            escape_html(og_type)
+        ),
+        format!(
+            r#"<meta property="og:title" content="{}">"#,
+            escape_html(title)
+        ),
+        format!(
+            r#"<meta property="og:description" content="{}">"#,
+            escape_html(description)
+        ),
+        format!(
+            r#"<meta name="description" content="{}">"#,
+            escape_html(description)
+        ),
+        format!(r#"<meta property="og:url" content="{}">"#, escape_html(url)),
+        format!(
+            r#"<meta property="og:site_name" content="{}">"#,
+            escape_html(site_name)
+        ),
+        format!(r#"<link rel="canonical" href="{}">"#, escape_html(url)),
+        format!(
+            r#"<link rel="alternate" type="application/activity+json" href="{}">"#,
+            escape_html(activitystream)
+        ),
+    ];
+    if let Some(image) = image.filter(|x| !x.is_empty()) {
+        tags.push(format!(
+            r#"<meta property="og:image" content="{}">"#,
+            escape_html(image)
+        ));
+    }
+
+    tags.join("\n")
+}
+
+fn display_name(account: &Account) -> &str {
+    match account.display_name.is_empty() {
+        true => &account.username,
+        false => &account.display_name,
+    }
+}
+
+/// `@user@domain`, local accounts only have a username in `acct`
+fn full_handle(state: &crate::config::Config, account: &Account) -> String {
+    match account.acct.contains('@') {
+        true => format!("@{}", account.acct),
+        false => format!("@{}@{}", account.acct, state.account_domain()),
+    }
+}
+
+fn status_article(status: &Status) -> String {
+    let content = content_html(&status.content);
+    let content = match status.spoiler_text.is_empty() {
+        true => content,
+        false => format!(
+            "<details><summary>{}</summary>{content}</details>",
+            escape_html(&status.spoiler_text)
+        ),
+    };
+
+    let media: String = status
+        .media_attachments
+        .iter()
+        .map(|x| {
+            let preview = x.preview_url.as_deref().unwrap_or(&x.url);
+            format!(
+                r#"<a href="{}"><img src="{}" alt="{}" loading="lazy"></a>"#,
+                escape_html(&x.url),
+                escape_html(preview),
+                escape_html(x.description.as_deref().unwrap_or_default())
+            )
+        })
+        .collect();
+
+    let url = status.url.as_deref().unwrap_or(&status.uri);
+    format!(
+        r#"<article class="status">
+<header><a href="{}">{}</a> <span class="acct">@{}</span></header>
+<div class="content">{content}</div>
+<div class="media">{media}</div>
+<footer><a href="{}"><time datetime="{}">{}</time></a></footer>
+</article>"#,
+        escape_html(&status.account.url),
+        escape_html(display_name(&status.account)),
+        escape_html(&status.account.acct),
+        escape_html(url),
+        escape_html(&status.created_at),
+        escape_html(&status.created_at)
+    )
+}
+
+/// the page browsers get for a local actor
+pub fn profile_page(
+    state: &crate::config::Config,
+    account: &Account,
+    actor_id: &str,
+    statuses: &[Status],
+) -> HttpResponse {
+    let handle = full_handle(state, account);
+    let title = format!("{} ({handle})", display_name(account));
+    let description = summarize(&account.note, 200);
+
+    let head = head_tags(
+        "profile",
+        &title,
+        &description,
+        &account.url,
+        Some(&account.avatar),
+        state.instance_title(),
+        actor_id,
+    );
+
+    let avatar = match account.avatar.is_empty() {
+        true => String::new(),
+        false => format!(
+            r#"<img class="avatar" src="{}" alt="" width="96" height="96">"#,
+            escape_html(&account.avatar)
+        ),
+    };
+    let statuses: Vec<String> = statuses.iter().map(status_article).collect();
+
+    let body = format!(
+        r#"<main>
+<section class="profile">
+{avatar}
+<h1>{}</h1>
+<p class="acct">{}</p>
+<div class="note">{}</div>
+<p class="stats">{} posts · {} following · {} followers</p>
+</section>
+{}
+</main>"#,
+        escape_html(display_name(account)),
+        escape_html(&handle),
+        content_html(&account.note),
+        account.statuses_count,
+        account.following_count,
+        account.followers_count,
+        statuses.join("\n")
+    );
+
+    page(&title, &head, &body)
+}
+
+/// the page browsers get for a status
+pub fn status_page(state: &crate::config::Config, status: &Status) -> HttpResponse {
+    let title = format!(
+        "{} ({})",
+        display_name(&status.account),
+        full_handle(state, &status.account)
+    );
+    //content warnings stay hidden in previews
+    let description = match status.spoiler_text.is_empty() {
+        true => summarize(&status.content, 200),
+        false => status.spoiler_text.clone(),
+    };
+    let image = status
+        .media_attachments
+        .iter()
+        .find(|x| x.type_field.eq("image") && !status.sensitive)
+        .map(|x| x.url.as_str())
+        .unwrap_or(&status.account.avatar);
+
+    let head = head_tags(
+        "article",
+        &title,
+        &description,
+        status.url.as_deref().unwrap_or(&status.uri),
+        Some(image),
+        state.instance_title(),
+        &status.uri,
+    );
+
+    let body = format!("<main>\n{}\n</main>", status_article(status));
+
+    page(&title, &head, &body)
+}
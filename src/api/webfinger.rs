@@ -2,11 +2,19 @@ use actix_web::{
     error::{ErrorBadRequest, ErrorNotFound},
     get,
     web::{self, Data},
-    HttpResponse, Result,
+    HttpRequest, HttpResponse, Result,
 };
 use serde::{Deserialize, Serialize};
+use url::Url;
 
-use crate::db::conn::DbConn;
+use crate::{
+    cache_and_fetch::Cache,
+    db::{
+        actor_utilities::get_ap_actor_by_db_id,
+        conn::DbConn,
+        internal_actor::{get_actor_id_from_internal, is_suspended},
+    },
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WebfingerQuery {
@@ -16,7 +24,8 @@ pub struct WebfingerQuery {
 }
 
 impl WebfingerQuery {
-    fn parse_query(input: String) -> Self {
+    /// parses `acct:user@domain`, `user@domain` or `@user@domain`
+    pub fn parse_query(input: String) -> Self {
         let resource = input.strip_prefix("acct:");
 
         let has_prefix;
@@ -32,6 +41,8 @@ impl WebfingerQuery {
             }
         };
 
+        let resource = resource.strip_prefix('@').unwrap_or(resource);
+
         let mut vals = resource.split('@');
         let preferred_username = vals.next();
         let domain = vals.next();
@@ -60,78 +71,260 @@ impl WebfingerQuery {
     }
 }
 
-#[derive(Deserialize, Debug)]
-struct Info {
-    resource: String,
+pub const REL_SELF: &str = "self";
+pub const REL_PROFILE_PAGE: &str = "http://webfinger.net/rel/profile-page";
+
+/// https://www.rfc-editor.org/rfc/rfc7033#section-4.4.4
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JrdLink {
+    pub rel: String,
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    /// used by links like subscribe that take a parameter instead of an href
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// a JSON Resource Descriptor
+///
+/// https://www.rfc-editor.org/rfc/rfc7033#section-4.4
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Jrd {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub links: Vec<JrdLink>,
+}
+
+/// the `preferred_username` a resource points at, `acct:` handles have to be on one of
+/// our domains and urls have to be one of our actor ids or profile pages
+fn resource_username(resource: &str, state: &crate::config::Config) -> Option<String> {
+    if let Ok(url) = Url::parse(resource) {
+        if matches!(url.scheme(), "https" | "http") {
+            if !url.domain()?.eq_ignore_ascii_case(&state.instance_domain) {
+                return None;
+            }
+            let path = url.path();
+            if let Some(x) = path.strip_prefix("/users/") {
+                return Some(x.to_string());
+            }
+            if let Some(x) = path.strip_prefix("/@") {
+                return Some(x.to_string());
+            }
+            return None;
+        }
+    }
+
+    let result = WebfingerQuery::parse_query(resource.to_string());
+    if let Some(x) = result.domain {
+        if !state.is_local_domain(&x) {
+            return None;
+        }
+    }
+    result.preferred_username.filter(|x| !x.is_empty())
+}
+
+/// when handles are on a different domain than the server, that domain sends discovery
+/// requests on to us. if it proxies them here instead, redirect them to the right host
+fn redirect_to_instance_domain(
+    request: &HttpRequest,
+    state: &crate::config::Config,
+) -> Option<HttpResponse> {
+    let connection_info = request.connection_info();
+    let host = connection_info.host().split(':').next().unwrap_or_default();
+    if host.eq_ignore_ascii_case(&state.instance_domain) || !state.is_local_domain(host) {
+        return None;
+    }
+
+    let mut location = format!("https://{}{}", state.instance_domain, request.path());
+    if !request.query_string().is_empty() {
+        location = format!("{location}?{}", request.query_string());
+    }
+    Some(
+        HttpResponse::MovedPermanently()
+            .insert_header(("Location", location))
+            .finish(),
+    )
+}
+
+fn lrdd_template(state: &crate::config::Config) -> String {
+    format!(
+        "https://{}/.well-known/webfinger?resource={{uri}}",
+        state.instance_domain
+    )
+}
+
+fn host_meta_json(state: &crate::config::Config) -> HttpResponse {
+    let jrd = Jrd {
+        subject: None,
+        aliases: Vec::new(),
+        links: vec![JrdLink {
+            rel: "lrdd".to_string(),
+            type_field: Some("application/jrd+json".to_string()),
+            href: None,
+            template: Some(lrdd_template(state)),
+        }],
+    };
+
+    HttpResponse::Ok()
+        .content_type("application/jrd+json; charset=utf-8")
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .body(serde_json::to_string(&jrd).unwrap())
+}
+
+/// points clients that don't know about `/.well-known/webfinger` at it. XRD unless
+/// json is asked for
+///
+/// https://www.rfc-editor.org/rfc/rfc6415
+#[get("/.well-known/host-meta")]
+async fn host_meta(
+    request: HttpRequest,
+    state: Data<crate::config::Config>,
+) -> Result<HttpResponse> {
+    if let Some(x) = redirect_to_instance_domain(&request, &state) {
+        return Ok(x);
+    }
+
+    let accept = request
+        .headers()
+        .get("Accept")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default();
+    if accept.contains("json") && !accept.contains("xrd+xml") {
+        return Ok(host_meta_json(&state));
+    }
+
+    let host_meta = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<XRD xmlns="http://docs.oasis-open.org/ns/xri/xrd-1.0">
+  <Link rel="lrdd" type="application/jrd+json" template="{}"/>
+</XRD>
+"#,
+        lrdd_template(&state)
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/xrd+xml; charset=utf-8")
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .body(host_meta))
+}
+
+/// https://www.rfc-editor.org/rfc/rfc6415#appendix-A
+#[get("/.well-known/host-meta.json")]
+async fn host_meta_jrd(
+    request: HttpRequest,
+    state: Data<crate::config::Config>,
+) -> Result<HttpResponse> {
+    if let Some(x) = redirect_to_instance_domain(&request, &state) {
+        return Ok(x);
+    }
+
+    Ok(host_meta_json(&state))
 }
 
 #[get("/.well-known/webfinger")]
 async fn webfinger(
+    request: HttpRequest,
     state: Data<crate::config::Config>,
     conn: Data<DbConn>,
-    info: web::Query<Info>,
+    cache: Data<Cache>,
+    query: web::Query<Vec<(String, String)>>,
 ) -> Result<HttpResponse> {
-    let resource = info.into_inner().resource;
-    let result = WebfingerQuery::parse_query(resource);
-
-    if let Some(x) = result.domain {
-        if !x.eq_ignore_ascii_case(&state.instance_domain) {
-            return Err(ErrorBadRequest("not from this domain"));
-        }
+    if let Some(x) = redirect_to_instance_domain(&request, &state) {
+        return Ok(x);
     }
-    let preferred_username = match result.preferred_username {
-        Some(x) => x,
-        None => return Err(ErrorBadRequest("no preferred username provided")),
+
+    //rel can be repeated so the query can't go into a struct
+    let query = query.into_inner();
+    let Some(resource) = query
+        .iter()
+        .find(|(key, _)| key.eq("resource"))
+        .map(|(_, value)| value.as_str())
+    else {
+        return Err(ErrorBadRequest("no resource provided"));
     };
+    let rels: Vec<&str> = query
+        .iter()
+        .filter(|(key, _)| key.eq("rel"))
+        .map(|(_, value)| value.as_str())
+        .collect();
 
-    let val = sqlx::query!(
-        "SELECT activitypub_actor FROM  internal_users WHERE preferred_username = $1",
-        preferred_username
-    )
-    .fetch_optional(&conn.db)
-    .await;
+    let domain = state.instance_domain.as_str();
+    let account_domain = state.account_domain();
+    let instance_actor = &cache.instance_actor.item.actor;
 
-    let id = match val.unwrap() {
-        Some(x) => x.activitypub_actor,
-        None => {
+    let actor = if resource.eq(instance_actor.id.as_str()) {
+        instance_actor.clone()
+    } else {
+        let Some(preferred_username) = resource_username(resource, &state) else {
             return Err(ErrorNotFound("not found"));
+        };
+        //the instance actor also goes by the name of the domain like on mastodon
+        if preferred_username.eq(&instance_actor.preferred_username)
+            || state.is_local_domain(&preferred_username)
+        {
+            instance_actor.clone()
+        } else {
+            let Some(id) = get_actor_id_from_internal(&conn.db, &preferred_username)
+                .await
+                .unwrap()
+            else {
+                return Err(ErrorNotFound("not found"));
+            };
+            if is_suspended(&conn.db, &preferred_username).await.unwrap() {
+                return Err(ErrorNotFound("not found"));
+            }
+            get_ap_actor_by_db_id(id, &conn).await
         }
     };
 
-    let actor = sqlx::query!(
-        "SELECT id, preferred_username FROM activitypub_users WHERE ap_user_id = $1",
-        id
-    )
-    .fetch_one(&conn.db)
-    .await
-    .unwrap();
-
-    let preferred_uname = actor.preferred_username;
-    let domain = state.instance_domain.clone();
-
-    let subject = format!("acct:{preferred_uname}@{domain}");
-
-    let id = actor.id;
+    let id = actor.id.to_string();
+    //the instance actor has no html page to link to
+    let profile_page = match instance_actor.id.eq(&actor.id) {
+        true => None,
+        false => Some(format!("https://{domain}/@{}", &actor.preferred_username)),
+    };
 
-    let webfinger = format!(
-        r#"
+    let mut links = vec![JrdLink {
+        rel: REL_SELF.to_string(),
+        type_field: Some("application/activity+json".to_string()),
+        href: Some(id.clone()),
+        template: None,
+    }];
+    let mut aliases = vec![id];
+    if let Some(x) = profile_page {
+        links.push(JrdLink {
+            rel: REL_PROFILE_PAGE.to_string(),
+            type_field: Some("text/html".to_string()),
+            href: Some(x.clone()),
+            template: None,
+        });
+        aliases.push(x);
+    }
 
-    {{
-        "subject": "{subject}",
-    
-        "links": [
-            {{
-                "rel": "self",
-                "type": "application/activity+json",
-                "href": "{id}"
-            }}
-        ]
-    }}
+    //https://www.rfc-editor.org/rfc/rfc7033#section-4.3
+    let links = links
+        .into_iter()
+        .filter(|x| rels.is_empty() || rels.contains(&x.rel.as_str()))
+        .collect();
 
-    "#
-    );
+    let jrd = Jrd {
+        subject: Some(format!(
+            "acct:{}@{account_domain}",
+            &actor.preferred_username
+        )),
+        aliases,
+        links,
+    };
 
     Ok(HttpResponse::Ok()
         .content_type("application/jrd+json; charset=utf-8")
-        .body(webfinger))
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .body(serde_json::to_string(&jrd).unwrap()))
 }
@@ -7,9 +7,21 @@ use actix_web::web::Data;
 use url::Url;
 
 use crate::{
-    activitystream_objects::core_types::ActivityStream,
-    db::conn::DbConn,
-    protocol::{fetch::authorized_fetch, instance_actor::InstanceActor},
+    activitystream_objects::{actors::Actor, core_types::ActivityStream},
+    api::webfinger::WebfingerQuery,
+    db::{
+        actor_utilities::{
+            create_ap_actor, get_ap_actor_by_db_id, get_ap_actor_id_by_fedi_id,
+            get_ap_actor_id_by_handle, set_actor_webfinger, update_actor_profile,
+        },
+        conn::DbConn,
+        internal_actor::get_actor_id_from_internal,
+        private_key::KeyEncryptionKey,
+    },
+    media::storage::MediaStorage,
+    protocol::{
+        fetch::authorized_fetch, instance_actor::InstanceActor, webfinger::webfinger_lookup,
+    },
 };
 
 // const MAX_AGE: std::time::Duration = Duration::from_secs(40);
@@ -44,13 +56,21 @@ pub struct CacheWithText<T: Clone> {
 pub struct Cache {
     pub state: crate::config::Config,
     pub instance_actor: CacheWithText<InstanceActor>,
+    pub kek: KeyEncryptionKey,
     pub domains: RwLock<HashMap<String, DomainRequest>>,
     // pub outgoing_cache: RwLock<HashMap<String, String>>, //cache of objects being externally requested
     pub fetch: RwLock<HashMap<String, CachedItem<ActivityStream>>>, //cache of objects being fetched
+    /// where uploaded media is kept
+    pub storage: Box<dyn MediaStorage>,
 }
 
 impl Cache {
-    pub fn new(instance_actor: InstanceActor, state: crate::config::Config) -> Cache {
+    pub fn new(
+        instance_actor: InstanceActor,
+        state: crate::config::Config,
+        kek: KeyEncryptionKey,
+        storage: Box<dyn MediaStorage>,
+    ) -> Cache {
         let string_rep = serde_json::to_string(&instance_actor.actor).unwrap();
         Cache {
             state,
@@ -58,9 +78,11 @@ impl Cache {
                 item: instance_actor,
                 string_rep: Some(string_rep),
             },
+            kek,
             domains: RwLock::new(HashMap::new()),
             // outgoing_cache: RwLock::new(HashMap::new()),
             fetch: RwLock::new(HashMap::new()),
+            storage,
         }
     }
 }
@@ -73,6 +95,7 @@ async fn get_local_object(id: &Url, cache: &Cache, conn: &Data<DbConn>) -> Activ
 pub enum FetchErr {
     MaxAdverse,
     DoesNotExist,
+    NotAnActor,
 }
 
 async fn get_federated_object(
@@ -129,7 +152,10 @@ async fn get_federated_object(
     .await;
     let object = match object {
         Ok(x) => x,
-        Err(x) => todo!(),
+        Err(x) => {
+            eprintln!("failed to fetch {}: {}", id, x);
+            return Err(FetchErr::DoesNotExist);
+        }
     };
 
     // let time = SystemTime::now();
@@ -162,5 +188,151 @@ pub async fn fetch_object(
         return get_federated_object(id, cache, conn).await;
     }
 
-    todo!()
+    //ip addresses and other hosts without a domain aren't fetched
+    Err(FetchErr::DoesNotExist)
+}
+
+/// gets an actor from the db, fetching and storing it first if we haven't seen it before
+pub async fn fetch_actor(id: &Url, cache: &Cache, conn: &Data<DbConn>) -> Result<Actor, FetchErr> {
+    let existing = get_ap_actor_id_by_fedi_id(&conn.db, id.as_str())
+        .await
+        .unwrap();
+    if let Some(x) = existing {
+        return Ok(get_ap_actor_by_db_id(x, conn).await);
+    }
+
+    let Some(domain) = id.domain() else {
+        return Err(FetchErr::DoesNotExist);
+    };
+    if domain.eq_ignore_ascii_case(&cache.state.instance_domain) {
+        return Err(FetchErr::DoesNotExist);
+    }
+
+    let object = get_federated_object(id, cache, conn).await?;
+    let Some(actor) = object.get_actor() else {
+        return Err(FetchErr::NotAnActor);
+    };
+
+    //an actor can only speak for its own domain
+    if actor.id.domain() != id.domain() {
+        return Err(FetchErr::NotAnActor);
+    }
+
+    match create_ap_actor(&actor, conn).await {
+        Ok(x) => Ok(get_ap_actor_by_db_id(x, conn).await),
+        Err(_) => Ok(*actor),
+    }
+}
+
+/// gets an actor from its server even if we already have it and updates our copy, for when
+/// an old copy isn't good enough like checking the aliases of an account being moved to
+pub async fn refetch_actor(
+    id: &Url,
+    cache: &Cache,
+    conn: &Data<DbConn>,
+) -> Result<Actor, FetchErr> {
+    let Some(domain) = id.domain() else {
+        return Err(FetchErr::DoesNotExist);
+    };
+    //our own actors are always up to date
+    if domain.eq_ignore_ascii_case(&cache.state.instance_domain) {
+        return fetch_actor(id, cache, conn).await;
+    }
+
+    let object = authorized_fetch(
+        id,
+        &cache.instance_actor.item.key_id,
+        &cache.instance_actor.item.private_key,
+    )
+    .await;
+    let object = match object {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("failed to refetch {}: {}", id, x);
+            return Err(FetchErr::DoesNotExist);
+        }
+    };
+    let Some(actor) = object.get_actor() else {
+        return Err(FetchErr::NotAnActor);
+    };
+    if actor.id.domain() != id.domain() {
+        return Err(FetchErr::NotAnActor);
+    }
+
+    let existing = get_ap_actor_id_by_fedi_id(&conn.db, actor.id.as_str())
+        .await
+        .unwrap();
+    match existing {
+        Some(x) => {
+            update_actor_profile(&conn.db, &actor).await.unwrap();
+            Ok(get_ap_actor_by_db_id(x, conn).await)
+        }
+        None => match create_ap_actor(&actor, conn).await {
+            Ok(x) => Ok(get_ap_actor_by_db_id(x, conn).await),
+            Err(_) => Ok(*actor),
+        },
+    }
+}
+
+/// finds the actor behind a handle like `@user@domain`, asking the domain with webfinger
+/// if it's an actor we haven't seen before. handles without a domain are local
+pub async fn resolve_handle(
+    handle: &str,
+    cache: &Cache,
+    conn: &Data<DbConn>,
+) -> Result<Actor, FetchErr> {
+    let query = WebfingerQuery::parse_query(handle.trim().to_string());
+    let Some(preferred_username) = query.preferred_username.filter(|x| !x.is_empty()) else {
+        return Err(FetchErr::DoesNotExist);
+    };
+    let domain = query
+        .domain
+        .unwrap_or_else(|| cache.state.account_domain().to_string());
+
+    //local actors are stored under the instance domain even if their handles aren't
+    if cache.state.is_local_domain(&domain) {
+        return match get_actor_id_from_internal(&conn.db, &preferred_username)
+            .await
+            .unwrap()
+        {
+            Some(x) => Ok(get_ap_actor_by_db_id(x, conn).await),
+            None => Err(FetchErr::DoesNotExist),
+        };
+    }
+
+    let existing = get_ap_actor_id_by_handle(&conn.db, &preferred_username, &domain)
+        .await
+        .unwrap();
+    if let Some(x) = existing {
+        return Ok(get_ap_actor_by_db_id(x, conn).await);
+    }
+
+    let local_domain = &cache.state.instance_domain;
+    let found = match webfinger_lookup(&preferred_username, &domain, local_domain).await {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!(
+                "webfinger lookup of {}@{} failed: {}",
+                preferred_username, domain, x
+            );
+            return Err(FetchErr::DoesNotExist);
+        }
+    };
+    let actor = fetch_actor(&found.actor, cache, conn).await?;
+
+    //any domain can point at an actor, so an actor on another host has to have
+    //a handle there that points back to it
+    let handle = match actor.id.host_str() {
+        Some(x) if x.eq_ignore_ascii_case(&domain) => found.handle,
+        Some(x) => match webfinger_lookup(&actor.preferred_username, x, local_domain).await {
+            Ok(verified) if verified.actor == actor.id => verified.handle,
+            _ => return Err(FetchErr::DoesNotExist),
+        },
+        None => return Err(FetchErr::DoesNotExist),
+    };
+    set_actor_webfinger(&conn.db, actor.id.as_str(), &handle)
+        .await
+        .unwrap();
+
+    Ok(actor)
 }
@@ -0,0 +1,193 @@
+use std::{io::BufRead, path::Path};
+
+use actix_web::web::Data;
+use sqlx::{migrate::Migrator, PgPool};
+
+use crate::{
+    cache_and_fetch::Cache,
+    db::{
+        access_tokens::revoke_user_tokens,
+        account_creation::{
+            create_internal_actor, generate_keypair, hash_password, validate_password, SignUp,
+        },
+        actor_utilities::get_ap_actor_by_db_id,
+        conn::DbConn,
+        internal_actor::{
+            approve_user, get_actor_id_from_internal, get_uid_from_internal, set_password,
+            set_suspended,
+        },
+        private_key::set_private_key,
+        public_key::update_public_key,
+    },
+    protocol::outbox::{delete_account, post_outbox, profile_update},
+};
+
+pub const USAGE: &str = r#"usage: activity_playground [command]
+
+runs the server when no command is given. commands read gater_config.toml like the server does
+
+    generate-kek                    print a new key encryption key
+    migrate                         apply the migrations in ./migrations
+    reencrypt-keys                  encrypt every private key with key_encryption_key
+    create-user <username> [email]  make an account, the password is read from stdin
+    approve-user <username>         let an account waiting for approval log in
+    reset-password <username>       set a password read from stdin and log the account out
+    suspend-user <username>         stop an account from logging in and log it out
+    unsuspend-user <username>
+    delete-user <username>          remove an account and send Delete(Person) everywhere
+    rotate-key <username>           give an account a new signing key and send Update(Person)
+    send-update <username>          send the account's profile to its followers again
+    dump-instance-actor             print the instance actor"#;
+
+/// applies the migrations that haven't been yet, the same ones `sqlx migrate run` would
+pub async fn migrate(pool: &PgPool) -> Result<(), String> {
+    let migrator = Migrator::new(Path::new("migrations"))
+        .await
+        .map_err(|x| x.to_string())?;
+    migrator.run(pool).await.map_err(|x| x.to_string())?;
+    println!("applied migrations");
+    Ok(())
+}
+
+/// reads a line from stdin so passwords can be piped in
+fn read_password() -> Result<String, String> {
+    eprint!("password: ");
+    let mut password = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .map_err(|x| x.to_string())?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn get_uid(conn: &DbConn, username: &str) -> Result<i64, String> {
+    match get_uid_from_internal(&conn.db, username).await {
+        Ok(Some(x)) => Ok(x),
+        Ok(None) => Err(format!("no user named {username}")),
+        Err(x) => Err(x.to_string()),
+    }
+}
+
+/// sends an `Update` with the user's current profile to their followers
+async fn send_update(conn: &Data<DbConn>, cache: &Cache, username: &str) -> Result<(), String> {
+    let uid = get_uid(conn, username).await?;
+    let ap_user_id = get_actor_id_from_internal(&conn.db, username)
+        .await
+        .map_err(|x| x.to_string())?
+        .ok_or(format!("no user named {username}"))?;
+    let actor = get_ap_actor_by_db_id(ap_user_id, conn).await;
+
+    post_outbox(conn, cache, uid, username, profile_update(&actor))
+        .await
+        .map_err(|x| x.to_string())?;
+    Ok(())
+}
+
+/// runs a command that needs the instance set up, `args` are the ones after the command
+pub async fn run(
+    command: &str,
+    args: &[String],
+    conn: Data<DbConn>,
+    cache: Data<Cache>,
+    state: Data<crate::config::Config>,
+) -> Result<(), String> {
+    let username = match (command, args.first()) {
+        ("dump-instance-actor", _) => String::new(),
+        (_, Some(x)) => x.clone(),
+        (_, None) => return Err(USAGE.to_string()),
+    };
+
+    match command {
+        "create-user" => {
+            let password = read_password()?;
+            let sign_up = SignUp {
+                email: args.get(1).cloned(),
+                ..Default::default()
+            };
+            let uid = create_internal_actor(state, conn, username.clone(), password, &sign_up)
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("created {username} with uid {uid}");
+        }
+        "approve-user" => {
+            let uid = get_uid(&conn, &username).await?;
+            match approve_user(&conn.db, uid)
+                .await
+                .map_err(|x| x.to_string())?
+            {
+                true => println!("approved {username}"),
+                false => println!("{username} was already approved"),
+            }
+        }
+        "reset-password" => {
+            let uid = get_uid(&conn, &username).await?;
+            let password = read_password()?;
+            validate_password(&username, &password).map_err(|x| x.to_string())?;
+            let password = hash_password(&password).map_err(|x| x.to_string())?;
+
+            let mut transaction = conn.db.begin().await.map_err(|x| x.to_string())?;
+            set_password(&mut *transaction, uid, &password)
+                .await
+                .map_err(|x| x.to_string())?;
+            let revoked = revoke_user_tokens(&mut *transaction, uid)
+                .await
+                .map_err(|x| x.to_string())?;
+            transaction.commit().await.map_err(|x| x.to_string())?;
+            println!("reset the password of {username} and revoked {revoked} tokens");
+        }
+        "suspend-user" | "unsuspend-user" => {
+            let uid = get_uid(&conn, &username).await?;
+            let suspend = command.eq("suspend-user");
+            let changed = set_suspended(&conn.db, uid, suspend)
+                .await
+                .map_err(|x| x.to_string())?;
+            if suspend {
+                revoke_user_tokens(&conn.db, uid)
+                    .await
+                    .map_err(|x| x.to_string())?;
+            }
+            match (changed, suspend) {
+                (true, true) => println!("suspended {username}"),
+                (true, false) => println!("unsuspended {username}"),
+                (false, true) => println!("{username} was already suspended"),
+                (false, false) => println!("{username} wasn't suspended"),
+            }
+        }
+        "delete-user" => {
+            let uid = get_uid(&conn, &username).await?;
+            let inboxes = delete_account(&conn, &cache, uid, &username)
+                .await
+                .map_err(|x| x.to_string())?;
+            println!("deleted {username} and sent Delete(Person) to {inboxes} inboxes");
+        }
+        "rotate-key" => {
+            let uid = get_uid(&conn, &username).await?;
+            let actor_id = format!("https://{}/users/{}", state.instance_domain, &username);
+            let (private_key, public) = generate_keypair(&cache.kek);
+
+            let mut transaction = conn.db.begin().await.map_err(|x| x.to_string())?;
+            set_private_key(&mut *transaction, uid, &private_key)
+                .await
+                .map_err(|x| x.to_string())?;
+            update_public_key(&mut *transaction, &actor_id, &public)
+                .await
+                .map_err(|x| x.to_string())?;
+            transaction.commit().await.map_err(|x| x.to_string())?;
+            println!("rotated the key of {username}");
+
+            send_update(&conn, &cache, &username).await?;
+            println!("sent Update(Person)");
+        }
+        "send-update" => {
+            send_update(&conn, &cache, &username).await?;
+            println!("sent Update(Person)");
+        }
+        "dump-instance-actor" => {
+            let actor = cache.instance_actor.item.actor.clone().to_activitystream();
+            println!("{}", serde_json::to_string_pretty(&actor).unwrap());
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+
+    Ok(())
+}
@@ -3,8 +3,85 @@ use serde::Deserialize;
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     pub database_url: String,
+    /// the domain everything is served from and that ids are built with
     pub instance_domain: String,
+    /// the domain in handles, `@user@account_domain`. lets handles live on a shorter
+    /// domain than the server, like mastodon's LOCAL_DOMAIN and WEB_DOMAIN.
+    /// defaults to `instance_domain`
+    pub account_domain: Option<String>,
     pub bind_address: String,
     pub contact_email: String,
+    /// name of the instance, defaults to the account domain
+    pub instance_title: Option<String>,
+    #[serde(default)]
+    pub instance_description: String,
+    /// server rules shown to clients, in order
+    #[serde(default)]
+    pub rules: Vec<String>,
     pub port: u16,
+    /// base64 encoded 32 byte key used to encrypt private keys at rest,
+    /// prefer setting it with the `KEY_ENCRYPTION_KEY` environment variable
+    pub key_encryption_key: String,
+    /// set while rotating the key encryption key so `reencrypt-keys`
+    /// can still read rows encrypted with the old one
+    pub previous_key_encryption_key: Option<String>,
+    /// directory uploaded media is stored in
+    #[serde(default = "default_media_path")]
+    pub media_path: String,
+    /// largest upload accepted, in bytes
+    #[serde(default = "default_max_upload_size")]
+    pub max_upload_size: usize,
+    /// who can sign up through `/api/v1/accounts`
+    #[serde(default)]
+    pub registrations: RegistrationMode,
+}
+
+/// who can sign up
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    /// nobody, accounts can only be made by an admin
+    #[default]
+    Closed,
+    Open,
+    /// anyone can sign up but can't log in until they're approved, unless they had an invite
+    Approval,
+    /// only people with an invite
+    Invite,
+}
+
+impl Config {
+    pub fn account_domain(&self) -> &str {
+        self.account_domain
+            .as_deref()
+            .unwrap_or(self.instance_domain.as_str())
+    }
+
+    pub fn instance_title(&self) -> &str {
+        self.instance_title
+            .as_deref()
+            .unwrap_or(self.account_domain())
+    }
+
+    /// if anyone can sign up without an invite, shown in nodeinfo
+    pub fn open_registrations(&self) -> bool {
+        matches!(
+            self.registrations,
+            RegistrationMode::Open | RegistrationMode::Approval
+        )
+    }
+
+    /// if `domain` is either of ours
+    pub fn is_local_domain(&self, domain: &str) -> bool {
+        domain.eq_ignore_ascii_case(&self.instance_domain)
+            || domain.eq_ignore_ascii_case(self.account_domain())
+    }
+}
+
+fn default_media_path() -> String {
+    "media".to_string()
+}
+
+fn default_max_upload_size() -> usize {
+    40 * 1024 * 1024
 }
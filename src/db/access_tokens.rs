@@ -0,0 +1,175 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sqlx::query;
+
+use crate::protocol::verification::generate_digest;
+
+/// how long a token issued by logging in stays valid
+pub const TOKEN_LIFETIME_MILIS: i64 = 1000 * 60 * 60 * 24 * 30;
+
+/// the scopes given to tokens issued by logging in directly
+pub const FULL_SCOPES: &str = "read write follow";
+
+pub struct TokenOwner {
+    pub uid: i64,
+    pub preferred_username: String,
+    /// space separated
+    pub scopes: String,
+    /// the oauth app the token was issued to, none for tokens from /login
+    pub app_id: Option<i64>,
+}
+
+/// unpadded url safe base64, as used by bearer tokens and PKCE
+pub fn base64_url(bytes: &[u8]) -> String {
+    openssl::base64::encode_block(bytes)
+        .replace('+', "-")
+        .replace('/', "_")
+        .replace('=', "")
+}
+
+/// generates a new url safe bearer token, only its hash should ever be stored
+pub fn generate_token() -> String {
+    let mut token = [0u8; 32];
+    openssl::rand::rand_bytes(&mut token).unwrap();
+    base64_url(&token)
+}
+
+pub fn hash_token(token: &str) -> String {
+    generate_digest(token.as_bytes())
+}
+
+pub fn now_milis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// stores a token for the user and returns its id
+pub async fn insert_access_token<'e, 'c: 'e, E>(
+    executor: E,
+    uid: i64,
+    token: &str,
+    expires: Option<i64>,
+    app_id: Option<i64>,
+    scopes: &str,
+) -> Result<i64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO access_tokens
+            (token_hash, uid, created, expires, app_id, scopes)
+        VALUES
+            ($1, $2, $3, $4, $5, $6)
+        RETURNING token_id
+        "#,
+        hash_token(token),
+        uid,
+        now_milis(),
+        expires,
+        app_id,
+        scopes
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.token_id),
+        Err(x) => Err(x),
+    }
+}
+
+/// gets the local user a token belongs to, expired tokens and tokens of accounts
+/// waiting for approval or suspended are treated as non existent
+pub async fn get_token_owner<'e, 'c: 'e, E>(
+    executor: E,
+    token: &str,
+) -> Result<Option<TokenOwner>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT internal_users.uid, internal_users.preferred_username,
+                access_tokens.scopes, access_tokens.app_id
+            FROM access_tokens
+            INNER JOIN internal_users ON access_tokens.uid = internal_users.uid
+            WHERE access_tokens.token_hash = $1
+            AND (access_tokens.expires IS NULL OR access_tokens.expires > $2)
+            AND internal_users.approved AND NOT internal_users.suspended
+        "#,
+        hash_token(token),
+        now_milis()
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| TokenOwner {
+            uid: x.uid,
+            preferred_username: x.preferred_username,
+            scopes: x.scopes,
+            app_id: x.app_id,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+/// returns true if a token was revoked
+pub async fn revoke_access_token<'e, 'c: 'e, E>(
+    executor: E,
+    token: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "DELETE FROM access_tokens WHERE token_hash = $1",
+        hash_token(token)
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// revokes a token only if it was issued to the given app, used by oauth clients
+pub async fn revoke_app_token<'e, 'c: 'e, E>(
+    executor: E,
+    token: &str,
+    app_id: i64,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "DELETE FROM access_tokens WHERE token_hash = $1 AND app_id = $2",
+        hash_token(token),
+        app_id
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// revokes every token a user has, returns how many there were
+pub async fn revoke_user_tokens<'e, 'c: 'e, E>(executor: E, uid: i64) -> Result<u64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!("DELETE FROM access_tokens WHERE uid = $1", uid)
+        .execute(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected()),
+        Err(x) => Err(x),
+    }
+}
@@ -8,7 +8,10 @@ use sqlx::query;
 
 use crate::{
     activitystream_objects::actors::ActorType,
-    db::{internal_actor::get_actor_id_from_internal, public_key::insert_public_key},
+    db::{
+        internal_actor::is_username_taken, invites::use_invite, objects::get_tombstone,
+        private_key::KeyEncryptionKey, public_key::insert_public_key,
+    },
 };
 
 use super::conn::DbConn;
@@ -20,6 +23,8 @@ pub struct UserLinks {
     pub followers: String,
     pub following: String,
     pub liked: String,
+    /// profile page
+    pub url: String,
 }
 
 fn generate_links(domain: &str, uname: &str) -> UserLinks {
@@ -30,68 +35,212 @@ fn generate_links(domain: &str, uname: &str) -> UserLinks {
         followers: format!("https://{domain}/users/{uname}/followers"),
         following: format!("https://{domain}/users/{uname}/following"),
         liked: format!("https://{domain}/users/{uname}/liked"),
+        url: format!("https://{domain}/@{uname}"),
     }
 }
 
+/// usernames can't be longer than this
+pub const MAX_USERNAME_LENGTH: usize = 30;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+/// hashing is slow on purpose so there's a limit
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+/// names that would clash with routes, the instance actor or be mistaken for staff
+const RESERVED_USERNAMES: [&str; 19] = [
+    "actor",
+    "inbox",
+    "outbox",
+    "bayou.internal",
+    "admin",
+    "administrator",
+    "root",
+    "api",
+    "users",
+    "media",
+    "proxy",
+    "oauth",
+    "login",
+    "logout",
+    "nodeinfo",
+    "about",
+    "postmaster",
+    "abuse",
+    "security",
+];
+
+#[derive(Debug)]
+pub enum AccountCreationErr {
+    InvalidUsername(&'static str),
+    UsernameTaken,
+    InvalidPassword(&'static str),
+    /// the invite doesn't exist, expired or was used up
+    InvalidInvite,
+    KeyErr,
+    HashErr,
+    DbErr(sqlx::Error),
+}
+
+impl std::fmt::Display for AccountCreationErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountCreationErr::InvalidUsername(x) => write!(f, "Username {}", x),
+            AccountCreationErr::UsernameTaken => write!(f, "Username is already taken"),
+            AccountCreationErr::InvalidPassword(x) => write!(f, "Password {}", x),
+            AccountCreationErr::InvalidInvite => write!(f, "Invite is invalid or expired"),
+            AccountCreationErr::KeyErr => write!(f, "KeyErr"),
+            AccountCreationErr::HashErr => write!(f, "HashErr"),
+            AccountCreationErr::DbErr(x) => write!(f, "DbErr: {}", x),
+        }
+    }
+}
+
+impl From<sqlx::Error> for AccountCreationErr {
+    fn from(value: sqlx::Error) -> Self {
+        match &value {
+            sqlx::Error::Database(x) if x.is_unique_violation() => {
+                AccountCreationErr::UsernameTaken
+            }
+            _ => AccountCreationErr::DbErr(value),
+        }
+    }
+}
+
+/// usernames end up in urls and handles so they're limited to ascii letters, digits
+/// and underscores
+pub fn validate_username(username: &str) -> Result<(), AccountCreationErr> {
+    if username.is_empty() {
+        return Err(AccountCreationErr::InvalidUsername("can't be blank"));
+    }
+    if username.len() > MAX_USERNAME_LENGTH {
+        return Err(AccountCreationErr::InvalidUsername("is too long"));
+    }
+    if !username
+        .chars()
+        .all(|x| x.is_ascii_alphanumeric() || x == '_')
+    {
+        return Err(AccountCreationErr::InvalidUsername(
+            "must only contain letters, numbers and underscores",
+        ));
+    }
+    if RESERVED_USERNAMES
+        .iter()
+        .any(|x| x.eq_ignore_ascii_case(username))
+    {
+        return Err(AccountCreationErr::InvalidUsername("is reserved"));
+    }
+    Ok(())
+}
+
+pub fn validate_password(username: &str, password: &str) -> Result<(), AccountCreationErr> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        return Err(AccountCreationErr::InvalidPassword("is too short"));
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        return Err(AccountCreationErr::InvalidPassword("is too long"));
+    }
+    if password.eq_ignore_ascii_case(username) {
+        return Err(AccountCreationErr::InvalidPassword(
+            "can't be the same as the username",
+        ));
+    }
+    Ok(())
+}
+
+/// a new rsa key for an actor, the private key is encrypted with the kek
+/// and the public key is a pem
+pub fn generate_keypair(kek: &KeyEncryptionKey) -> (String, String) {
+    let rsa = Rsa::generate(2048).unwrap();
+
+    let private_key = kek.encrypt(&String::from_utf8(rsa.private_key_to_pem().unwrap()).unwrap());
+
+    let public = String::from_utf8(rsa.public_key_to_pem().unwrap()).unwrap();
+
+    (private_key, public)
+}
+
+/// argon2 with a random salt, for storing in `internal_users.password`
+pub fn hash_password(password: &str) -> Result<String, AccountCreationErr> {
+    let salt = SaltString::generate(&mut OsRng);
+    match Argon2::default().hash_password(password.as_bytes(), &salt) {
+        Ok(x) => Ok(x.to_string()),
+        Err(_) => Err(AccountCreationErr::HashErr),
+    }
+}
+
+/// what's known about how an account signed up
+#[derive(Debug, Clone, Default)]
+pub struct SignUp {
+    pub email: Option<String>,
+    /// why they want to join, for whoever approves them
+    pub reason: Option<String>,
+    /// used up when the account is made
+    pub invite: Option<String>,
+    /// the account can't log in until it's approved
+    pub pending: bool,
+}
+
 pub async fn create_internal_actor(
     state: Data<crate::config::Config>,
     conn: Data<DbConn>,
     username: String,
     password: String,
-) -> Result<i64, ()> {
-    let mut transaction = conn.db.begin().await.unwrap();
+    sign_up: &SignUp,
+) -> Result<i64, AccountCreationErr> {
+    validate_username(&username)?;
+    validate_password(&username, &password)?;
 
-    //confirm that the username is not taken
-    let val = get_actor_id_from_internal(&mut *transaction, "test").await;
+    let Ok(kek) = KeyEncryptionKey::from_config(&state) else {
+        return Err(AccountCreationErr::KeyErr);
+    };
+
+    let mut transaction = conn.db.begin().await?;
 
-    if val.unwrap().is_some() {
-        return Err(());
+    //confirm that the username is not taken
+    if is_username_taken(&mut *transaction, &username).await? {
+        return Err(AccountCreationErr::UsernameTaken);
     };
 
     let links = generate_links(&state.instance_domain, &username);
 
-    let rsa = Rsa::generate(2048).unwrap();
+    //names of deleted accounts stay taken so their ids are never reused
+    if get_tombstone(&mut *transaction, &links.id).await?.is_some() {
+        return Err(AccountCreationErr::UsernameTaken);
+    }
 
-    let private_key = String::from_utf8(rsa.private_key_to_pem().unwrap()).unwrap();
+    if let Some(invite) = &sign_up.invite {
+        if !use_invite(&mut *transaction, invite).await? {
+            return Err(AccountCreationErr::InvalidInvite);
+        }
+    }
 
-    let public = rsa.public_key_to_pem().unwrap();
+    let (private_key, public) = generate_keypair(&kek);
 
     let key_id = format!(
         "https://{}/users/{}#main-key",
         &state.instance_domain, &username
     );
-    dbg!(&key_id);
-
-    let x =
-        insert_into_ap_users(&mut *transaction, &username, &state.instance_domain, &links).await;
-
-    let _key_id = insert_public_key(
-        &mut *transaction,
-        &key_id,
-        &links.id,
-        &String::from_utf8(public).unwrap(),
-    )
-    .await
-    .unwrap();
-
-    let actor = x.unwrap();
 
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2.hash_password(password.as_bytes(), &salt);
+    let actor =
+        insert_into_ap_users(&mut *transaction, &username, &state.instance_domain, &links).await?;
 
-    if password_hash.is_err() {
-        return Err(());
-    }
+    insert_public_key(&mut *transaction, &key_id, &links.id, &public).await?;
 
-    let pass = password_hash.unwrap().to_string();
+    let pass = hash_password(&password)?;
 
-    let uid =
-        insert_into_local_users(&mut *transaction, &pass, &username, actor, &private_key).await;
+    let uid = insert_into_local_users(
+        &mut *transaction,
+        &pass,
+        &username,
+        actor,
+        &private_key,
+        sign_up,
+    )
+    .await?;
 
-    transaction.commit().await.unwrap();
+    transaction.commit().await?;
 
-    Ok(uid.unwrap())
+    Ok(uid)
 }
 
 pub async fn insert_into_ap_users<'e, 'c: 'e, E>(
@@ -104,11 +253,12 @@ where
     E: 'e + sqlx::PgExecutor<'c>,
 {
     let type_field = serde_json::to_string(&ActorType::Person).unwrap();
+    let url = serde_json::to_string(&links.url).unwrap();
     let val = query!(
         r#"INSERT INTO activitypub_users
-            (id, type_field, preferred_username, domain, inbox, outbox, followers, following, liked)
+            (id, type_field, preferred_username, domain, inbox, outbox, followers, following, liked, url)
         VALUES
-            ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         RETURNING ap_user_id
         "#,
         links.id,
@@ -120,6 +270,7 @@ where
         links.followers,
         links.following,
         links.liked,
+        url,
     )
     .fetch_one(executor)
     .await;
@@ -136,21 +287,25 @@ pub async fn insert_into_local_users<'e, 'c: 'e, E>(
     username: &str,
     actor: i64,
     private_key: &str,
+    sign_up: &SignUp,
 ) -> Result<i64, sqlx::Error>
 where
     E: 'e + sqlx::PgExecutor<'c>,
 {
     let val = query!(
         r#"INSERT INTO internal_users 
-            (password, preferred_username, activitypub_actor, private_key )
+            (password, preferred_username, activitypub_actor, private_key, approved, email, registration_reason)
         VALUES
-            ($1, $2, $3, $4)
+            ($1, $2, $3, $4, $5, $6, $7)
         RETURNING uid
         "#,
         pass,
         &username,
         actor,
-        private_key
+        private_key,
+        !sign_up.pending,
+        sign_up.email,
+        sign_up.reason
     )
     .fetch_one(executor)
     .await;
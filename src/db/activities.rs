@@ -0,0 +1,158 @@
+use sqlx::query;
+
+use super::access_tokens::now_milis;
+
+#[derive(Debug, Clone)]
+pub struct StoredActivity {
+    pub act_id: i64,
+    pub id: Option<String>,
+    /// serialized [`crate::activitystream_objects::activities::ActivityType`]
+    pub type_field: String,
+    pub actor: String,
+    pub object: String,
+    pub body: Option<String>,
+    pub published: i64,
+}
+
+/// reserves a row for an activity so its id can be generated from the act_id,
+/// don't forget to set the id and body with [`set_activity_body`]
+pub async fn insert_activity<'e, 'c: 'e, E>(
+    executor: E,
+    type_field: &str,
+    actor: &str,
+    object: &str,
+) -> Result<i64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO activities
+            (type_field, actor, object, published)
+        VALUES
+            ($1, $2, $3, $4)
+        RETURNING act_id
+        "#,
+        type_field,
+        actor,
+        object,
+        now_milis()
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.act_id),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn set_activity_body<'e, 'c: 'e, E>(
+    executor: E,
+    act_id: i64,
+    id: &str,
+    body: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "UPDATE activities SET id = $1, body = $2 WHERE act_id = $3",
+        id,
+        body,
+        act_id
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_activity_by_fedi_id<'e, 'c: 'e, E>(
+    executor: E,
+    id: &str,
+) -> Result<Option<StoredActivity>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(r#"SELECT * FROM activities WHERE id = $1"#, id)
+        .fetch_optional(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| StoredActivity {
+            act_id: x.act_id,
+            id: x.id,
+            type_field: x.type_field,
+            actor: x.actor,
+            object: x.object,
+            body: x.body,
+            published: x.published,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_activity_by_db_id<'e, 'c: 'e, E>(
+    executor: E,
+    act_id: i64,
+) -> Result<Option<StoredActivity>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(r#"SELECT * FROM activities WHERE act_id = $1"#, act_id)
+        .fetch_optional(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| StoredActivity {
+            act_id: x.act_id,
+            id: x.id,
+            type_field: x.type_field,
+            actor: x.actor,
+            object: x.object,
+            body: x.body,
+            published: x.published,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+/// the newest activity of a type that `actor` sent about `object`
+pub async fn get_latest_activity<'e, 'c: 'e, E>(
+    executor: E,
+    type_field: &str,
+    actor: &str,
+    object: &str,
+) -> Result<Option<StoredActivity>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT * FROM activities
+            WHERE type_field = $1 AND actor = $2 AND object = $3
+            ORDER BY act_id DESC
+            LIMIT 1
+        "#,
+        type_field,
+        actor,
+        object
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| StoredActivity {
+            act_id: x.act_id,
+            id: x.id,
+            type_field: x.type_field,
+            actor: x.actor,
+            object: x.object,
+            body: x.body,
+            published: x.published,
+        })),
+        Err(x) => Err(x),
+    }
+}
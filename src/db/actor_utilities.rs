@@ -9,6 +9,7 @@ use crate::activitystream_objects::{
 
 use super::{
     conn::DbConn,
+    objects::{from_json, to_json},
     public_key::{get_actor_public_key, insert_actor_public_key},
 };
 
@@ -63,9 +64,12 @@ where
 
     let val = query!(
         r#"INSERT INTO activitypub_users 
-            (id, type_field, preferred_username, domain, inbox, outbox, followers, following, liked)
+            (id, type_field, preferred_username, domain, inbox, outbox, followers, following, liked,
+            name, summary, url, icon, image, featured, featured_tags, manually_approves_followers,
+            discoverable, indexable, memorial, attachment, also_known_as, moved_to)
         VALUES
-            ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21,
+            $22, $23)
         RETURNING ap_user_id
         "#,
         actor_id,
@@ -76,7 +80,21 @@ where
         actor.outbox,
         actor.followers,
         actor.following,
-        actor.liked
+        actor.liked,
+        actor.name,
+        actor.summary.clone().unwrap_or_default(),
+        to_json(&actor.url),
+        to_json(&actor.icon),
+        to_json(&actor.image),
+        actor.featured.as_ref().map(|x| x.as_str()),
+        actor.featured_tags.as_ref().map(|x| x.as_str()),
+        actor.manually_approves_followers.unwrap_or(false),
+        actor.discoverable,
+        actor.indexable,
+        actor.memorial.unwrap_or(false),
+        to_json(&actor.attachment),
+        to_json(&actor.also_known_as),
+        actor.moved_to.as_ref().map(|x| x.as_str())
     )
     .fetch_one(executor)
     .await;
@@ -87,6 +105,73 @@ where
     }
 }
 
+/// records that an actor moved to another account
+pub async fn set_moved_to<'e, 'c: 'e, E>(
+    executor: E,
+    id: &str,
+    moved_to: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "UPDATE activitypub_users SET moved_to = $2 WHERE id = $1",
+        id,
+        moved_to
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// replaces the profile of an actor, everything but its links and key
+pub async fn update_actor_profile<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &Actor,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let type_field = serde_json::to_string(&actor.type_field).unwrap();
+
+    let val = query!(
+        r#"UPDATE activitypub_users SET
+            type_field = $1, name = $2, summary = $3, url = $4, icon = $5, image = $6,
+            featured = $7, featured_tags = $8, manually_approves_followers = $9,
+            discoverable = $10, indexable = $11, memorial = $12, attachment = $13,
+            also_known_as = $14, moved_to = $15
+        WHERE id = $16
+        "#,
+        type_field,
+        actor.name,
+        actor.summary.clone().unwrap_or_default(),
+        to_json(&actor.url),
+        to_json(&actor.icon),
+        to_json(&actor.image),
+        actor.featured.as_ref().map(|x| x.as_str()),
+        actor.featured_tags.as_ref().map(|x| x.as_str()),
+        actor.manually_approves_followers.unwrap_or(false),
+        actor.discoverable,
+        actor.indexable,
+        actor.memorial.unwrap_or(false),
+        to_json(&actor.attachment),
+        to_json(&actor.also_known_as),
+        actor.moved_to.as_ref().map(|x| x.as_str()),
+        actor.id.as_str()
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
 pub async fn get_ap_actor_by_db_id(id: i64, conn: &Data<DbConn>) -> Actor {
     let actor = sqlx::query!("SELECT * FROM activitypub_users WHERE ap_user_id = $1", id)
         .fetch_one(&conn.db)
@@ -113,6 +198,20 @@ pub async fn get_ap_actor_by_db_id(id: i64, conn: &Data<DbConn>) -> Actor {
         ap_user_id: Some(actor.ap_user_id),
         domain: Some(actor.domain),
         liked: actor.liked,
+        name: actor.name,
+        summary: Some(actor.summary).filter(|x| !x.is_empty()),
+        url: from_json(actor.url),
+        icon: from_json(actor.icon),
+        image: from_json(actor.image),
+        featured: actor.featured.and_then(|x| url::Url::parse(&x).ok()),
+        featured_tags: actor.featured_tags.and_then(|x| url::Url::parse(&x).ok()),
+        manually_approves_followers: Some(actor.manually_approves_followers),
+        discoverable: actor.discoverable,
+        indexable: actor.indexable,
+        memorial: Some(actor.memorial).filter(|x| *x),
+        attachment: from_json(actor.attachment),
+        also_known_as: from_json(actor.also_known_as),
+        moved_to: actor.moved_to.and_then(|x| url::Url::parse(&x).ok()),
     }
 }
 
@@ -148,5 +247,131 @@ pub async fn get_ap_actor_by_fedi_id(
         ap_user_id: Some(actor.ap_user_id),
         domain: Some(actor.domain),
         liked: actor.liked,
+        name: actor.name,
+        summary: Some(actor.summary).filter(|x| !x.is_empty()),
+        url: from_json(actor.url),
+        icon: from_json(actor.icon),
+        image: from_json(actor.image),
+        featured: actor.featured.and_then(|x| url::Url::parse(&x).ok()),
+        featured_tags: actor.featured_tags.and_then(|x| url::Url::parse(&x).ok()),
+        manually_approves_followers: Some(actor.manually_approves_followers),
+        discoverable: actor.discoverable,
+        indexable: actor.indexable,
+        memorial: Some(actor.memorial).filter(|x| *x),
+        attachment: from_json(actor.attachment),
+        also_known_as: from_json(actor.also_known_as),
+        moved_to: actor.moved_to.and_then(|x| url::Url::parse(&x).ok()),
+    }
+}
+
+pub struct ActorStats {
+    pub followers_count: i64,
+    pub following_count: i64,
+    pub statuses_count: i64,
+    /// timestamp in milis of their newest object
+    pub last_status_at: Option<i64>,
+    /// timestamp in milis of when we first stored the actor
+    pub created_at: i64,
+}
+
+pub async fn get_actor_stats<'e, 'c: 'e, E>(
+    executor: E,
+    ap_user_id: i64,
+) -> Result<ActorStats, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT
+            (SELECT COUNT(*) FROM following WHERE following.following = activitypub_users.id) AS "followers_count!",
+            (SELECT COUNT(*) FROM following WHERE following.actor = activitypub_users.id) AS "following_count!",
+            (SELECT COUNT(*) FROM objects WHERE objects.ap_user_id = activitypub_users.ap_user_id) AS "statuses_count!",
+            (SELECT MAX(published) FROM objects WHERE objects.ap_user_id = activitypub_users.ap_user_id) AS last_status_at,
+            created_at
+        FROM activitypub_users WHERE ap_user_id = $1
+        "#,
+        ap_user_id
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(ActorStats {
+            followers_count: x.followers_count,
+            following_count: x.following_count,
+            statuses_count: x.statuses_count,
+            last_status_at: x.last_status_at,
+            created_at: x.created_at,
+        }),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_ap_actor_id_by_fedi_id<'e, 'c: 'e, E>(
+    executor: E,
+    id: &str,
+) -> Result<Option<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!("SELECT ap_user_id FROM activitypub_users WHERE id = $1", id)
+        .fetch_optional(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| x.ap_user_id)),
+        Err(x) => Err(x),
+    }
+}
+
+/// finds an actor we already know about by `preferred_username` and domain. a handle
+/// remembered from webfinger wins over one guessed from where the actor is hosted
+pub async fn get_ap_actor_id_by_handle<'e, 'c: 'e, E>(
+    executor: E,
+    preferred_username: &str,
+    domain: &str,
+) -> Result<Option<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT ap_user_id FROM activitypub_users
+            WHERE LOWER(webfinger) = LOWER($1 || '@' || $2)
+                OR (LOWER(preferred_username) = LOWER($1) AND LOWER(domain) = LOWER($2))
+            ORDER BY webfinger IS NULL, ap_user_id
+            LIMIT 1
+        "#,
+        preferred_username,
+        domain
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| x.ap_user_id)),
+        Err(x) => Err(x),
+    }
+}
+
+/// remembers the handle webfinger resolved to the actor `id`
+pub async fn set_actor_webfinger<'e, 'c: 'e, E>(
+    executor: E,
+    id: &str,
+    handle: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "UPDATE activitypub_users SET webfinger = $1 WHERE id = $2",
+        handle,
+        id
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
     }
 }
@@ -0,0 +1,322 @@
+use sqlx::query;
+
+use crate::media::processing::MediaInfo;
+
+use super::access_tokens::now_milis;
+
+#[derive(Debug, Clone)]
+pub struct MediaFile {
+    pub file_id: i64,
+    /// key the file is kept under in [`crate::media::storage::MediaStorage`]
+    pub storage_key: String,
+    pub media_type: String,
+    /// in bytes
+    pub size: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub blurhash: Option<String>,
+    /// alt text
+    pub description: Option<String>,
+    pub uploader: Option<i64>,
+    pub created: i64,
+    /// where the file was fetched from if it came through the media proxy
+    pub remote_url: Option<String>,
+    /// storage key of the smaller version made by [`crate::media::processing::process_upload`]
+    pub preview_key: Option<String>,
+    pub preview_media_type: Option<String>,
+    pub preview_width: Option<i32>,
+    pub preview_height: Option<i32>,
+}
+
+pub struct NewFile<'a> {
+    pub storage_key: &'a str,
+    pub media_type: &'a str,
+    pub size: i64,
+    pub info: &'a MediaInfo,
+    pub description: Option<&'a str>,
+    pub uploader: Option<i64>,
+    pub preview: Option<NewPreview<'a>>,
+}
+
+pub struct NewPreview<'a> {
+    pub storage_key: &'a str,
+    pub media_type: &'a str,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub async fn insert_file<'e, 'c: 'e, E>(executor: E, file: NewFile<'_>) -> Result<i64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO files
+            (storage_key, media_type, size, width, height, blurhash, description, uploader, created,
+            preview_key, preview_media_type, preview_width, preview_height)
+        VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        RETURNING file_id
+        "#,
+        file.storage_key,
+        file.media_type,
+        file.size,
+        file.info.width.map(|x| x as i32),
+        file.info.height.map(|x| x as i32),
+        file.info.blurhash,
+        file.description,
+        file.uploader,
+        now_milis(),
+        file.preview.as_ref().map(|x| x.storage_key),
+        file.preview.as_ref().map(|x| x.media_type),
+        file.preview.as_ref().map(|x| x.width as i32),
+        file.preview.as_ref().map(|x| x.height as i32),
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.file_id),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_file<'e, 'c: 'e, E>(
+    executor: E,
+    file_id: i64,
+) -> Result<Option<MediaFile>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(r#"SELECT * FROM files WHERE file_id = $1"#, file_id)
+        .fetch_optional(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| MediaFile {
+            file_id: x.file_id,
+            storage_key: x.storage_key,
+            media_type: x.media_type,
+            size: x.size,
+            width: x.width,
+            height: x.height,
+            blurhash: x.blurhash,
+            description: x.description,
+            uploader: x.uploader,
+            created: x.created,
+            remote_url: x.remote_url,
+            preview_key: x.preview_key,
+            preview_media_type: x.preview_media_type,
+            preview_width: x.preview_width,
+            preview_height: x.preview_height,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+/// stores a file fetched from `remote_url`. if another request already stored it
+/// the existing row is kept and its id returned
+pub async fn insert_remote_file<'e, 'c: 'e, E>(
+    executor: E,
+    file: NewFile<'_>,
+    remote_url: &str,
+) -> Result<i64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO files
+            (storage_key, media_type, size, width, height, blurhash, description, uploader, created, remote_url)
+        VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (remote_url) DO UPDATE SET remote_url = EXCLUDED.remote_url
+        RETURNING file_id
+        "#,
+        file.storage_key,
+        file.media_type,
+        file.size,
+        file.info.width.map(|x| x as i32),
+        file.info.height.map(|x| x as i32),
+        file.info.blurhash,
+        file.description,
+        file.uploader,
+        now_milis(),
+        remote_url
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.file_id),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_file_by_remote_url<'e, 'c: 'e, E>(
+    executor: E,
+    remote_url: &str,
+) -> Result<Option<MediaFile>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(r#"SELECT * FROM files WHERE remote_url = $1"#, remote_url)
+        .fetch_optional(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| MediaFile {
+            file_id: x.file_id,
+            storage_key: x.storage_key,
+            media_type: x.media_type,
+            size: x.size,
+            width: x.width,
+            height: x.height,
+            blurhash: x.blurhash,
+            description: x.description,
+            uploader: x.uploader,
+            created: x.created,
+            remote_url: x.remote_url,
+            preview_key: x.preview_key,
+            preview_media_type: x.preview_media_type,
+            preview_width: x.preview_width,
+            preview_height: x.preview_height,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn set_file_description<'e, 'c: 'e, E>(
+    executor: E,
+    file_id: i64,
+    description: Option<&str>,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "UPDATE files SET description = $1 WHERE file_id = $2",
+        description,
+        file_id
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// links an uploaded file to the object it was posted with, keeping
+/// what gets published about it
+pub async fn attach_file<'e, 'c: 'e, E>(
+    executor: E,
+    obj_id: i64,
+    file: &MediaFile,
+    url: &str,
+    preview_url: Option<&str>,
+    position: i32,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let type_field = match file.media_type.split('/').next() {
+        Some("image") => "Image",
+        Some("video") => "Video",
+        Some("audio") => "Audio",
+        _ => "Document",
+    };
+
+    let val = query!(
+        r#"INSERT INTO attachments
+            (obj_id, type_field, content, url, file_id, position, media_type, width, height,
+            blurhash, preview_url, preview_width, preview_height)
+        VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        "#,
+        obj_id,
+        type_field,
+        file.description,
+        url,
+        file.file_id,
+        position,
+        file.media_type,
+        file.width,
+        file.height,
+        file.blurhash,
+        preview_url,
+        file.preview_width,
+        file.preview_height
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+/// the objects a file is attached to
+pub async fn get_file_objects<'e, 'c: 'e, E>(
+    executor: E,
+    file_id: i64,
+) -> Result<Vec<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT obj_id FROM attachments WHERE file_id = $1"#,
+        file_id
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.into_iter().map(|x| x.obj_id).collect()),
+        Err(x) => Err(x),
+    }
+}
+
+/// files attached to an object, in the order they were attached
+pub async fn get_object_files<'e, 'c: 'e, E>(
+    executor: E,
+    obj_id: i64,
+) -> Result<Vec<MediaFile>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT files.* FROM attachments
+            INNER JOIN files ON attachments.file_id = files.file_id
+            WHERE attachments.obj_id = $1
+            ORDER BY attachments.position
+        "#,
+        obj_id
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x
+            .into_iter()
+            .map(|x| MediaFile {
+                file_id: x.file_id,
+                storage_key: x.storage_key,
+                media_type: x.media_type,
+                size: x.size,
+                width: x.width,
+                height: x.height,
+                blurhash: x.blurhash,
+                description: x.description,
+                uploader: x.uploader,
+                created: x.created,
+                remote_url: x.remote_url,
+                preview_key: x.preview_key,
+                preview_media_type: x.preview_media_type,
+                preview_width: x.preview_width,
+                preview_height: x.preview_height,
+            })
+            .collect()),
+        Err(x) => Err(x),
+    }
+}
@@ -0,0 +1,320 @@
+use sqlx::query;
+
+use super::access_tokens::now_milis;
+
+/// records that `actor` follows `following`, both must already be in activitypub_users
+pub async fn insert_follow<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    following: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO following
+            (actor, following)
+        VALUES
+            ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        actor,
+        following
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+/// returns true if there was a follow to remove
+pub async fn delete_follow<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    following: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "DELETE FROM following WHERE actor = $1 AND following = $2",
+        actor,
+        following
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// inboxes of every follower of `actor` that isn't on `local_domain`
+pub async fn get_remote_follower_inboxes<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    local_domain: &str,
+) -> Result<Vec<String>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT DISTINCT activitypub_users.inbox FROM following
+            INNER JOIN activitypub_users ON following.actor = activitypub_users.id
+            WHERE following.following = $1 AND activitypub_users.domain != $2
+        "#,
+        actor,
+        local_domain
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.into_iter().map(|x| x.inbox).collect()),
+        Err(x) => Err(x),
+    }
+}
+
+/// the inbox of every remote actor we know of, for activities every server should
+/// hear about like an account being deleted
+pub async fn get_known_inboxes<'e, 'c: 'e, E>(
+    executor: E,
+    local_domain: &str,
+) -> Result<Vec<String>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT DISTINCT inbox FROM activitypub_users WHERE domain != $1"#,
+        local_domain
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.into_iter().map(|x| x.inbox).collect()),
+        Err(x) => Err(x),
+    }
+}
+
+/// makes local actors following `old` follow `new` instead, for when an account moves.
+/// returns the followers that weren't already following `new`
+pub async fn move_local_follows<'e, 'c: 'e, E>(
+    executor: E,
+    old: &str,
+    new: &str,
+    local_domain: &str,
+) -> Result<Vec<String>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"WITH moved AS (
+            DELETE FROM following WHERE following = $1 AND actor IN (
+                SELECT id FROM activitypub_users WHERE domain = $3
+            )
+            RETURNING actor
+        )
+        INSERT INTO following (actor, following)
+            SELECT actor, $2 FROM moved
+        ON CONFLICT DO NOTHING
+        RETURNING actor
+        "#,
+        old,
+        new,
+        local_domain
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.into_iter().map(|x| x.actor).collect()),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn is_following<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    following: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT EXISTS (SELECT 1 FROM following WHERE actor = $1 AND following = $2) AS "exists!""#,
+        actor,
+        following
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.exists),
+        Err(x) => Err(x),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FollowRequest {
+    pub actor: String,
+    pub target: String,
+    /// id of the Follow activity
+    pub activity_id: String,
+    pub published: i64,
+}
+
+/// records a follow that is waiting to be accepted, a repeated follow replaces the activity id
+pub async fn insert_follow_request<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    target: &str,
+    activity_id: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO follow_requests
+            (actor, target, activity_id, published)
+        VALUES
+            ($1, $2, $3, $4)
+        ON CONFLICT (actor, target) DO UPDATE SET activity_id = $3
+        "#,
+        actor,
+        target,
+        activity_id,
+        now_milis()
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_follow_request<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    target: &str,
+) -> Result<Option<FollowRequest>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "SELECT * FROM follow_requests WHERE actor = $1 AND target = $2",
+        actor,
+        target
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| FollowRequest {
+            actor: x.actor,
+            target: x.target,
+            activity_id: x.activity_id,
+            published: x.published,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_follow_request_by_activity<'e, 'c: 'e, E>(
+    executor: E,
+    activity_id: &str,
+) -> Result<Option<FollowRequest>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "SELECT * FROM follow_requests WHERE activity_id = $1",
+        activity_id
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| FollowRequest {
+            actor: x.actor,
+            target: x.target,
+            activity_id: x.activity_id,
+            published: x.published,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+/// returns true if there was a request to remove
+pub async fn delete_follow_request<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    target: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "DELETE FROM follow_requests WHERE actor = $1 AND target = $2",
+        actor,
+        target
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// ap_user_ids of actors waiting on `target` to accept their follow, oldest first
+pub async fn get_pending_followers<'e, 'c: 'e, E>(
+    executor: E,
+    target: &str,
+) -> Result<Vec<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT activitypub_users.ap_user_id FROM follow_requests
+            INNER JOIN activitypub_users ON follow_requests.actor = activitypub_users.id
+            WHERE follow_requests.target = $1
+            ORDER BY follow_requests.published
+        "#,
+        target
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.into_iter().map(|x| x.ap_user_id).collect()),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn count_pending_followers<'e, 'c: 'e, E>(
+    executor: E,
+    target: &str,
+) -> Result<i64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT COUNT(*) AS "count!" FROM follow_requests WHERE target = $1"#,
+        target
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.count),
+        Err(x) => Err(x),
+    }
+}
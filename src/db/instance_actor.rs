@@ -2,44 +2,50 @@ use sqlx::query;
 
 use crate::protocol::instance_actor::InstanceActor;
 
+use super::private_key::{get_private_key, KeyEncryptionKey, KeyErr, KeyOwner};
+
+/// loads the instance actor, generating it on first start. fails if its
+/// stored private key can't be read with `kek`
 pub async fn init_instance_actpr(
     conn: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     domain: &str,
-) -> InstanceActor {
-    let instance_actor = query!(r#"SELECT * FROM instance_actor LIMIT 1"#,)
+    kek: &KeyEncryptionKey,
+) -> Result<InstanceActor, KeyErr> {
+    let instance_actor = query!(r#"SELECT public_key_pem FROM instance_actor LIMIT 1"#,)
         .fetch_optional(&mut **conn)
         .await;
 
-    let instance_actor = match instance_actor.unwrap() {
-        Some(x) => InstanceActor::new(
-            openssl::rsa::Rsa::private_key_from_pem(x.private_key.as_bytes()).unwrap(),
-            x.public_key_pem,
-            domain,
-        ),
+    let instance_actor = match instance_actor.map_err(KeyErr::DbErr)? {
+        Some(x) => {
+            let private_key = get_private_key(&mut **conn, kek, KeyOwner::InstanceActor).await?;
+            let Some(private_key) = private_key else {
+                return Err(KeyErr::DbErr(sqlx::Error::RowNotFound));
+            };
+            let Ok(rsa) = private_key.rsa() else {
+                return Err(KeyErr::InvalidPem);
+            };
+            InstanceActor::new(rsa, x.public_key_pem, domain)
+        }
         None => {
             let rsa = openssl::rsa::Rsa::generate(2048).unwrap();
             let private_key = String::from_utf8(rsa.private_key_to_pem().unwrap()).unwrap();
             let public = String::from_utf8(rsa.public_key_to_pem().unwrap()).unwrap();
 
             let val = query!(
-                r#"INSERT INTO instance_actor 
+                r#"INSERT INTO instance_actor
                     (private_key, public_key_pem)
                 VALUES
                     ($1, $2)
                 "#,
-                &private_key,
+                &kek.encrypt(&private_key),
                 &public,
             )
             .execute(&mut **conn)
             .await;
 
-            val.unwrap();
-            InstanceActor::new(
-                openssl::rsa::Rsa::private_key_from_pem(private_key.as_bytes()).unwrap(),
-                public,
-                domain,
-            )
+            val.map_err(KeyErr::DbErr)?;
+            InstanceActor::new(rsa, public, domain)
         }
     };
-    instance_actor
+    Ok(instance_actor)
 }
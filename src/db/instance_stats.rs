@@ -0,0 +1,51 @@
+use sqlx::query;
+
+use super::access_tokens::now_milis;
+
+const MONTH_MILIS: i64 = 30 * 24 * 60 * 60 * 1000;
+const HALF_YEAR_MILIS: i64 = 180 * 24 * 60 * 60 * 1000;
+
+pub struct InstanceCounts {
+    pub user_count: i64,
+    /// users active in the last 30 days
+    pub active_month: i64,
+    /// users active in the last 180 days
+    pub active_half_year: i64,
+    pub local_posts: i64,
+    /// other domains we have seen actors from
+    pub domain_count: i64,
+}
+
+pub async fn get_instance_counts<'e, 'c: 'e, E>(
+    executor: E,
+    local_domain: &str,
+) -> Result<InstanceCounts, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT
+            (SELECT COUNT(*) FROM internal_users) AS "user_count!",
+            (SELECT COUNT(*) FROM internal_users WHERE last_active >= $2) AS "active_month!",
+            (SELECT COUNT(*) FROM internal_users WHERE last_active >= $3) AS "active_half_year!",
+            (SELECT COUNT(*) FROM objects WHERE domain = $1) AS "local_posts!",
+            (SELECT COUNT(DISTINCT domain) FROM activitypub_users WHERE domain != $1) AS "domain_count!"
+        "#,
+        local_domain,
+        now_milis() - MONTH_MILIS,
+        now_milis() - HALF_YEAR_MILIS
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(InstanceCounts {
+            user_count: x.user_count,
+            active_month: x.active_month,
+            active_half_year: x.active_half_year,
+            local_posts: x.local_posts,
+            domain_count: x.domain_count,
+        }),
+        Err(x) => Err(x),
+    }
+}
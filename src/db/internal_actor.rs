@@ -1,3 +1,10 @@
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+
+use super::access_tokens::now_milis;
+
+/// [`touch_last_active`] only writes when the last update is older than this
+const LAST_ACTIVE_RESOLUTION_MILIS: i64 = 60 * 60 * 1000;
+
 pub async fn get_actor_id_from_internal<'e, 'c: 'e, E>(
     executor: E,
     username: &str,
@@ -19,3 +26,222 @@ where
         Err(x) => Err(x),
     }
 }
+
+pub async fn get_uid_from_internal<'e, 'c: 'e, E>(
+    executor: E,
+    username: &str,
+) -> Result<Option<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        "SELECT uid FROM internal_users WHERE preferred_username = $1",
+        username
+    )
+    .fetch_optional(executor)
+    .await;
+    match val {
+        Ok(x) => Ok(x.map(|x| x.uid)),
+        Err(x) => Err(x),
+    }
+}
+
+/// if a local user already has this username, ignoring case
+pub async fn is_username_taken<'e, 'c: 'e, E>(
+    executor: E,
+    username: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM internal_users WHERE LOWER(preferred_username) = LOWER($1)
+        ) AS "taken!"
+        "#,
+        username
+    )
+    .fetch_one(executor)
+    .await;
+    match val {
+        Ok(x) => Ok(x.taken),
+        Err(x) => Err(x),
+    }
+}
+
+/// checks a login against the argon2 hash in internal_users and returns the uid if it matches.
+/// accounts still waiting for approval or suspended can't log in
+pub async fn verify_password<'e, 'c: 'e, E>(
+    executor: E,
+    username: &str,
+    password: &str,
+) -> Result<Option<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        r#"SELECT uid, password FROM internal_users
+            WHERE preferred_username = $1 AND approved AND NOT suspended
+        "#,
+        username
+    )
+    .fetch_optional(executor)
+    .await;
+
+    let user = match val {
+        Ok(Some(x)) => x,
+        Ok(None) => return Ok(None),
+        Err(x) => return Err(x),
+    };
+
+    let Ok(hash) = PasswordHash::new(&user.password) else {
+        return Ok(None);
+    };
+
+    match Argon2::default().verify_password(password.as_bytes(), &hash) {
+        Ok(_) => Ok(Some(user.uid)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// marks a user as active now, only writes once per [`LAST_ACTIVE_RESOLUTION_MILIS`]
+pub async fn touch_last_active<'e, 'c: 'e, E>(executor: E, uid: i64) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let now = now_milis();
+    let val = sqlx::query!(
+        r#"UPDATE internal_users SET last_active = $2
+            WHERE uid = $1 AND (last_active IS NULL OR last_active < $3)
+        "#,
+        uid,
+        now,
+        now - LAST_ACTIVE_RESOLUTION_MILIS
+    )
+    .execute(executor)
+    .await;
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+/// `password` has to already be hashed
+pub async fn set_password<'e, 'c: 'e, E>(
+    executor: E,
+    uid: i64,
+    password: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        "UPDATE internal_users SET password = $2 WHERE uid = $1",
+        uid,
+        password
+    )
+    .execute(executor)
+    .await;
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// lets an account that signed up while approval was required log in
+pub async fn approve_user<'e, 'c: 'e, E>(executor: E, uid: i64) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        "UPDATE internal_users SET approved = TRUE WHERE uid = $1 AND NOT approved",
+        uid
+    )
+    .execute(executor)
+    .await;
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// returns false if the user doesn't exist or already was (un)suspended
+pub async fn set_suspended<'e, 'c: 'e, E>(
+    executor: E,
+    uid: i64,
+    suspended: bool,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        "UPDATE internal_users SET suspended = $2 WHERE uid = $1 AND suspended != $2",
+        uid,
+        suspended
+    )
+    .execute(executor)
+    .await;
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// false if the user isn't suspended or doesn't exist
+pub async fn is_suspended<'e, 'c: 'e, E>(
+    executor: E,
+    preferred_username: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        "SELECT suspended FROM internal_users WHERE preferred_username = $1",
+        preferred_username
+    )
+    .fetch_optional(executor)
+    .await;
+    match val {
+        Ok(x) => Ok(x.is_some_and(|x| x.suspended)),
+        Err(x) => Err(x),
+    }
+}
+
+/// the same as [`is_suspended`] by actor, false for remote actors
+pub async fn is_actor_suspended<'e, 'c: 'e, E>(
+    executor: E,
+    ap_user_id: i64,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        "SELECT suspended FROM internal_users WHERE activitypub_actor = $1",
+        ap_user_id
+    )
+    .fetch_optional(executor)
+    .await;
+    match val {
+        Ok(x) => Ok(x.is_some_and(|x| x.suspended)),
+        Err(x) => Err(x),
+    }
+}
+
+/// removes a local user and their actor, everything they own goes with it
+pub async fn delete_internal_user<'e, 'c: 'e, E>(executor: E, uid: i64) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = sqlx::query!(
+        r#"DELETE FROM activitypub_users WHERE ap_user_id = (
+            SELECT activitypub_actor FROM internal_users WHERE uid = $1
+        )"#,
+        uid
+    )
+    .execute(executor)
+    .await;
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
@@ -0,0 +1,116 @@
+use sqlx::query;
+
+use super::access_tokens::{base64_url, now_milis};
+
+pub struct Invite {
+    pub code: String,
+    /// the local user that made it
+    pub uid: i64,
+    /// unlimited when none
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub created: i64,
+    pub expires: Option<i64>,
+}
+
+/// a short url safe code, invites are meant to be shared as links
+pub fn generate_invite_code() -> String {
+    let mut code = [0u8; 9];
+    openssl::rand::rand_bytes(&mut code).unwrap();
+    base64_url(&code)
+}
+
+/// stores a new invite from `uid` and returns its code
+pub async fn insert_invite<'e, 'c: 'e, E>(
+    executor: E,
+    uid: i64,
+    max_uses: Option<i32>,
+    expires: Option<i64>,
+) -> Result<Invite, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let code = generate_invite_code();
+    let created = now_milis();
+    let val = query!(
+        r#"INSERT INTO invites (code, uid, max_uses, created, expires)
+            VALUES ($1, $2, $3, $4, $5)
+        "#,
+        code,
+        uid,
+        max_uses,
+        created,
+        expires
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(Invite {
+            code,
+            uid,
+            max_uses,
+            uses: 0,
+            created,
+            expires,
+        }),
+        Err(x) => Err(x),
+    }
+}
+
+/// the invites a user made that can still be used, newest first
+pub async fn get_invites<'e, 'c: 'e, E>(executor: E, uid: i64) -> Result<Vec<Invite>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT code, uid, max_uses, uses, created, expires FROM invites
+            WHERE uid = $1
+            AND (expires IS NULL OR expires > $2)
+            AND (max_uses IS NULL OR uses < max_uses)
+            ORDER BY created DESC
+        "#,
+        uid,
+        now_milis()
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x
+            .into_iter()
+            .map(|x| Invite {
+                code: x.code,
+                uid: x.uid,
+                max_uses: x.max_uses,
+                uses: x.uses,
+                created: x.created,
+                expires: x.expires,
+            })
+            .collect()),
+        Err(x) => Err(x),
+    }
+}
+
+/// counts a use of an invite, returns false if it doesn't exist, expired or was used up
+pub async fn use_invite<'e, 'c: 'e, E>(executor: E, code: &str) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"UPDATE invites SET uses = uses + 1
+            WHERE code = $1
+            AND (expires IS NULL OR expires > $2)
+            AND (max_uses IS NULL OR uses < max_uses)
+        "#,
+        code,
+        now_milis()
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
@@ -1,8 +1,17 @@
+pub mod access_tokens;
 pub mod account_creation;
+pub mod activities;
 pub mod actor_utilities;
 pub mod conn;
+pub mod files;
+pub mod following;
 pub mod instance_actor;
+pub mod instance_stats;
 pub mod internal_actor;
+pub mod invites;
+pub mod oauth;
 pub mod objects;
 pub mod private_key;
 pub mod public_key;
+pub mod reactions;
+pub mod timelines;
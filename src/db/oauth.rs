@@ -0,0 +1,169 @@
+use sqlx::query;
+
+use super::access_tokens::{hash_token, now_milis};
+
+/// how long an authorization code can be exchanged for a token
+pub const AUTHORIZATION_CODE_LIFETIME_MILIS: i64 = 1000 * 60 * 10;
+
+#[derive(Debug, Clone)]
+pub struct OAuthApp {
+    pub app_id: i64,
+    pub client_id: String,
+    /// sha256 of the client secret
+    pub client_secret: String,
+    pub name: String,
+    pub website: Option<String>,
+    pub redirect_uris: Vec<String>,
+    /// space separated
+    pub scopes: String,
+}
+
+impl OAuthApp {
+    pub fn check_secret(&self, client_secret: &str) -> bool {
+        self.client_secret.eq(&hash_token(client_secret))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizationCode {
+    pub app_id: i64,
+    pub uid: i64,
+    pub redirect_uri: String,
+    pub scopes: String,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub expires: i64,
+}
+
+pub struct NewApp<'a> {
+    pub client_id: &'a str,
+    pub client_secret: &'a str,
+    pub name: &'a str,
+    pub website: Option<&'a str>,
+    pub redirect_uris: &'a [String],
+    pub scopes: &'a str,
+}
+
+pub async fn insert_app<'e, 'c: 'e, E>(executor: E, app: NewApp<'_>) -> Result<i64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO oauth_apps
+            (client_id, client_secret, name, website, redirect_uris, scopes, created)
+        VALUES
+            ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING app_id
+        "#,
+        app.client_id,
+        hash_token(app.client_secret),
+        app.name,
+        app.website,
+        app.redirect_uris.join("\n"),
+        app.scopes,
+        now_milis()
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.app_id),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_app_by_client_id<'e, 'c: 'e, E>(
+    executor: E,
+    client_id: &str,
+) -> Result<Option<OAuthApp>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT * FROM oauth_apps WHERE client_id = $1"#,
+        client_id
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| OAuthApp {
+            app_id: x.app_id,
+            client_id: x.client_id,
+            client_secret: x.client_secret,
+            name: x.name,
+            website: x.website,
+            redirect_uris: x.redirect_uris.lines().map(|x| x.to_string()).collect(),
+            scopes: x.scopes,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn insert_authorization_code<'e, 'c: 'e, E>(
+    executor: E,
+    code: &str,
+    authorization: &AuthorizationCode,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO oauth_authorization_codes
+            (code_hash, app_id, uid, redirect_uri, scopes, code_challenge, code_challenge_method, expires)
+        VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        hash_token(code),
+        authorization.app_id,
+        authorization.uid,
+        authorization.redirect_uri,
+        authorization.scopes,
+        authorization.code_challenge,
+        authorization.code_challenge_method,
+        authorization.expires
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+/// codes can only be used once so this removes it as it is read.
+/// expired codes are treated as non existent
+pub async fn take_authorization_code<'e, 'c: 'e, E>(
+    executor: E,
+    code: &str,
+) -> Result<Option<AuthorizationCode>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"DELETE FROM oauth_authorization_codes WHERE code_hash = $1 RETURNING *"#,
+        hash_token(code)
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(Some(x)) => {
+            if x.expires < now_milis() {
+                return Ok(None);
+            }
+            Ok(Some(AuthorizationCode {
+                app_id: x.app_id,
+                uid: x.uid,
+                redirect_uri: x.redirect_uri,
+                scopes: x.scopes,
+                code_challenge: x.code_challenge,
+                code_challenge_method: x.code_challenge_method,
+                expires: x.expires,
+            }))
+        }
+        Ok(None) => Ok(None),
+        Err(x) => Err(x),
+    }
+}
@@ -1,16 +1,16 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use chrono::DateTime;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sqlx::query;
 use url::Url;
 
 use crate::{
     activitystream_objects::{
         activities::Question,
-        core_types::{ActivityStream, RangeLinkExtendsObject},
+        core_types::{normalize_public, ActivityStream, RangeLinkExtendsObject, SimpleLinkOrArray},
         link::LinkSimpleOrExpanded,
-        object::{Object, ObjectType, ObjectWrapper},
+        object::{Object, ObjectType, ObjectWrapper, Visibility},
     },
     db::actor_utilities::get_ap_actor_by_fedi_id,
 };
@@ -57,9 +57,39 @@ impl std::fmt::Display for InsertErr {
 
 ///inserts an object and returns its id
 pub async fn create_new_object(
+    object: &DbObject,
+    transaction: sqlx::Transaction<'_, sqlx::Postgres>,
+    domain: &str,
+) -> Result<i64, InsertErr> {
+    insert_object(object, transaction, domain, false).await
+}
+
+/// inserts an object from another instance, keeping the id it already has.
+/// the actor it is attributed to must already be stored
+pub async fn insert_federated_object(
+    object: &DbObject,
+    transaction: sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<i64, InsertErr> {
+    let domain = match object {
+        DbObject::Object(x) => x.object.id.domain().map(|x| x.to_string()),
+        DbObject::Question(x) => x
+            .extends_intransitive
+            .extends_object
+            .id
+            .domain()
+            .map(|x| x.to_string()),
+    };
+    let Some(domain) = domain else {
+        return Err(InsertErr::NoDomain);
+    };
+    insert_object(object, transaction, &domain, true).await
+}
+
+async fn insert_object(
     object: &DbObject,
     mut transaction: sqlx::Transaction<'_, sqlx::Postgres>,
     domain: &str,
+    keep_id: bool,
 ) -> Result<i64, InsertErr> {
     let val = match object {
         DbObject::Object(obj_wrap) => {
@@ -91,27 +121,39 @@ pub async fn create_new_object(
 
             let internal_type = serde_json::to_string(&InternalTypes::Object).unwrap();
             let activitystream_type = serde_json::to_string(&obj_wrap.type_field).unwrap();
+            let visibility = Visibility::from_addressing(
+                obj_wrap.object.to.as_ref(),
+                obj_wrap.object.cc.as_ref(),
+                &actor.followers,
+            );
             let val = query!(
                 r#"INSERT INTO objects 
-                            (domain, internal_type, activitystream_type, ap_user_id, published)
+                            (domain, internal_type, activitystream_type, ap_user_id, published, visibility)
                         VALUES
-                            ($1, $2, $3, $4, $5)
+                            ($1, $2, $3, $4, $5, $6)
                         RETURNING obj_id
                         "#,
                 domain,
                 internal_type,
                 &activitystream_type,
                 actor_id,
-                published
+                published,
+                serde_json::to_string(&visibility).unwrap()
             )
             .fetch_one(&mut *transaction)
             .await;
 
-            let obj_id = val.unwrap().obj_id;
-            let id_link = format!(
-                "https://{}/users/{}/statuses/{}",
-                domain, actor.preferred_username, obj_id
-            );
+            let obj_id = match val {
+                Ok(x) => x.obj_id,
+                Err(x) => return Err(InsertErr::DbErr(x)),
+            };
+            let id_link = match keep_id {
+                true => obj_wrap.object.id.as_str().to_string(),
+                false => format!(
+                    "https://{}/users/{}/statuses/{}",
+                    domain, actor.preferred_username, obj_id
+                ),
+            };
 
             let _result = query!(
                 "UPDATE objects SET id = $1 WHERE obj_id = $2",
@@ -126,24 +168,49 @@ pub async fn create_new_object(
                 None => None,
             };
 
-            let _result = query!(
+            let object = &obj_wrap.object;
+            let val = query!(
                 r#"INSERT INTO activity_objects
-                            (obj_id, type_field, id, name, attributedTo, content, in_reply_to, published)
+                            (obj_id, type_field, id, name, attributedTo, content, in_reply_to, published,
+                            summary, sensitive, media_type, generator, replies, attachment, start_time,
+                            end_time, updated, tag, url, icon, image, duration, preview)
                         VALUES
-                            ($1, $2, $3, $4, $5, $6, $7, $8)
+                            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16,
+                            $17, $18, $19, $20, $21, $22, $23)
                         "#,
                 obj_id,
                 activitystream_type,
                 &id_link,
-                obj_wrap.object.name,
+                object.name,
                 actor_fedi_id.as_str(),
-                obj_wrap.object.content,
+                object.content,
                 reply,
-                published
+                published,
+                object.summary,
+                object.sensitive,
+                to_json(&object.media_type),
+                object.generator,
+                to_json(&object.replies),
+                to_json(&object.attachment),
+                object.start_time,
+                object.end_time,
+                object.updated,
+                to_json(&object.tag),
+                to_json(&object.url),
+                object.icon,
+                object.image,
+                object.duration,
+                to_json(&object.preview)
             )
             .execute(&mut *transaction)
             .await;
 
+            if let Err(x) = val {
+                return Err(InsertErr::DbErr(x));
+            }
+
+            insert_recipients(&mut transaction, obj_id, &obj_wrap.object).await?;
+
             obj_id
         }
         DbObject::Question(x) => todo!(),
@@ -154,6 +221,165 @@ pub async fn create_new_object(
     Ok(val)
 }
 
+/// for fields that are stored as json
+pub fn to_json<T: Serialize>(value: &Option<T>) -> Option<String> {
+    value.as_ref().map(|x| serde_json::to_string(x).unwrap())
+}
+
+pub fn from_json<T: DeserializeOwned>(value: Option<String>) -> Option<T> {
+    value.map(|x| serde_json::from_str(&x).expect("invalid json field stored in db"))
+}
+
+/// addressing fields that are stored, bto and bcc are never kept
+const STORED_ADDRESSING: [&str; 2] = ["to", "cc"];
+
+async fn insert_recipients(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    obj_id: i64,
+    object: &Object,
+) -> Result<(), InsertErr> {
+    for (field, recipients) in STORED_ADDRESSING.iter().zip([&object.to, &object.cc]) {
+        let Some(recipients) = recipients else {
+            continue;
+        };
+        for recipient in recipients.to_vec() {
+            let val = query!(
+                r#"INSERT INTO object_recipients
+                    (obj_id, field, recipient)
+                VALUES
+                    ($1, $2, $3)
+                ON CONFLICT DO NOTHING
+                "#,
+                obj_id,
+                field,
+                normalize_public(recipient.as_str())
+            )
+            .execute(&mut **transaction)
+            .await;
+
+            if let Err(x) = val {
+                return Err(InsertErr::DbErr(x));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// replaces the stored addressing of an object and works out its visibility again
+pub async fn update_object_recipients(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    obj_id: i64,
+    object: &Object,
+    followers: &str,
+) -> Result<(), InsertErr> {
+    let val = query!(r#"DELETE FROM object_recipients WHERE obj_id = $1"#, obj_id)
+        .execute(&mut **transaction)
+        .await;
+    if let Err(x) = val {
+        return Err(InsertErr::DbErr(x));
+    }
+
+    insert_recipients(transaction, obj_id, object).await?;
+
+    let visibility = Visibility::from_addressing(object.to.as_ref(), object.cc.as_ref(), followers);
+    let val = query!(
+        r#"UPDATE objects SET visibility = $1 WHERE obj_id = $2"#,
+        serde_json::to_string(&visibility).unwrap(),
+        obj_id
+    )
+    .execute(&mut **transaction)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(InsertErr::DbErr(x)),
+    }
+}
+
+/// fills in `to` and `cc` from what was stored for the object
+async fn set_recipients(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    obj_id: i64,
+    mut object: Object,
+) -> Result<Object, sqlx::Error> {
+    let recipients = get_object_recipients(&mut **transaction, obj_id).await?;
+
+    let to: Vec<Url> = recipients
+        .iter()
+        .filter(|(field, _)| field.eq("to"))
+        .filter_map(|(_, x)| Url::parse(x).ok())
+        .collect();
+    let cc: Vec<Url> = recipients
+        .iter()
+        .filter(|(field, _)| field.eq("cc"))
+        .filter_map(|(_, x)| Url::parse(x).ok())
+        .collect();
+
+    object.to = (!to.is_empty()).then_some(SimpleLinkOrArray::Multiple(to));
+    object.cc = (!cc.is_empty()).then_some(SimpleLinkOrArray::Multiple(cc));
+    Ok(object)
+}
+
+/// (field, recipient) pairs of an object's addressing
+pub async fn get_object_recipients<'e, 'c: 'e, E>(
+    executor: E,
+    obj_id: i64,
+) -> Result<Vec<(String, String)>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT field, recipient FROM object_recipients WHERE obj_id = $1 ORDER BY field DESC, recipient"#,
+        obj_id
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.into_iter().map(|x| (x.field, x.recipient)).collect()),
+        Err(x) => Err(x),
+    }
+}
+
+/// if `actor` is allowed to see a non public object. the author, anyone it is
+/// addressed to and, when addressed to the author's followers, their followers can
+pub async fn can_actor_view_object<'e, 'c: 'e, E>(
+    executor: E,
+    obj_id: i64,
+    actor: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM objects
+            INNER JOIN activitypub_users owner ON owner.ap_user_id = objects.ap_user_id
+            WHERE objects.obj_id = $1 AND (
+                owner.id = $2
+                OR EXISTS (
+                    SELECT 1 FROM object_recipients
+                    WHERE object_recipients.obj_id = objects.obj_id AND recipient = $2
+                )
+                OR EXISTS (
+                    SELECT 1 FROM object_recipients
+                    INNER JOIN following ON following.following = owner.id AND following.actor = $2
+                    WHERE object_recipients.obj_id = objects.obj_id AND recipient = owner.followers
+                )
+            )
+        ) AS "exists!""#,
+        obj_id,
+        actor
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.exists),
+        Err(x) => Err(x),
+    }
+}
+
 pub async fn get_object_by_db_id(
     obj_id: i64,
     mut transaction: sqlx::Transaction<'_, sqlx::Postgres>,
@@ -193,14 +419,32 @@ pub async fn get_object_by_db_id(
             let obj_type: ObjectType =
                 serde_json::from_str(&object.type_field).expect("invalid object type stored in db");
 
-            let output = Object::new(Url::parse(&object.id).expect("invalid url stored in db"))
+            let mut output = Object::new(Url::parse(&object.id).expect("invalid url stored in db"))
                 .attributed_to_link(Some(
                     Url::parse(&object.attributedto).expect("invalid actor url stored in db"),
                 ))
                 .name(object.name)
                 .content(object.content)
                 .in_reply_to(reply)
-                .published_milis(object.published)
+                .published_milis(object.published);
+            output.summary = object.summary;
+            output.sensitive = object.sensitive;
+            output.media_type = from_json(object.media_type);
+            output.generator = object.generator;
+            output.replies = from_json(object.replies);
+            output.attachment = from_json(object.attachment);
+            output.start_time = object.start_time;
+            output.end_time = object.end_time;
+            output.updated = object.updated;
+            output.tag = from_json(object.tag);
+            output.url = from_json(object.url);
+            output.icon = object.icon;
+            output.image = object.image;
+            output.duration = object.duration;
+            output.preview = from_json(object.preview);
+            let output = set_recipients(&mut transaction, obj_id, output)
+                .await
+                .unwrap()
                 .wrap(obj_type);
 
             Some(DbObject::Object(output))
@@ -208,3 +452,476 @@ pub async fn get_object_by_db_id(
         InternalTypes::Question => todo!(),
     }
 }
+
+pub struct ObjectMeta {
+    pub obj_id: i64,
+    pub id: Option<String>,
+    pub ap_user_id: i64,
+    pub domain: String,
+    pub published: i64,
+    pub visibility: Visibility,
+}
+
+pub async fn get_object_meta<'e, 'c: 'e, E>(
+    executor: E,
+    obj_id: i64,
+) -> Result<Option<ObjectMeta>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT obj_id, id, ap_user_id, domain, published, visibility FROM objects WHERE obj_id = $1"#,
+        obj_id
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| ObjectMeta {
+            obj_id: x.obj_id,
+            id: x.id,
+            ap_user_id: x.ap_user_id,
+            domain: x.domain,
+            published: x.published,
+            visibility: serde_json::from_str(&x.visibility)
+                .expect("invalid visibility stored in db"),
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn get_object_meta_by_fedi_id<'e, 'c: 'e, E>(
+    executor: E,
+    id: &str,
+) -> Result<Option<ObjectMeta>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT obj_id, id, ap_user_id, domain, published, visibility FROM objects WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| ObjectMeta {
+            obj_id: x.obj_id,
+            id: x.id,
+            ap_user_id: x.ap_user_id,
+            domain: x.domain,
+            published: x.published,
+            visibility: serde_json::from_str(&x.visibility)
+                .expect("invalid visibility stored in db"),
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+/// updates the mutable fields of an object we store
+pub async fn update_object<'e, 'c: 'e, E>(
+    executor: E,
+    id: &str,
+    object: &Object,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"UPDATE activity_objects SET
+            name = $1, content = $2, summary = $3, sensitive = $4, media_type = $5,
+            attachment = $6, tag = $7, url = $8, updated = $9, start_time = $10,
+            end_time = $11, icon = $12, image = $13, duration = $14, preview = $15
+        WHERE id = $16
+        "#,
+        object.name,
+        object.content,
+        object.summary,
+        object.sensitive,
+        to_json(&object.media_type),
+        to_json(&object.attachment),
+        to_json(&object.tag),
+        to_json(&object.url),
+        object.updated,
+        object.start_time,
+        object.end_time,
+        object.icon,
+        object.image,
+        object.duration,
+        to_json(&object.preview),
+        id
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+/// removes an object and leaves a tombstone in its place so fetches get a 410
+pub async fn delete_object(
+    id: &str,
+    former_type: &str,
+    mut transaction: sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<bool, sqlx::Error> {
+    let deleted = query!("DELETE FROM objects WHERE id = $1", id)
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+
+    if deleted == 0 {
+        transaction.rollback().await?;
+        return Ok(false);
+    }
+
+    insert_tombstone(&mut *transaction, id, former_type).await?;
+
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// marks an id as deleted so fetches of it get a 410, `former_type` is the json of its type
+pub async fn insert_tombstone<'e, 'c: 'e, E>(
+    executor: E,
+    id: &str,
+    former_type: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO tombstones
+            (id, former_type, deleted)
+        VALUES
+            ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        id,
+        former_type,
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+/// leaves a tombstone for every object an actor owns, for when the actor is about
+/// to be deleted and its objects go with it. returns how many there were
+pub async fn tombstone_actor_objects<'e, 'c: 'e, E>(
+    executor: E,
+    ap_user_id: i64,
+) -> Result<u64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO tombstones
+            (id, former_type, deleted)
+        SELECT id, activitystream_type, $2 FROM objects
+            WHERE ap_user_id = $1 AND id IS NOT NULL
+        ON CONFLICT DO NOTHING
+        "#,
+        ap_user_id,
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected()),
+        Err(x) => Err(x),
+    }
+}
+
+pub struct Tombstone {
+    pub id: String,
+    pub former_type: String,
+    /// timestamp in milis
+    pub deleted: i64,
+}
+
+pub async fn get_tombstone<'e, 'c: 'e, E>(
+    executor: E,
+    id: &str,
+) -> Result<Option<Tombstone>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(r#"SELECT * FROM tombstones WHERE id = $1"#, id)
+        .fetch_optional(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.map(|x| Tombstone {
+            id: x.id,
+            former_type: x.former_type,
+            deleted: x.deleted,
+        })),
+        Err(x) => Err(x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{
+        collection::{btree_set, vec},
+        option,
+        prelude::*,
+        strategy::ValueTree,
+        test_runner::TestRunner,
+    };
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::activitystream_objects::{
+        actors::ActorType,
+        collections::{Collection, CollectionType, ExtendsCollection},
+        core_types::LinkOrArray,
+        link::{Link, LinkType},
+        object::{AttachmentOrArray, Document, MediaType},
+    };
+
+    const ACTOR: &str = "https://remote.example/users/alice";
+
+    async fn insert_actor(pool: &PgPool) {
+        sqlx::query(
+            r#"INSERT INTO activitypub_users
+                (id, type_field, preferred_username, domain, inbox, outbox, followers, following)
+            VALUES
+                ($1, $2, 'alice', 'remote.example', $1 || '/inbox', $1 || '/outbox',
+                $1 || '/followers', $1 || '/following')
+            "#,
+        )
+        .bind(ACTOR)
+        .bind(serde_json::to_string(&ActorType::Person).unwrap())
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(r#"INSERT INTO public_keys (id, owner, public_key_pem) VALUES ($1, $2, '')"#)
+            .bind(format!("{}#main-key", ACTOR))
+            .bind(ACTOR)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    fn url() -> impl Strategy<Value = Url> {
+        "https://[a-z]{1,8}\\.example/[a-z0-9]{1,8}".prop_map(|x| Url::parse(&x).unwrap())
+    }
+
+    fn text() -> impl Strategy<Value = Option<String>> {
+        option::of("\\PC{0,40}")
+    }
+
+    fn media_type() -> impl Strategy<Value = MediaType> {
+        prop_oneof![
+            Just(MediaType::Html),
+            Just(MediaType::Markdown),
+            "[a-z]{1,8}/[a-z]{1,8}".prop_map(MediaType::Other),
+        ]
+    }
+
+    fn addressing() -> impl Strategy<Value = Option<SimpleLinkOrArray>> {
+        btree_set(url(), 0..4).prop_map(|x| {
+            (!x.is_empty()).then(|| SimpleLinkOrArray::Multiple(x.into_iter().collect()))
+        })
+    }
+
+    fn link() -> impl Strategy<Value = Link> {
+        let type_field = prop_oneof![
+            Just(LinkType::Link),
+            Just(LinkType::Mention),
+            Just(LinkType::Hashtag),
+        ];
+        let size = option::of(1..4096u32);
+        (type_field, url(), text(), size.clone(), size).prop_map(
+            |(type_field, href, name, width, height)| Link {
+                type_field,
+                href,
+                hreflang: None,
+                media_type: None,
+                name,
+                height,
+                width,
+                preview: None,
+                rel: None,
+            },
+        )
+    }
+
+    fn document() -> impl Strategy<Value = Document> {
+        let type_field = prop_oneof![
+            Just(ObjectType::Document),
+            Just(ObjectType::Image),
+            Just(ObjectType::Video),
+            Just(ObjectType::Audio),
+        ];
+        let size = option::of(1..4096u32);
+        (
+            type_field,
+            option::of(media_type()),
+            url(),
+            text(),
+            text(),
+            size.clone(),
+            size,
+            option::of(link()),
+        )
+            .prop_map(
+                |(type_field, media_type, url, name, blurhash, width, height, preview)| Document {
+                    type_field,
+                    media_type,
+                    url,
+                    name,
+                    blurhash,
+                    width,
+                    height,
+                    preview: preview.map(Box::new),
+                },
+            )
+    }
+
+    fn replies() -> impl Strategy<Value = ExtendsCollection> {
+        (url(), 0..1000u32, option::of(url())).prop_map(|(id, total_items, first)| {
+            ExtendsCollection::Collection(Collection {
+                type_field: CollectionType::Collection,
+                extends_object: Object::new(id),
+                total_items,
+                current: None,
+                first: first.map(|x| x.to_string()),
+                last: None,
+                items: None,
+            })
+        })
+    }
+
+    /// every property of [`Object`], the id is set by the test
+    fn object() -> impl Strategy<Value = Object> {
+        let text_fields = (
+            text(),
+            text(),
+            text(),
+            option::of(any::<bool>()),
+            option::of(media_type()),
+            text(),
+        );
+        let links = (
+            option::of(vec(link(), 1..4)),
+            option::of(vec(url(), 1..3)),
+            option::of(vec(document(), 1..5)),
+            option::of(url()),
+            option::of(replies()),
+            option::of(url()),
+        );
+        let extra = (text(), text(), text(), text(), text(), text());
+        let addressing = (
+            addressing(),
+            addressing(),
+            addressing(),
+            text(),
+            0..4_102_444_800_000i64,
+        );
+
+        (text_fields, links, extra, addressing).prop_map(
+            |(text_fields, links, extra, addressing)| {
+                let (name, content, summary, sensitive, media_type, generator) = text_fields;
+                let (tag, url, attachment, in_reply_to, replies, preview) = links;
+                let (start_time, end_time, updated, duration, icon, image) = extra;
+                let (to, cc, bto, bcc, published) = addressing;
+
+                let in_reply_to = in_reply_to.map(|x| {
+                    RangeLinkExtendsObject::Link(Box::new(LinkSimpleOrExpanded::Simple(x)))
+                });
+                let mut object = Object::new(Url::parse("https://remote.example/").unwrap())
+                    .attributed_to_link(Some(Url::parse(ACTOR).unwrap()))
+                    .name(name)
+                    .content(content)
+                    .in_reply_to(in_reply_to)
+                    .published_milis(published);
+                object.summary = summary;
+                object.sensitive = sensitive;
+                object.media_type = media_type;
+                object.generator = generator;
+                object.tag = tag.map(|x| {
+                    LinkOrArray::Multiple(
+                        x.into_iter().map(LinkSimpleOrExpanded::Expanded).collect(),
+                    )
+                });
+                object.url = url.map(|x| {
+                    LinkOrArray::Multiple(x.into_iter().map(LinkSimpleOrExpanded::Simple).collect())
+                });
+                object.attachment = attachment.map(AttachmentOrArray::Multiple);
+                object.replies = replies.map(Box::new);
+                object.preview = preview.map(|x| {
+                    RangeLinkExtendsObject::Link(Box::new(LinkSimpleOrExpanded::Simple(x)))
+                });
+                object.start_time = start_time;
+                object.end_time = end_time;
+                object.updated = updated;
+                object.duration = duration;
+                object.icon = icon;
+                object.image = image;
+                object.to = to;
+                object.cc = cc;
+                object.bto = bto;
+                object.bcc = bcc;
+                object
+            },
+        )
+    }
+
+    /// recipients are loaded in the database's collation order
+    fn sort_addressing(mut value: Value) -> Value {
+        for key in ["to", "cc"] {
+            if let Some(Value::Array(x)) = value.get_mut(key) {
+                x.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+            }
+        }
+        value
+    }
+
+    /// everything modeled on an object is the same after storing and loading it,
+    /// apart from `bto` and `bcc` which are never kept
+    #[sqlx::test(migrations = "./migrations")]
+    async fn stored_objects_round_trip(pool: PgPool) {
+        insert_actor(&pool).await;
+
+        let mut runner = TestRunner::default();
+        let strategy = object();
+        for case in 0..runner.config().cases {
+            let mut object = strategy.new_tree(&mut runner).unwrap().current();
+            object = object
+                .set_id(Url::parse(&format!("https://remote.example/statuses/{}", case)).unwrap());
+
+            let wrapped = DbObject::Object(object.clone().wrap(ObjectType::Note));
+            let obj_id = insert_federated_object(&wrapped, pool.begin().await.unwrap())
+                .await
+                .unwrap();
+            let Some(DbObject::Object(loaded)) =
+                get_object_by_db_id(obj_id, pool.begin().await.unwrap()).await
+            else {
+                panic!("stored object {} could not be loaded", obj_id);
+            };
+
+            object.bto = None;
+            object.bcc = None;
+            let expected =
+                sort_addressing(serde_json::to_value(object.wrap(ObjectType::Note)).unwrap());
+            let loaded = sort_addressing(serde_json::to_value(loaded).unwrap());
+            assert_eq!(expected, loaded);
+        }
+    }
+}
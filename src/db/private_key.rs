@@ -1,35 +1,309 @@
-use openssl::pkey::{PKey, Private};
+use std::fmt::Display;
+
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    rsa::Rsa,
+    sign::Signer,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
 use sqlx::query;
-use url::Url;
 
-use crate::activitystream_objects::actors::PublicKey;
+use crate::config::Config;
+
+use super::conn::DbConn;
+
+/// marks a stored private key as encrypted, the version lets us change
+/// the scheme later without guessing at what a row contains
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// the key-encryption key used to protect private keys at rest.
+/// taken from `key_encryption_key` in the config or the
+/// `KEY_ENCRYPTION_KEY` environment variable as 32 base64 encoded bytes
+#[derive(Clone)]
+pub struct KeyEncryptionKey {
+    key: Vec<u8>,
+}
+
+impl std::fmt::Debug for KeyEncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "KeyEncryptionKey(<redacted>)")
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyErr {
+    InvalidKek,
+    DbErr(sqlx::Error),
+    /// the stored value is a plaintext pem, run `reencrypt-keys`
+    NotEncrypted,
+    Malformed,
+    DecryptFailed,
+    InvalidPem,
+}
+
+impl Display for KeyErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyErr::InvalidKek => write!(f, "InvalidKek: expected 32 base64 encoded bytes"),
+            KeyErr::DbErr(x) => write!(f, "DbErr: {}", x),
+            KeyErr::NotEncrypted => write!(
+                f,
+                "NotEncrypted: private key is stored as plaintext, run reencrypt-keys"
+            ),
+            KeyErr::Malformed => write!(f, "Malformed"),
+            KeyErr::DecryptFailed => write!(f, "DecryptFailed"),
+            KeyErr::InvalidPem => write!(f, "InvalidPem"),
+        }
+    }
+}
+
+impl KeyEncryptionKey {
+    pub fn new(encoded: &str) -> Result<Self, KeyErr> {
+        let Ok(key) = openssl::base64::decode_block(encoded.trim()) else {
+            return Err(KeyErr::InvalidKek);
+        };
+        if key.len() != 32 {
+            return Err(KeyErr::InvalidKek);
+        }
+        Ok(KeyEncryptionKey { key })
+    }
+    pub fn from_config(config: &Config) -> Result<Self, KeyErr> {
+        KeyEncryptionKey::new(&config.key_encryption_key)
+    }
+    /// the key being rotated away from, if one is configured
+    pub fn previous_from_config(config: &Config) -> Result<Option<Self>, KeyErr> {
+        match &config.previous_key_encryption_key {
+            Some(x) => Ok(Some(KeyEncryptionKey::new(x)?)),
+            None => Ok(None),
+        }
+    }
+    pub fn generate() -> String {
+        let mut key = [0u8; 32];
+        openssl::rand::rand_bytes(&mut key).unwrap();
+        openssl::base64::encode_block(&key)
+    }
+    /// hmac-sha256 of `data` with a key derived from this one for `purpose`,
+    /// so signatures for different things can't be swapped for each other
+    pub fn sign(&self, purpose: &str, data: &[u8]) -> Vec<u8> {
+        let hmac = |key: &[u8], data: &[u8]| {
+            let key = PKey::hmac(key).unwrap();
+            let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+            signer.update(data).unwrap();
+            signer.sign_to_vec().unwrap()
+        };
+        let derived = hmac(&self.key, purpose.as_bytes());
+        hmac(&derived, data)
+    }
+    pub fn is_encrypted(stored: &str) -> bool {
+        stored.starts_with(ENCRYPTED_PREFIX)
+    }
+    pub fn encrypt(&self, pem: &str) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        openssl::rand::rand_bytes(&mut nonce).unwrap();
+        let mut tag = [0u8; TAG_LEN];
+
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce),
+            &[],
+            pem.as_bytes(),
+            &mut tag,
+        )
+        .unwrap();
+
+        format!(
+            "{ENCRYPTED_PREFIX}{}:{}:{}",
+            openssl::base64::encode_block(&nonce),
+            openssl::base64::encode_block(&ciphertext),
+            openssl::base64::encode_block(&tag)
+        )
+    }
+    pub fn decrypt(&self, stored: &str) -> Result<String, KeyErr> {
+        let Some(encrypted) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Err(KeyErr::NotEncrypted);
+        };
+        let mut parts = encrypted.split(':');
+        let (Some(nonce), Some(ciphertext), Some(tag), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(KeyErr::Malformed);
+        };
+        let (Ok(nonce), Ok(ciphertext), Ok(tag)) = (
+            openssl::base64::decode_block(nonce),
+            openssl::base64::decode_block(ciphertext),
+            openssl::base64::decode_block(tag),
+        ) else {
+            return Err(KeyErr::Malformed);
+        };
+
+        let Ok(pem) = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce),
+            &[],
+            &ciphertext,
+            &tag,
+        ) else {
+            return Err(KeyErr::DecryptFailed);
+        };
+
+        String::from_utf8(pem).map_err(|_| KeyErr::Malformed)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum KeyOwner {
+    /// a local user by their `internal_users.uid`
+    User(i64),
+    InstanceActor,
+}
 
+/// the only place private keys get decrypted, everything that needs to
+/// sign something should go through here
 pub async fn get_private_key<'e, 'c: 'e, E>(
     executor: E,
-    userid: i64,
-) -> Result<Option<PKey<Private>>, sqlx::Error>
+    kek: &KeyEncryptionKey,
+    owner: KeyOwner,
+) -> Result<Option<PKey<Private>>, KeyErr>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let stored = match owner {
+        KeyOwner::User(uid) => query!(
+            r#"SELECT private_key FROM internal_users
+                WHERE uid = $1
+            "#,
+            uid,
+        )
+        .fetch_optional(executor)
+        .await
+        .map(|x| x.map(|x| x.private_key)),
+        KeyOwner::InstanceActor => query!(r#"SELECT private_key FROM instance_actor LIMIT 1"#,)
+            .fetch_optional(executor)
+            .await
+            .map(|x| x.map(|x| x.private_key)),
+    };
+
+    let stored = match stored {
+        Ok(Some(x)) => x,
+        Ok(None) => return Ok(None),
+        Err(x) => return Err(KeyErr::DbErr(x)),
+    };
+
+    let pem = kek.decrypt(&stored)?;
+
+    let Ok(key) = Rsa::private_key_from_pem(pem.as_bytes()) else {
+        return Err(KeyErr::InvalidPem);
+    };
+    let Ok(key) = PKey::from_rsa(key) else {
+        return Err(KeyErr::InvalidPem);
+    };
+
+    Ok(Some(key))
+}
+
+/// `private_key` has to already be encrypted with the kek
+pub async fn set_private_key<'e, 'c: 'e, E>(
+    executor: E,
+    uid: i64,
+    private_key: &str,
+) -> Result<bool, sqlx::Error>
 where
     E: 'e + sqlx::PgExecutor<'c>,
 {
     let val = query!(
-        r#"SELECT * FROM internal_users
-            WHERE uid = $1        
-        "#,
-        userid,
+        "UPDATE internal_users SET private_key = $2 WHERE uid = $1",
+        uid,
+        private_key
     )
-    .fetch_optional(executor)
+    .execute(executor)
     .await;
 
     match val {
-        Ok(x) => match x {
-            Some(x) => {
-                // let key = openssl::rsa::Rsa::private_key_from_pem(key.private_key.as_bytes()).unwrap();
-                // let key = PKey::from_rsa(key).unwrap();
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
 
-                todo!()
-            }
-            None => Ok(None),
+/// encrypts a stored value with `new_kek`. values are decrypted with `old_kek`
+/// when it is given and falls back to `new_kek`, plaintext pems from before
+/// keys were encrypted get encrypted as is
+fn reencrypt_value(
+    stored: &str,
+    new_kek: &KeyEncryptionKey,
+    old_kek: Option<&KeyEncryptionKey>,
+) -> Result<String, KeyErr> {
+    if !KeyEncryptionKey::is_encrypted(stored) {
+        if Rsa::private_key_from_pem(stored.as_bytes()).is_err() {
+            return Err(KeyErr::InvalidPem);
+        }
+        return Ok(new_kek.encrypt(stored));
+    }
+
+    let pem = match old_kek {
+        Some(old) => match old.decrypt(stored) {
+            Ok(x) => x,
+            Err(_) => new_kek.decrypt(stored)?,
         },
-        Err(x) => Err(x),
+        None => new_kek.decrypt(stored)?,
+    };
+
+    Ok(new_kek.encrypt(&pem))
+}
+
+/// re-encrypts every private key in the db under `new_kek`, used both to
+/// encrypt rows from before keys were encrypted and to rotate the kek.
+/// returns the number of keys rewritten
+pub async fn reencrypt_private_keys(
+    conn: &DbConn,
+    new_kek: &KeyEncryptionKey,
+    old_kek: Option<&KeyEncryptionKey>,
+) -> Result<u64, KeyErr> {
+    let mut transaction = conn.db.begin().await.map_err(KeyErr::DbErr)?;
+
+    let users = query!(r#"SELECT uid, private_key FROM internal_users"#,)
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(KeyErr::DbErr)?;
+
+    let mut count = 0;
+
+    for user in users {
+        let private_key = reencrypt_value(&user.private_key, new_kek, old_kek)?;
+        query!(
+            "UPDATE internal_users SET private_key = $1 WHERE uid = $2",
+            private_key,
+            user.uid
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(KeyErr::DbErr)?;
+        count += 1;
     }
+
+    let instance_actor = query!(r#"SELECT private_key FROM instance_actor LIMIT 1"#,)
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(KeyErr::DbErr)?;
+
+    if let Some(instance_actor) = instance_actor {
+        let private_key = reencrypt_value(&instance_actor.private_key, new_kek, old_kek)?;
+        query!(
+            "UPDATE instance_actor SET private_key = $1 WHERE private_key = $2",
+            private_key,
+            instance_actor.private_key
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(KeyErr::DbErr)?;
+        count += 1;
+    }
+
+    transaction.commit().await.map_err(KeyErr::DbErr)?;
+
+    Ok(count)
 }
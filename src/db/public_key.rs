@@ -53,6 +53,29 @@ where
     .await
 }
 
+/// replaces the key of an actor, used when rotating keys
+pub async fn update_public_key<'e, 'c: 'e, E>(
+    executor: E,
+    owner: &str,
+    public_key_pem: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        "UPDATE public_keys SET public_key_pem = $2 WHERE owner = $1",
+        owner,
+        public_key_pem
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
 pub async fn get_actor_public_key<'e, 'c: 'e, E>(
     executor: E,
     owner: &str,
@@ -0,0 +1,97 @@
+use sqlx::query;
+
+use super::access_tokens::now_milis;
+
+pub async fn insert_like<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    object: &str,
+    activity_id: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO likes
+            (actor, object, activity_id, published)
+        VALUES
+            ($1, $2, $3, $4)
+        ON CONFLICT (actor, object) DO UPDATE SET activity_id = $3
+        "#,
+        actor,
+        object,
+        activity_id,
+        now_milis()
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn delete_like_by_activity<'e, 'c: 'e, E>(
+    executor: E,
+    activity_id: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!("DELETE FROM likes WHERE activity_id = $1", activity_id)
+        .execute(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn insert_announce<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    object: &str,
+    activity_id: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO announces
+            (actor, object, activity_id, published)
+        VALUES
+            ($1, $2, $3, $4)
+        ON CONFLICT (actor, object) DO UPDATE SET activity_id = $3
+        "#,
+        actor,
+        object,
+        activity_id,
+        now_milis()
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+pub async fn delete_announce_by_activity<'e, 'c: 'e, E>(
+    executor: E,
+    activity_id: &str,
+) -> Result<bool, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!("DELETE FROM announces WHERE activity_id = $1", activity_id)
+        .execute(executor)
+        .await;
+
+    match val {
+        Ok(x) => Ok(x.rows_affected() > 0),
+        Err(x) => Err(x),
+    }
+}
@@ -0,0 +1,396 @@
+use sqlx::query;
+
+use crate::activitystream_objects::core_types::PUBLIC_COLLECTION;
+
+use super::access_tokens::now_milis;
+
+/// cursor pagination over `objects.obj_id`, newest first
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    /// only items older than this
+    pub max_id: Option<i64>,
+    /// only items newer than this
+    pub since_id: Option<i64>,
+    /// only items newer than this, starting from the oldest. used to page backwards
+    pub min_id: Option<i64>,
+    pub limit: i64,
+}
+
+impl Page {
+    pub const DEFAULT_LIMIT: i64 = 20;
+    pub const MAX_LIMIT: i64 = 40;
+
+    pub fn new(
+        max_id: Option<i64>,
+        since_id: Option<i64>,
+        min_id: Option<i64>,
+        limit: Option<i64>,
+    ) -> Page {
+        Page {
+            max_id,
+            since_id,
+            min_id,
+            limit: limit
+                .unwrap_or(Page::DEFAULT_LIMIT)
+                .clamp(1, Page::MAX_LIMIT),
+        }
+    }
+}
+
+/// objects from every actor that are addressed to as:Public in `to`, optionally only
+/// those from `domain` or from other domains. returns obj_ids newest first
+pub async fn get_public_timeline<'e, 'c: 'e, E>(
+    executor: E,
+    local_domain: &str,
+    local: Option<bool>,
+    page: &Page,
+) -> Result<Vec<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let lower = page.min_id.or(page.since_id);
+
+    let val = match page.min_id {
+        Some(_) => query!(
+            r#"SELECT obj_id FROM objects
+                WHERE ($1::BOOLEAN IS NULL OR (domain = $2) = $1)
+                AND ($3::BIGINT IS NULL OR obj_id < $3)
+                AND ($4::BIGINT IS NULL OR obj_id > $4)
+                AND EXISTS (
+                    SELECT 1 FROM object_recipients
+                    WHERE object_recipients.obj_id = objects.obj_id
+                    AND field = 'to' AND recipient = $6
+                )
+                ORDER BY obj_id ASC
+                LIMIT $5
+            "#,
+            local,
+            local_domain,
+            page.max_id,
+            lower,
+            page.limit,
+            PUBLIC_COLLECTION
+        )
+        .fetch_all(executor)
+        .await
+        .map(|x| x.into_iter().rev().map(|x| x.obj_id).collect()),
+        None => query!(
+            r#"SELECT obj_id FROM objects
+                WHERE ($1::BOOLEAN IS NULL OR (domain = $2) = $1)
+                AND ($3::BIGINT IS NULL OR obj_id < $3)
+                AND ($4::BIGINT IS NULL OR obj_id > $4)
+                AND EXISTS (
+                    SELECT 1 FROM object_recipients
+                    WHERE object_recipients.obj_id = objects.obj_id
+                    AND field = 'to' AND recipient = $6
+                )
+                ORDER BY obj_id DESC
+                LIMIT $5
+            "#,
+            local,
+            local_domain,
+            page.max_id,
+            lower,
+            page.limit,
+            PUBLIC_COLLECTION
+        )
+        .fetch_all(executor)
+        .await
+        .map(|x| x.into_iter().map(|x| x.obj_id).collect()),
+    };
+
+    val
+}
+
+/// the newest objects by one actor that are addressed to as:Public in `to`.
+/// returns obj_ids newest first
+pub async fn get_actor_public_timeline<'e, 'c: 'e, E>(
+    executor: E,
+    ap_user_id: i64,
+    limit: i64,
+) -> Result<Vec<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT obj_id FROM objects
+            WHERE ap_user_id = $1
+            AND EXISTS (
+                SELECT 1 FROM object_recipients
+                WHERE object_recipients.obj_id = objects.obj_id
+                AND field = 'to' AND recipient = $3
+            )
+            ORDER BY obj_id DESC
+            LIMIT $2
+        "#,
+        ap_user_id,
+        limit,
+        PUBLIC_COLLECTION
+    )
+    .fetch_all(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.into_iter().map(|x| x.obj_id).collect()),
+        Err(x) => Err(x),
+    }
+}
+
+/// the materialized feed of a local actor, minus anything it isn't allowed to see.
+/// returns obj_ids newest first
+pub async fn get_home_timeline<'e, 'c: 'e, E>(
+    executor: E,
+    ap_user_id: i64,
+    page: &Page,
+) -> Result<Vec<i64>, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let lower = page.min_id.or(page.since_id);
+
+    let val = match page.min_id {
+        Some(_) => query!(
+            r#"SELECT obj_id FROM home_timeline
+                WHERE ap_user_id = $1
+                AND ($2::BIGINT IS NULL OR obj_id < $2)
+                AND ($3::BIGINT IS NULL OR obj_id > $3)
+                AND EXISTS (
+                    SELECT 1 FROM objects
+                    INNER JOIN activitypub_users owner ON owner.ap_user_id = objects.ap_user_id
+                    INNER JOIN activitypub_users viewer ON viewer.ap_user_id = $1
+                    WHERE objects.obj_id = home_timeline.obj_id AND (
+                        objects.visibility IN ('"public"', '"unlisted"')
+                        OR owner.id = viewer.id
+                        OR EXISTS (
+                            SELECT 1 FROM object_recipients
+                            WHERE object_recipients.obj_id = objects.obj_id AND recipient = viewer.id
+                        )
+                        OR EXISTS (
+                            SELECT 1 FROM object_recipients
+                            INNER JOIN following ON following.following = owner.id AND following.actor = viewer.id
+                            WHERE object_recipients.obj_id = objects.obj_id AND recipient = owner.followers
+                        )
+                    )
+                )
+                ORDER BY obj_id ASC
+                LIMIT $4
+            "#,
+            ap_user_id,
+            page.max_id,
+            lower,
+            page.limit
+        )
+        .fetch_all(executor)
+        .await
+        .map(|x| x.into_iter().rev().map(|x| x.obj_id).collect()),
+        None => query!(
+            r#"SELECT obj_id FROM home_timeline
+                WHERE ap_user_id = $1
+                AND ($2::BIGINT IS NULL OR obj_id < $2)
+                AND ($3::BIGINT IS NULL OR obj_id > $3)
+                AND EXISTS (
+                    SELECT 1 FROM objects
+                    INNER JOIN activitypub_users owner ON owner.ap_user_id = objects.ap_user_id
+                    INNER JOIN activitypub_users viewer ON viewer.ap_user_id = $1
+                    WHERE objects.obj_id = home_timeline.obj_id AND (
+                        objects.visibility IN ('"public"', '"unlisted"')
+                        OR owner.id = viewer.id
+                        OR EXISTS (
+                            SELECT 1 FROM object_recipients
+                            WHERE object_recipients.obj_id = objects.obj_id AND recipient = viewer.id
+                        )
+                        OR EXISTS (
+                            SELECT 1 FROM object_recipients
+                            INNER JOIN following ON following.following = owner.id AND following.actor = viewer.id
+                            WHERE object_recipients.obj_id = objects.obj_id AND recipient = owner.followers
+                        )
+                    )
+                )
+                ORDER BY obj_id DESC
+                LIMIT $4
+            "#,
+            ap_user_id,
+            page.max_id,
+            lower,
+            page.limit
+        )
+        .fetch_all(executor)
+        .await
+        .map(|x| x.into_iter().map(|x| x.obj_id).collect()),
+    };
+
+    val
+}
+
+pub async fn count_home_timeline<'e, 'c: 'e, E>(
+    executor: E,
+    ap_user_id: i64,
+) -> Result<i64, sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"SELECT COUNT(*) AS "count!" FROM home_timeline
+            WHERE ap_user_id = $1
+            AND EXISTS (
+                SELECT 1 FROM objects
+                INNER JOIN activitypub_users owner ON owner.ap_user_id = objects.ap_user_id
+                INNER JOIN activitypub_users viewer ON viewer.ap_user_id = $1
+                WHERE objects.obj_id = home_timeline.obj_id AND (
+                    objects.visibility IN ('"public"', '"unlisted"')
+                    OR owner.id = viewer.id
+                    OR EXISTS (
+                        SELECT 1 FROM object_recipients
+                        WHERE object_recipients.obj_id = objects.obj_id AND recipient = viewer.id
+                    )
+                    OR EXISTS (
+                        SELECT 1 FROM object_recipients
+                        INNER JOIN following ON following.following = owner.id AND following.actor = viewer.id
+                        WHERE object_recipients.obj_id = objects.obj_id AND recipient = owner.followers
+                    )
+                )
+            )
+        "#,
+        ap_user_id
+    )
+    .fetch_one(executor)
+    .await;
+
+    match val {
+        Ok(x) => Ok(x.count),
+        Err(x) => Err(x),
+    }
+}
+
+/// puts an object in the feed of a single actor, only local actors have feeds
+pub async fn add_to_home_timeline<'e, 'c: 'e, E>(
+    executor: E,
+    ap_user_id: i64,
+    obj_id: i64,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO home_timeline
+            (ap_user_id, obj_id, inserted)
+        SELECT activitypub_actor, $2, $3 FROM internal_users WHERE activitypub_actor = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        ap_user_id,
+        obj_id,
+        now_milis()
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+/// puts an object in the feed of its owner, if local, and every local follower of the owner
+pub async fn add_to_home_timelines<'e, 'c: 'e, E>(
+    executor: E,
+    obj_id: i64,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO home_timeline
+            (ap_user_id, obj_id, inserted)
+        SELECT internal_users.activitypub_actor, objects.obj_id, $2::BIGINT FROM objects
+            INNER JOIN internal_users ON internal_users.activitypub_actor = objects.ap_user_id
+            WHERE objects.obj_id = $1
+        UNION
+        SELECT internal_users.activitypub_actor, objects.obj_id, $2::BIGINT FROM objects
+            INNER JOIN activitypub_users owner ON objects.ap_user_id = owner.ap_user_id
+            INNER JOIN following ON following.following = owner.id
+            INNER JOIN activitypub_users follower ON following.actor = follower.id
+            INNER JOIN internal_users ON internal_users.activitypub_actor = follower.ap_user_id
+            WHERE objects.obj_id = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        obj_id,
+        now_milis()
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+/// fills a feed with what we already have from a newly followed actor, leaving
+/// out anything addressed to other people. the follow has to be recorded first
+pub async fn backfill_home_timeline<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    following: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"INSERT INTO home_timeline
+            (ap_user_id, obj_id, inserted)
+        SELECT internal_users.activitypub_actor, objects.obj_id, $3 FROM objects
+            INNER JOIN activitypub_users owner ON objects.ap_user_id = owner.ap_user_id
+            INNER JOIN activitypub_users follower ON follower.id = $1
+            INNER JOIN internal_users ON internal_users.activitypub_actor = follower.ap_user_id
+            WHERE owner.id = $2 AND (
+                objects.visibility IN ('"public"', '"unlisted"')
+                OR EXISTS (
+                    SELECT 1 FROM object_recipients
+                    WHERE object_recipients.obj_id = objects.obj_id
+                    AND recipient IN (follower.id, owner.followers)
+                )
+            )
+        ON CONFLICT DO NOTHING
+        "#,
+        actor,
+        following,
+        now_milis()
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
+
+/// takes an unfollowed actor's objects back out of a feed
+pub async fn remove_from_home_timeline<'e, 'c: 'e, E>(
+    executor: E,
+    actor: &str,
+    following: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: 'e + sqlx::PgExecutor<'c>,
+{
+    let val = query!(
+        r#"DELETE FROM home_timeline
+            USING objects, activitypub_users owner, activitypub_users follower
+            WHERE home_timeline.obj_id = objects.obj_id
+            AND objects.ap_user_id = owner.ap_user_id
+            AND home_timeline.ap_user_id = follower.ap_user_id
+            AND follower.id = $1
+            AND owner.id = $2
+        "#,
+        actor,
+        following
+    )
+    .execute(executor)
+    .await;
+
+    match val {
+        Ok(_) => Ok(()),
+        Err(x) => Err(x),
+    }
+}
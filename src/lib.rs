@@ -1,6 +1,8 @@
 pub mod activitystream_objects;
 pub mod api;
 pub mod cache_and_fetch;
+pub mod cli;
 pub mod config;
 pub mod db;
+pub mod media;
 pub mod protocol;
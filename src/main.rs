@@ -8,26 +8,50 @@ use activity_playground::{
     },
     api::{
         // activities::{get_activity, get_object},
-        actor::{create_test, get_actor, get_instance_actor},
-        inbox::{inspect_inbox, private_inbox, shared_inbox, Inbox},
-        objects::get_object,
+        activities::{get_activity, get_create_activity},
+        actor::{get_actor, get_actor_alias, get_instance_actor},
+        authentication::{login, logout},
+        inbox::{inbox_collection, inspect_inbox, private_inbox, shared_inbox, Inbox},
+        mastodon::{
+            accounts::{
+                aliases, follow_account, get_account, lookup_account, move_account, relationships,
+                set_aliases, unfollow_account, update_credentials, verify_credentials,
+            },
+            follow_requests::{authorize_follow_request, follow_requests, reject_follow_request},
+            instance::{instance, instance_v2, rules},
+            registrations::{create_invite, invites, register_account},
+            statuses::{get_status, post_status},
+            timelines::{home_timeline, public_timeline},
+        },
+        media::{
+            get_media, proxy_media, serve_media, serve_media_preview, update_media,
+            upload_media_v1, upload_media_v2,
+        },
+        nodeinfo::{nodeinfo, nodeinfo_discovery},
+        oauth::{authorize, authorize_form, register_app, revoke, token},
+        objects::{get_object, get_object_alias},
         outbox::{self, create_post, private_outbox},
-        webfinger::webfinger,
+        webfinger::{host_meta, host_meta_jrd, webfinger},
     },
     cache_and_fetch::Cache,
+    cli,
     config::Config,
-    db::{conn::DbConn, instance_actor::init_instance_actpr},
+    db::{
+        conn::DbConn,
+        instance_actor::init_instance_actpr,
+        private_key::{reencrypt_private_keys, KeyEncryptionKey},
+    },
+    media::storage::LocalStorage,
     protocol::{fetch::authorized_fetch, instance_actor::InstanceActor},
 };
 use actix_web::{
     // error::ErrorBadRequest,
     get,
-    web::{self, Data},
+    web::Data,
     App,
     HttpResponse,
     HttpServer,
     Responder,
-    Result,
 };
 // use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, query};
@@ -38,24 +62,184 @@ async fn hello() -> impl Responder {
     HttpResponse::Ok().body("Hello world!")
 }
 
-#[get("/@{preferred_username}")]
-async fn get_profile_page(/*conn: Data<DbConn>, */ path: web::Path<String>) -> Result<String> {
-    // let val = sqlx::query!(
-    //     "INSERT INTO internal_users (password, preferredUsername) VALUES ($1, $2)",
-    //     "hi".to_string(),
-    //     "hi".to_string()
-    // )
-    // .execute(&conn.db)
-    // .await;
-
-    let preferred_username = path.into_inner();
-    Ok(preferred_username)
-}
-
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // env::set_var("RUST_BACKTRACE", "1");
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = args.first().map(|x| x.as_str());
+
+    if command == Some("generate-kek") {
+        println!("{}", KeyEncryptionKey::generate());
+        return Ok(());
+    }
+    if matches!(command, Some("help" | "--help" | "-h")) {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    //----------------config file settings----------------
+
+    let settings = config::Config::builder()
+        // Add in `./Settings.toml`
+        .add_source(config::File::with_name("gater_config"))
+        // Add in settings from the environment (with a prefix of APP)
+        // Eg.. `APP_DEBUG=1 ./target/app` would set the `debug` key
+        .add_source(config::Environment::default())
+        .build();
+
+    let settings = match settings {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("{:#?}", x);
+            return Ok(());
+        }
+    };
+
+    let config = match settings.try_deserialize::<Config>() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{:#?}", error);
+            return Ok(());
+        }
+    };
+
+    let bind = config.bind_address.clone();
+    let port = config.port;
+
+    //-------------database ------------------
+
+    let pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&config.database_url)
+        .await
+        .expect("Error building a connection pool");
+
+    //-------------private key encryption----------------
+
+    let kek = match KeyEncryptionKey::from_config(&config) {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("key_encryption_key: {}", x);
+            return Ok(());
+        }
+    };
+
+    if command == Some("migrate") {
+        if let Err(x) = cli::migrate(&pool).await {
+            eprintln!("failed to apply migrations: {}", x);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if command == Some("reencrypt-keys") {
+        let previous = match KeyEncryptionKey::previous_from_config(&config) {
+            Ok(x) => x,
+            Err(x) => {
+                eprintln!("previous_key_encryption_key: {}", x);
+                return Ok(());
+            }
+        };
+        let result =
+            reencrypt_private_keys(&DbConn { db: pool.clone() }, &kek, previous.as_ref()).await;
+        match result {
+            Ok(x) => println!("re-encrypted {x} private keys"),
+            Err(x) => eprintln!("failed to re-encrypt private keys: {}", x),
+        }
+        return Ok(());
+    }
+
+    //-------------init instance actor----------------
+
+    let mut transaction = pool.begin().await.expect("failed to establish transaction");
+    let instance_actor = match init_instance_actpr(&mut transaction, &config.instance_domain, &kek)
+        .await
+    {
+        Ok(x) => x,
+        Err(x) => {
+            eprintln!("failed to load the instance actor: {}", x);
+            eprintln!(
+                "check that KEY_ENCRYPTION_KEY is the key the private keys were encrypted with, \
+                    or run reencrypt-keys if they are still stored as plaintext"
+            );
+            std::process::exit(1);
+        }
+    };
+    transaction
+        .commit()
+        .await
+        .expect("failed to commit the instance actor");
+
+    // let instance_actor = query!(r#"SELECT * FROM instance_actor LIMIT 1"#,)
+    //     .fetch_optional(&pool)
+    //     .await;
+
+    // let instance_actor = match instance_actor.unwrap() {
+    //     Some(x) => InstanceActor::new(
+    //         openssl::rsa::Rsa::private_key_from_pem(x.private_key.as_bytes()).unwrap(),
+    //         x.public_key_pem,
+    //         &config.instance_domain,
+    //     ),
+    //     None => {
+    //         let rsa = openssl::rsa::Rsa::generate(2048).unwrap();
+    //         let private_key = String::from_utf8(rsa.private_key_to_pem().unwrap()).unwrap();
+    //         let public = String::from_utf8(rsa.public_key_to_pem().unwrap()).unwrap();
+
+    //         let val = query!(
+    //             r#"INSERT INTO instance_actor
+    //                 (private_key, public_key_pem)
+    //             VALUES
+    //                 ($1, $2)
+    //             "#,
+    //             &private_key,
+    //             &public,
+    //         )
+    //         .execute(&pool)
+    //         .await;
+
+    //         val.unwrap();
+    //         InstanceActor::new(
+    //             openssl::rsa::Rsa::private_key_from_pem(private_key.as_bytes()).unwrap(),
+    //             public,
+    //             &config.instance_domain,
+    //         )
+    //     }
+    // };
+
+    //-------------------------------------------------
+
+    let inbox = Data::new(Inbox {
+        inbox: Mutex::new(Vec::new()),
+    });
+
+    let storage = LocalStorage::new(&config.media_path).expect("failed to create media directory");
+
+    let cache = Data::new(Cache::new(
+        instance_actor,
+        config.clone(),
+        kek,
+        Box::new(storage),
+    ));
+
+    //-------------admin commands----------------
+
+    if let Some(command) = command {
+        let result = cli::run(
+            command,
+            &args[1..],
+            Data::new(DbConn { db: pool.clone() }),
+            cache.clone(),
+            Data::new(config.clone()),
+        )
+        .await;
+        if let Err(x) = result {
+            eprintln!("{}", x);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let test_obj = Object::new(Url::parse("https://test.com").unwrap())
         .content(Some("hello".to_string()))
         .published_milis(1720121686859)
@@ -174,94 +358,6 @@ async fn main() -> std::io::Result<()> {
     let test = serde_json::to_string_pretty(&deserialized).unwrap();
 
     // println!("{test}");
-    //----------------config file settings----------------
-
-    let settings = config::Config::builder()
-        // Add in `./Settings.toml`
-        .add_source(config::File::with_name("gater_config"))
-        // Add in settings from the environment (with a prefix of APP)
-        // Eg.. `APP_DEBUG=1 ./target/app` would set the `debug` key
-        .add_source(config::Environment::default())
-        .build();
-
-    let settings = match settings {
-        Ok(x) => x,
-        Err(x) => {
-            eprintln!("{:#?}", x);
-            return Ok(());
-        }
-    };
-
-    let config = match settings.try_deserialize::<Config>() {
-        Ok(config) => config,
-        Err(error) => {
-            eprintln!("{:#?}", error);
-            return Ok(());
-        }
-    };
-
-    let bind = config.bind_address.clone();
-    let port = config.port;
-
-    //-------------database ------------------
-
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&config.database_url)
-        .await
-        .expect("Error building a connection pool");
-
-    //-------------init instance actor----------------
-
-    let instance_actor = init_instance_actpr(
-        &mut pool.begin().await.expect("failed to establish transaction"),
-        &config.instance_domain,
-    )
-    .await;
-
-    // let instance_actor = query!(r#"SELECT * FROM instance_actor LIMIT 1"#,)
-    //     .fetch_optional(&pool)
-    //     .await;
-
-    // let instance_actor = match instance_actor.unwrap() {
-    //     Some(x) => InstanceActor::new(
-    //         openssl::rsa::Rsa::private_key_from_pem(x.private_key.as_bytes()).unwrap(),
-    //         x.public_key_pem,
-    //         &config.instance_domain,
-    //     ),
-    //     None => {
-    //         let rsa = openssl::rsa::Rsa::generate(2048).unwrap();
-    //         let private_key = String::from_utf8(rsa.private_key_to_pem().unwrap()).unwrap();
-    //         let public = String::from_utf8(rsa.public_key_to_pem().unwrap()).unwrap();
-
-    //         let val = query!(
-    //             r#"INSERT INTO instance_actor
-    //                 (private_key, public_key_pem)
-    //             VALUES
-    //                 ($1, $2)
-    //             "#,
-    //             &private_key,
-    //             &public,
-    //         )
-    //         .execute(&pool)
-    //         .await;
-
-    //         val.unwrap();
-    //         InstanceActor::new(
-    //             openssl::rsa::Rsa::private_key_from_pem(private_key.as_bytes()).unwrap(),
-    //             public,
-    //             &config.instance_domain,
-    //         )
-    //     }
-    // };
-
-    //-------------------------------------------------
-
-    let inbox = Data::new(Inbox {
-        inbox: Mutex::new(Vec::new()),
-    });
-
-    let cache = Data::new(Cache::new(instance_actor, config.clone()));
 
     //
 
@@ -283,19 +379,63 @@ async fn main() -> std::io::Result<()> {
             .app_data(cache.clone())
             .service(hello)
             .service(webfinger)
+            .service(host_meta)
+            .service(host_meta_jrd)
+            .service(nodeinfo_discovery)
+            .service(nodeinfo)
             .service(get_actor)
-            .service(get_profile_page)
+            .service(get_actor_alias)
+            .service(get_object_alias)
             // .service(get_activity)
             // .service(get_object)
-            .service(create_test)
             // .service(post_test)
             .service(shared_inbox)
             .service(private_inbox)
+            .service(inbox_collection)
             .service(inspect_inbox)
             .service(create_post)
             .service(private_outbox)
             .service(get_object)
+            .service(get_activity)
+            .service(get_create_activity)
             .service(get_instance_actor)
+            .service(login)
+            .service(logout)
+            .service(register_app)
+            .service(authorize_form)
+            .service(authorize)
+            .service(token)
+            .service(revoke)
+            .service(verify_credentials)
+            .service(update_credentials)
+            .service(lookup_account)
+            .service(aliases)
+            .service(set_aliases)
+            .service(move_account)
+            .service(relationships)
+            .service(get_account)
+            .service(follow_account)
+            .service(unfollow_account)
+            .service(follow_requests)
+            .service(authorize_follow_request)
+            .service(reject_follow_request)
+            .service(post_status)
+            .service(get_status)
+            .service(home_timeline)
+            .service(public_timeline)
+            .service(instance)
+            .service(instance_v2)
+            .service(register_account)
+            .service(invites)
+            .service(create_invite)
+            .service(rules)
+            .service(upload_media_v1)
+            .service(upload_media_v2)
+            .service(get_media)
+            .service(update_media)
+            .service(serve_media)
+            .service(serve_media_preview)
+            .service(proxy_media)
     })
     .bind((bind, port))?
     .run()
@@ -0,0 +1,3 @@
+pub mod processing;
+pub mod proxy;
+pub mod storage;
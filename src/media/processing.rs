@@ -0,0 +1,160 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::jpeg::JpegEncoder, DynamicImage, GenericImageView, ImageDecoder, ImageFormat,
+    ImageReader,
+};
+
+/// previews are scaled to fit in a box this size
+const PREVIEW_SIZE: u32 = 400;
+const JPEG_QUALITY: u8 = 90;
+
+/// what we could work out about an uploaded file
+#[derive(Debug, Clone, Default)]
+pub struct MediaInfo {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub blurhash: Option<String>,
+}
+
+/// media types we accept uploads of
+pub fn is_allowed_media_type(media_type: &str) -> bool {
+    media_type.starts_with("image/")
+        || media_type.starts_with("video/")
+        || media_type.starts_with("audio/")
+}
+
+/// the mastodon attachment type for a media type
+pub fn attachment_type(media_type: &str) -> &'static str {
+    match media_type.split('/').next() {
+        Some("image") if media_type.eq("image/gif") => "gifv",
+        Some("image") => "image",
+        Some("video") => "video",
+        Some("audio") => "audio",
+        _ => "unknown",
+    }
+}
+
+/// a smaller version of an image for timelines
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub data: Vec<u8>,
+    pub media_type: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug)]
+pub enum ProcessErr {
+    /// an image type we can't strip metadata from
+    UnsupportedType,
+    /// the file couldn't be decoded or encoded again
+    InvalidImage,
+}
+
+/// an upload after processing, `data` is what should be stored
+#[derive(Debug, Clone)]
+pub struct ProcessedMedia {
+    pub data: Vec<u8>,
+    pub info: MediaInfo,
+    pub preview: Option<Preview>,
+}
+
+fn image_info(image: &DynamicImage) -> MediaInfo {
+    let (width, height) = image.dimensions();
+
+    //blurhash only needs a tiny version of the image
+    let small = image.thumbnail(64, 64).to_rgba8();
+    let blurhash = blurhash::encode(4, 3, small.width(), small.height(), small.as_raw()).ok();
+
+    MediaInfo {
+        width: Some(width),
+        height: Some(height),
+        blurhash,
+    }
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Option<Vec<u8>> {
+    let mut out = Cursor::new(Vec::new());
+    let res = match format {
+        //jpeg doesn't have an alpha channel
+        ImageFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)),
+        _ => image.write_to(&mut out, format),
+    };
+    res.ok().map(|_| out.into_inner())
+}
+
+/// dimensions and blurhash for images, other media is left alone
+pub fn inspect_media(data: &[u8], media_type: &str) -> MediaInfo {
+    if !media_type.starts_with("image/") {
+        return MediaInfo::default();
+    }
+
+    match image::load_from_memory(data) {
+        Ok(x) => image_info(&x),
+        Err(_) => MediaInfo::default(),
+    }
+}
+
+/// decodes an image with its exif orientation applied, since the exif data is dropped
+fn decode_oriented(data: &[u8], format: ImageFormat) -> Option<DynamicImage> {
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format)
+        .into_decoder()
+        .ok()?;
+    let orientation = decoder.orientation().ok()?;
+    let mut image = DynamicImage::from_decoder(decoder).ok()?;
+    image.apply_orientation(orientation);
+    Some(image)
+}
+
+/// gets uploads ready to publish. still images are decoded and encoded again,
+/// which drops exif data like gps location, and get a preview and blurhash.
+/// gifs are kept as they are so they stay animated, they don't carry exif.
+/// images we can't do that for are rejected rather than stored as they are
+pub fn process_upload(data: Vec<u8>, media_type: &str) -> Result<ProcessedMedia, ProcessErr> {
+    let format = match media_type {
+        "image/jpeg" => ImageFormat::Jpeg,
+        "image/png" => ImageFormat::Png,
+        "image/webp" => ImageFormat::WebP,
+        "image/gif" => ImageFormat::Gif,
+        x if x.starts_with("image/") => return Err(ProcessErr::UnsupportedType),
+        _ => {
+            return Ok(ProcessedMedia {
+                info: inspect_media(&data, media_type),
+                data,
+                preview: None,
+            })
+        }
+    };
+    let Some(image) = decode_oriented(&data, format) else {
+        return Err(ProcessErr::InvalidImage);
+    };
+
+    let info = image_info(&image);
+
+    let data = match format {
+        ImageFormat::Gif => data,
+        _ => encode(&image, format).ok_or(ProcessErr::InvalidImage)?,
+    };
+
+    //previews keep transparency unless the original was a jpeg
+    let (preview_format, preview_type) = match format {
+        ImageFormat::Jpeg => (ImageFormat::Jpeg, "image/jpeg"),
+        _ => (ImageFormat::Png, "image/png"),
+    };
+    let small = image.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE);
+    let preview = encode(&small, preview_format).map(|x| Preview {
+        data: x,
+        media_type: preview_type.to_string(),
+        width: small.width(),
+        height: small.height(),
+    });
+
+    Ok(ProcessedMedia {
+        data,
+        info,
+        preview,
+    })
+}
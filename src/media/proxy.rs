@@ -0,0 +1,267 @@
+use std::{
+    fmt::Display,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use actix_web::web::Data;
+use url::Url;
+
+use crate::{
+    cache_and_fetch::Cache,
+    db::{
+        access_tokens::{base64_url, generate_token},
+        conn::DbConn,
+        files::{get_file, get_file_by_remote_url, insert_remote_file, MediaFile, NewFile},
+    },
+};
+
+use super::processing::{inspect_media, is_allowed_media_type};
+
+/// purpose passed to [`crate::db::private_key::KeyEncryptionKey::sign`] for proxy links
+const PROXY_SIGNATURE_PURPOSE: &str = "media-proxy";
+
+/// redirects are followed by hand so every hop gets checked
+const MAX_REDIRECTS: usize = 3;
+
+/// how long a checked fetch gets, including reading the body
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum ProxyErr {
+    /// not something we're willing to fetch
+    InvalidUrl,
+    RequestErr(reqwest::Error),
+    /// the remote server didn't return a 2xx
+    BadStatus(u16),
+    UnsupportedType(String),
+    TooLarge,
+    StorageErr(std::io::Error),
+    DbErr(sqlx::Error),
+}
+
+impl Display for ProxyErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyErr::InvalidUrl => write!(f, "InvalidUrl"),
+            ProxyErr::RequestErr(x) => write!(f, "RequestErr: {}", x),
+            ProxyErr::BadStatus(x) => write!(f, "BadStatus: {}", x),
+            ProxyErr::UnsupportedType(x) => write!(f, "UnsupportedType: {}", x),
+            ProxyErr::TooLarge => write!(f, "TooLarge"),
+            ProxyErr::StorageErr(x) => write!(f, "StorageErr: {}", x),
+            ProxyErr::DbErr(x) => write!(f, "DbErr: {}", x),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ProxyErr {
+    fn from(value: sqlx::Error) -> Self {
+        ProxyErr::DbErr(value)
+    }
+}
+
+impl From<reqwest::Error> for ProxyErr {
+    fn from(value: reqwest::Error) -> Self {
+        ProxyErr::RequestErr(value)
+    }
+}
+
+fn proxy_signature(cache: &Cache, remote: &str) -> String {
+    base64_url(&cache.kek.sign(PROXY_SIGNATURE_PURPOSE, remote.as_bytes()))
+}
+
+/// a link to `remote` through our media proxy. the signature stops the
+/// proxy being used to fetch arbitrary urls
+pub fn proxy_url(cache: &Cache, remote: &Url) -> Url {
+    let mut url = Url::parse(&format!(
+        "https://{}/proxy/{}",
+        cache.state.instance_domain,
+        proxy_signature(cache, remote.as_str())
+    ))
+    .unwrap();
+    url.query_pairs_mut().append_pair("url", remote.as_str());
+    url
+}
+
+pub fn verify_proxy_signature(cache: &Cache, remote: &str, signature: &str) -> bool {
+    let expected = proxy_signature(cache, remote);
+    expected.len() == signature.len()
+        && openssl::memcmp::eq(expected.as_bytes(), signature.as_bytes())
+}
+
+/// only fetch from other servers by domain name over http(s), or just https if `https_only`
+fn is_fetchable(remote: &Url, local_domain: &str, https_only: bool) -> bool {
+    match remote.scheme() {
+        "https" => {}
+        "http" if !https_only => {}
+        _ => return false,
+    }
+    match remote.domain() {
+        Some(x) => !x.eq_ignore_ascii_case(local_domain) && !x.eq_ignore_ascii_case("localhost"),
+        None => false,
+    }
+}
+
+/// addresses out on the internet, not loopback or one of the server's own networks
+fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(x) => {
+            let [a, b, ..] = x.octets();
+            !(a == 0
+                || x.is_private()
+                || x.is_loopback()
+                || x.is_link_local()
+                || x.is_broadcast()
+                || x.is_documentation()
+                || x.is_multicast()
+                //carrier grade nat
+                || (a == 100 && (64..128).contains(&b))
+                //reserved
+                || a >= 240)
+        }
+        IpAddr::V6(x) => {
+            if let Some(x) = x.to_ipv4_mapped() {
+                return is_global(IpAddr::V4(x));
+            }
+            let first = x.segments()[0];
+            !(x.is_unspecified()
+                || x.is_loopback()
+                || x.is_multicast()
+                //unique local
+                || (first & 0xfe00) == 0xfc00
+                //link local
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// looks up the host of `remote`, every address it resolves to has to be global
+async fn resolve_fetchable(remote: &Url) -> Result<Vec<SocketAddr>, ProxyErr> {
+    let (Some(host), Some(port)) = (remote.host_str(), remote.port_or_known_default()) else {
+        return Err(ProxyErr::InvalidUrl);
+    };
+    let lookup = (host.to_string(), port);
+    let addrs: Vec<SocketAddr> =
+        match actix_web::rt::task::spawn_blocking(move || lookup.to_socket_addrs()).await {
+            Ok(Ok(x)) => x.collect(),
+            _ => return Err(ProxyErr::InvalidUrl),
+        };
+
+    if addrs.is_empty() || !addrs.iter().all(|x| is_global(x.ip())) {
+        return Err(ProxyErr::InvalidUrl);
+    }
+    Ok(addrs)
+}
+
+/// gets `remote` from another server that isn't on a private network, following
+/// redirects only to urls that pass the same checks
+pub async fn fetch_checked(
+    remote: &Url,
+    local_domain: &str,
+    https_only: bool,
+    accept: Option<&str>,
+) -> Result<reqwest::Response, ProxyErr> {
+    let mut url = remote.clone();
+    for _ in 0..=MAX_REDIRECTS {
+        if !is_fetchable(&url, local_domain, https_only) {
+            return Err(ProxyErr::InvalidUrl);
+        }
+        let addrs = resolve_fetchable(&url).await?;
+
+        //connect to the addresses that were checked instead of resolving the host again
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve_to_addrs(url.host_str().unwrap(), &addrs)
+            .timeout(FETCH_TIMEOUT)
+            .build()?;
+        let mut request = client.get(url.clone());
+        if let Some(x) = accept {
+            request = request.header("accept", x);
+        }
+        let res = request.send().await?;
+        if !res.status().is_redirection() {
+            return Ok(res);
+        }
+
+        let location = res
+            .headers()
+            .get("Location")
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| url.join(x).ok());
+        match location {
+            Some(x) => url = x,
+            None => return Err(ProxyErr::BadStatus(res.status().as_u16())),
+        }
+    }
+    Err(ProxyErr::InvalidUrl)
+}
+
+/// gets a remote file from storage, fetching and storing it the first time it's asked for
+pub async fn fetch_remote_media(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    remote: &Url,
+) -> Result<MediaFile, ProxyErr> {
+    if let Some(x) = get_file_by_remote_url(&conn.db, remote.as_str()).await? {
+        return Ok(x);
+    }
+    let max_size = cache.state.max_upload_size;
+
+    let mut res = fetch_checked(remote, &cache.state.instance_domain, false, None).await?;
+    if !res.status().is_success() {
+        return Err(ProxyErr::BadStatus(res.status().as_u16()));
+    }
+
+    let media_type = res
+        .headers()
+        .get("Content-Type")
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.split(';').next())
+        .map(|x| x.trim().to_lowercase())
+        .unwrap_or_default();
+    if !is_allowed_media_type(&media_type) {
+        return Err(ProxyErr::UnsupportedType(media_type));
+    }
+    if res.content_length().is_some_and(|x| x as usize > max_size) {
+        return Err(ProxyErr::TooLarge);
+    }
+
+    //the content length can lie so keep counting
+    let mut data = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+        if data.len() + chunk.len() > max_size {
+            return Err(ProxyErr::TooLarge);
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let info = inspect_media(&data, &media_type);
+
+    let storage_key = generate_token();
+    if let Err(x) = cache.storage.put(&storage_key, &data) {
+        return Err(ProxyErr::StorageErr(x));
+    }
+
+    let file_id = insert_remote_file(
+        &conn.db,
+        NewFile {
+            storage_key: &storage_key,
+            media_type: &media_type,
+            size: data.len() as i64,
+            info: &info,
+            description: None,
+            uploader: None,
+            preview: None,
+        },
+        remote.as_str(),
+    )
+    .await?;
+
+    let file = get_file(&conn.db, file_id).await?.unwrap();
+    //someone else fetched it at the same time and got there first
+    if file.storage_key != storage_key {
+        let _ = cache.storage.delete(&storage_key);
+    }
+
+    Ok(file)
+}
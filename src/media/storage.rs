@@ -0,0 +1,45 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// somewhere to keep media files. `key` is generated by us and is safe to use as a file name
+pub trait MediaStorage: Send + Sync {
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()>;
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// stores media as files in a directory on local disk
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// creates the directory if it doesn't exist
+    pub fn new(root: impl AsRef<Path>) -> io::Result<LocalStorage> {
+        std::fs::create_dir_all(root.as_ref())?;
+        Ok(LocalStorage {
+            root: root.as_ref().to_path_buf(),
+        })
+    }
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        //keys are generated by us but never let one escape the directory
+        if key.is_empty() || key.contains(['/', '\\']) || key.starts_with('.') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid key"));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+impl MediaStorage for LocalStorage {
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        std::fs::write(self.path(key)?, data)
+    }
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.path(key)?)
+    }
+    fn delete(&self, key: &str) -> io::Result<()> {
+        std::fs::remove_file(self.path(key)?)
+    }
+}
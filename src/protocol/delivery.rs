@@ -0,0 +1,105 @@
+use actix_web::web::Data;
+use openssl::pkey::{PKey, Private};
+use url::Url;
+
+use crate::{
+    activitystream_objects::{core_types::PUBLIC_COLLECTION, object::Visibility},
+    cache_and_fetch::{fetch_actor, Cache},
+    db::{
+        actor_utilities::get_ap_actor_id_by_fedi_id,
+        conn::DbConn,
+        following::get_remote_follower_inboxes,
+        objects::get_object_meta,
+        timelines::{add_to_home_timeline, add_to_home_timelines},
+    },
+};
+
+use super::verification::post_to_inbox;
+
+/// resolves the addressing of an activity to the inboxes it should be posted to.
+/// public activities and ones addressed to the actor's followers collection go to
+/// every remote follower, local recipients are skipped since they already have it
+pub async fn get_delivery_inboxes(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    actor_id: &str,
+    followers: &str,
+    recipients: &[Url],
+) -> Vec<String> {
+    let mut inboxes: Vec<String> = Vec::new();
+
+    for recipient in recipients {
+        let recipient_str = recipient.as_str();
+        if recipient_str.eq(PUBLIC_COLLECTION) || recipient_str.eq(followers) {
+            let follower_inboxes =
+                get_remote_follower_inboxes(&conn.db, actor_id, &cache.state.instance_domain)
+                    .await
+                    .unwrap();
+            inboxes.extend(follower_inboxes);
+            continue;
+        }
+        if recipient_str.eq(actor_id) {
+            continue;
+        }
+        if let Some(domain) = recipient.domain() {
+            if domain.eq_ignore_ascii_case(&cache.state.instance_domain) {
+                continue;
+            }
+        }
+
+        match fetch_actor(recipient, cache, conn).await {
+            Ok(x) => inboxes.push(x.inbox),
+            Err(x) => {
+                //probably a collection we don't know how to expand
+                println!("not delivering to {recipient_str}: {:?}", x);
+            }
+        }
+    }
+
+    inboxes.sort();
+    inboxes.dedup();
+    inboxes
+}
+
+/// posts a signed activity to each inbox
+pub async fn deliver(activity: &str, from_id: &str, key: &PKey<Private>, inboxes: &[String]) {
+    for inbox in inboxes {
+        let Ok(url) = Url::parse(inbox) else {
+            continue;
+        };
+        let Some(domain) = url.domain() else {
+            continue;
+        };
+        post_to_inbox(activity, from_id, domain, inbox, key).await;
+    }
+}
+
+/// the local half of delivery, puts a stored object in the home timelines of the
+/// owner's local followers, unless it is direct, and of any local actors it is addressed to
+pub async fn deliver_locally(
+    conn: &Data<DbConn>,
+    local_domain: &str,
+    obj_id: i64,
+    recipients: &[Url],
+) {
+    let meta = get_object_meta(&conn.db, obj_id).await.unwrap();
+    //direct messages only go to the people mentioned
+    if meta.is_some_and(|x| x.visibility != Visibility::Direct) {
+        add_to_home_timelines(&conn.db, obj_id).await.unwrap();
+    }
+
+    for recipient in recipients {
+        let local = recipient
+            .domain()
+            .is_some_and(|x| x.eq_ignore_ascii_case(local_domain));
+        if !local {
+            continue;
+        }
+        let ap_user_id = get_ap_actor_id_by_fedi_id(&conn.db, recipient.as_str())
+            .await
+            .unwrap();
+        if let Some(x) = ap_user_id {
+            add_to_home_timeline(&conn.db, x, obj_id).await.unwrap();
+        }
+    }
+}
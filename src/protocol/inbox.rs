@@ -0,0 +1,339 @@
+use actix_web::web::Data;
+use serde_json::{json, Map, Value};
+use url::Url;
+
+use crate::{
+    activitystream_objects::{actors::Actor, object::ObjectWrapper},
+    cache_and_fetch::{fetch_actor, fetch_object, refetch_actor, Cache},
+    db::{
+        actor_utilities::{
+            get_ap_actor_by_db_id, get_ap_actor_id_by_fedi_id, set_moved_to, update_actor_profile,
+        },
+        conn::DbConn,
+        following::{
+            delete_follow, delete_follow_request, get_follow_request_by_activity, insert_follow,
+            insert_follow_request,
+        },
+        internal_actor::{get_uid_from_internal, is_actor_suspended},
+        objects::{get_object_meta_by_fedi_id, insert_federated_object, DbObject},
+        timelines::{backfill_home_timeline, remove_from_home_timeline},
+    },
+};
+
+use super::{
+    delivery::deliver_locally,
+    outbox::{get_link, get_recipients, move_followers, post_outbox},
+};
+
+/// object properties we keep from incoming objects
+const STORED_FIELDS: [&str; 24] = [
+    "id",
+    "type",
+    "attributedTo",
+    "name",
+    "content",
+    "summary",
+    "sensitive",
+    "inReplyTo",
+    "published",
+    "updated",
+    "to",
+    "cc",
+    "tag",
+    "url",
+    "mediaType",
+    "attachment",
+    "generator",
+    "replies",
+    "icon",
+    "image",
+    "preview",
+    "duration",
+    "startTime",
+    "endTime",
+];
+
+/// the stored properties of an incoming object. ones in a shape we don't model, like
+/// mastodon's `replies` with an embedded first page, are left out instead of the object
+fn stored_properties(object: &Value) -> Map<String, Value> {
+    let mut stored = Map::new();
+    for key in STORED_FIELDS {
+        let Some(x) = object.get(key) else {
+            continue;
+        };
+        let mut single = Map::new();
+        single.insert("id".to_string(), object["id"].clone());
+        single.insert("type".to_string(), Value::String("Object".to_string()));
+        single.insert(key.to_string(), x.clone());
+        if serde_json::from_value::<ObjectWrapper>(Value::Object(single)).is_ok() {
+            stored.insert(key.to_string(), x.clone());
+        }
+    }
+    stored
+}
+
+/// applies the side effects of an activity that has already passed [`super::verification::verify_incoming`]
+pub async fn handle_incoming(conn: &Data<DbConn>, cache: &Cache, body: &str) {
+    let Ok(activity) = serde_json::from_str::<Value>(body) else {
+        return;
+    };
+
+    match activity.get("type").and_then(|x| x.as_str()) {
+        Some("Create") => handle_create(conn, cache, &activity).await,
+        Some("Update") => handle_update(conn, &activity).await,
+        Some("Follow") => handle_follow(conn, cache, &activity).await,
+        Some("Undo") => handle_undo(conn, &activity).await,
+        Some("Accept") => handle_follow_response(conn, &activity, true).await,
+        Some("Reject") => handle_follow_response(conn, &activity, false).await,
+        Some("Move") => handle_move(conn, cache, &activity).await,
+        _ => {}
+    }
+}
+
+async fn handle_create(conn: &Data<DbConn>, cache: &Cache, activity: &Value) {
+    let Some(actor) = activity
+        .get("actor")
+        .and_then(|x| x.as_str())
+        .and_then(|x| Url::parse(x).ok())
+    else {
+        return;
+    };
+
+    let object = match activity.get("object") {
+        Some(Value::Object(x)) => Value::Object(x.clone()),
+        Some(Value::String(x)) => {
+            let Ok(id) = Url::parse(x) else {
+                return;
+            };
+            let Ok(fetched) = fetch_object(&id, cache, conn).await else {
+                return;
+            };
+            let Some(object) = fetched.get_object() else {
+                return;
+            };
+            serde_json::to_value(*object).unwrap()
+        }
+        _ => return,
+    };
+
+    let stored = stored_properties(&object);
+    let Ok(wrapper) = serde_json::from_value::<ObjectWrapper>(Value::Object(stored)) else {
+        println!("unsupported object in create: {}", object);
+        return;
+    };
+
+    //an actor can only create objects on its own domain
+    if wrapper.object.id.domain() != actor.domain() {
+        return;
+    }
+    if wrapper.object.get_attributed_to().map(|x| x.as_str()) != Some(actor.as_str()) {
+        return;
+    }
+
+    let mut recipients = get_recipients(activity);
+    recipients.extend(get_recipients(&object));
+
+    let existing = get_object_meta_by_fedi_id(&conn.db, wrapper.object.id.as_str())
+        .await
+        .unwrap();
+    let obj_id = match existing {
+        Some(x) => x.obj_id,
+        None => {
+            if fetch_actor(&actor, cache, conn).await.is_err() {
+                return;
+            }
+            let inserted =
+                insert_federated_object(&DbObject::Object(wrapper), conn.db.begin().await.unwrap())
+                    .await;
+            match inserted {
+                Ok(x) => x,
+                Err(x) => {
+                    eprintln!("failed to store federated object: {}", x);
+                    return;
+                }
+            }
+        }
+    };
+
+    deliver_locally(conn, &cache.state.instance_domain, obj_id, &recipients).await;
+}
+
+/// only profile updates for now, an actor can only update itself
+async fn handle_update(conn: &Data<DbConn>, activity: &Value) {
+    let Some(actor) = activity.get("actor").and_then(|x| x.as_str()) else {
+        return;
+    };
+    let Some(object) = activity.get("object").filter(|x| x.is_object()) else {
+        return;
+    };
+    if object.get("id").and_then(|x| x.as_str()) != Some(actor) {
+        return;
+    }
+    let Ok(updated) = serde_json::from_value::<Actor>(object.clone()) else {
+        return;
+    };
+    //we only keep profiles of actors we already know about
+    if let Err(x) = update_actor_profile(&conn.db, &updated).await {
+        eprintln!("failed to update the profile of {}: {}", actor, x);
+    }
+}
+
+/// an actor moving to another account. the new account has to list the old one in
+/// `alsoKnownAs`, then local followers of the old account follow the new one instead
+///
+/// https://docs.joinmastodon.org/spec/activitypub/#Move
+async fn handle_move(conn: &Data<DbConn>, cache: &Cache, activity: &Value) {
+    let Some(actor) = get_link(activity, "actor") else {
+        return;
+    };
+    let Some(target) = get_link(activity, "target") else {
+        return;
+    };
+    //actors can only move themselves
+    if get_link(activity, "object").is_none_or(|x| x.ne(&actor)) || target.eq(&actor) {
+        return;
+    }
+
+    //our copy of the new account could be from before the alias was added
+    let Ok(new_actor) = refetch_actor(&target, cache, conn).await else {
+        return;
+    };
+    if !new_actor
+        .also_known_as
+        .is_some_and(|x| x.contains(actor.as_str()))
+    {
+        return;
+    }
+
+    set_moved_to(&conn.db, actor.as_str(), target.as_str())
+        .await
+        .unwrap();
+    if let Err(x) = move_followers(conn, cache, actor.as_str(), &target).await {
+        eprintln!("failed to move the followers of {}: {}", actor, x);
+    }
+}
+
+/// follows of local actors are kept as requests, actors that don't manually
+/// approve followers accept them straight away
+async fn handle_follow(conn: &Data<DbConn>, cache: &Cache, activity: &Value) {
+    let Some(actor) = get_link(activity, "actor") else {
+        return;
+    };
+    let Some(target) = get_link(activity, "object") else {
+        return;
+    };
+    let Some(follow_id) = activity.get("id").and_then(|x| x.as_str()) else {
+        return;
+    };
+    if !target
+        .domain()
+        .is_some_and(|x| x.eq_ignore_ascii_case(&cache.state.instance_domain))
+    {
+        return;
+    }
+    let Some(target_ap_id) = get_ap_actor_id_by_fedi_id(&conn.db, target.as_str())
+        .await
+        .unwrap()
+    else {
+        return;
+    };
+    if is_actor_suspended(&conn.db, target_ap_id).await.unwrap() {
+        return;
+    }
+    if fetch_actor(&actor, cache, conn).await.is_err() {
+        return;
+    }
+
+    insert_follow_request(&conn.db, actor.as_str(), target.as_str(), follow_id)
+        .await
+        .unwrap();
+
+    let target_actor = get_ap_actor_by_db_id(target_ap_id, conn).await;
+    if target_actor.manually_approves_followers.unwrap_or(false) {
+        return;
+    }
+    let Some(uid) = get_uid_from_internal(&conn.db, &target_actor.preferred_username)
+        .await
+        .unwrap()
+    else {
+        return;
+    };
+    let accept = json!({
+        "type": "Accept",
+        "actor": target.as_str(),
+        "object": follow_id,
+    });
+    if let Err(x) = post_outbox(conn, cache, uid, &target_actor.preferred_username, accept).await {
+        eprintln!("failed to accept the follow {}: {}", follow_id, x);
+    }
+}
+
+/// only undoing follows for now, the object can be the follow itself or its id
+async fn handle_undo(conn: &Data<DbConn>, activity: &Value) {
+    let Some(actor) = activity.get("actor").and_then(|x| x.as_str()) else {
+        return;
+    };
+    let target = match activity.get("object") {
+        Some(Value::Object(x)) if x.get("type").and_then(|x| x.as_str()) == Some("Follow") => {
+            get_link(&activity["object"], "object")
+        }
+        Some(Value::String(x)) => get_follow_request_by_activity(&conn.db, x)
+            .await
+            .unwrap()
+            .filter(|x| x.actor.eq(actor))
+            .and_then(|x| Url::parse(&x.target).ok()),
+        _ => None,
+    };
+    let Some(target) = target else {
+        return;
+    };
+
+    delete_follow_request(&conn.db, actor, target.as_str())
+        .await
+        .unwrap();
+    delete_follow(&conn.db, actor, target.as_str())
+        .await
+        .unwrap();
+}
+
+/// an answer to a follow we sent. a reject with no pending request removes a follow
+/// that was already accepted
+async fn handle_follow_response(conn: &Data<DbConn>, activity: &Value, accepted: bool) {
+    let Some(actor) = activity.get("actor").and_then(|x| x.as_str()) else {
+        return;
+    };
+    let Some(follow_id) = get_link(activity, "object") else {
+        return;
+    };
+
+    let request = get_follow_request_by_activity(&conn.db, follow_id.as_str())
+        .await
+        .unwrap()
+        .filter(|x| x.target.eq(actor));
+    let follower = match &request {
+        Some(x) => x.actor.clone(),
+        None if !accepted => match get_link(&activity["object"], "actor") {
+            Some(x) => x.to_string(),
+            None => return,
+        },
+        None => return,
+    };
+
+    delete_follow_request(&conn.db, &follower, actor)
+        .await
+        .unwrap();
+    match accepted {
+        true => {
+            insert_follow(&conn.db, &follower, actor).await.unwrap();
+            backfill_home_timeline(&conn.db, &follower, actor)
+                .await
+                .unwrap();
+        }
+        false => {
+            delete_follow(&conn.db, &follower, actor).await.unwrap();
+            remove_from_home_timeline(&conn.db, &follower, actor)
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -24,6 +24,7 @@ fn instance_actor_links(domain: &str) -> UserLinks {
         followers: format!("https://{domain}/actor/followers"),
         following: format!("https://{domain}/actor/following"),
         liked: format!("https://{domain}/actor/liked"),
+        url: format!("https://{domain}/about/more?instance_actor=true"),
     }
 }
 
@@ -50,6 +51,20 @@ impl InstanceActor {
             ap_user_id: None,
             domain: None,
             liked: None,
+            name: None,
+            summary: None,
+            url: None,
+            icon: None,
+            image: None,
+            featured: None,
+            featured_tags: None,
+            manually_approves_followers: Some(true),
+            discoverable: Some(false),
+            indexable: Some(false),
+            memorial: None,
+            attachment: None,
+            also_known_as: None,
+            moved_to: None,
         };
 
         InstanceActor {
@@ -1,3 +1,7 @@
+pub mod delivery;
 pub mod fetch;
+pub mod inbox;
 pub mod instance_actor;
+pub mod outbox;
 pub mod verification;
+pub mod webfinger;
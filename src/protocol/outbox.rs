@@ -0,0 +1,812 @@
+use actix_web::web::Data;
+use serde_json::{json, Map, Value};
+use url::Url;
+
+use crate::{
+    activitystream_objects::{
+        activities::ActivityType,
+        actors::{actor_context, Actor},
+        core_types::{normalize_public, PUBLIC_COLLECTION},
+        object::ObjectWrapper,
+    },
+    cache_and_fetch::{fetch_actor, fetch_object, refetch_actor, Cache},
+    db::{
+        access_tokens::now_milis,
+        activities::{get_activity_by_fedi_id, insert_activity, set_activity_body},
+        actor_utilities::{
+            get_ap_actor_by_db_id, get_ap_actor_id_by_fedi_id, set_moved_to, update_actor_profile,
+        },
+        conn::DbConn,
+        following::{
+            delete_follow, delete_follow_request, get_follow_request_by_activity,
+            get_known_inboxes, insert_follow, insert_follow_request, move_local_follows,
+        },
+        internal_actor::{
+            delete_internal_user, get_actor_id_from_internal, get_uid_from_internal, is_suspended,
+        },
+        objects::{
+            create_new_object, delete_object, get_object_by_db_id, get_object_meta_by_fedi_id,
+            insert_tombstone, tombstone_actor_objects, update_object, update_object_recipients,
+            DbObject, InsertErr,
+        },
+        private_key::{get_private_key, KeyErr, KeyOwner},
+        reactions::{
+            delete_announce_by_activity, delete_like_by_activity, insert_announce, insert_like,
+        },
+        timelines::{backfill_home_timeline, remove_from_home_timeline},
+    },
+};
+
+use super::delivery::{deliver, deliver_locally, get_delivery_inboxes};
+
+/// placeholder id for objects that haven't been given one yet,
+/// [`create_new_object`] replaces it with the real one
+const TEMP_ID: &str = "https://temp.com";
+
+/// actor properties a user can change about themselves
+pub const PROFILE_FIELDS: [&str; 13] = [
+    "name",
+    "summary",
+    "url",
+    "icon",
+    "image",
+    "featured",
+    "featuredTags",
+    "manuallyApprovesFollowers",
+    "discoverable",
+    "indexable",
+    "memorial",
+    "attachment",
+    "alsoKnownAs",
+];
+
+/// an `Update` of a local actor's whole profile. every profile property is sent so
+/// ones that were cleared get removed
+pub fn profile_update(actor: &Actor) -> Value {
+    let mut object = serde_json::to_value(actor).unwrap();
+    for key in PROFILE_FIELDS {
+        if object.get(key).is_none() {
+            object[key] = Value::Null;
+        }
+    }
+    json!({
+        "@context": actor_context(),
+        "type": "Update",
+        "actor": actor.id.as_str(),
+        "to": [PUBLIC_COLLECTION],
+        "object": object,
+    })
+}
+
+/// properties of an object its author can change with an `Update`
+const OBJECT_FIELDS: [&str; 16] = [
+    "name",
+    "content",
+    "summary",
+    "sensitive",
+    "mediaType",
+    "attachment",
+    "tag",
+    "url",
+    "startTime",
+    "endTime",
+    "icon",
+    "image",
+    "duration",
+    "preview",
+    "to",
+    "cc",
+];
+
+/// addressing properties, in the order they are checked for recipients
+const ADDRESSING: [&str; 5] = ["to", "bto", "cc", "bcc", "audience"];
+
+#[derive(Debug)]
+pub enum OutboxErr {
+    /// the body wasn't an activity or object we could understand
+    BadBody(String),
+    /// a valid activity that we don't do anything with
+    Unsupported(String),
+    NotFound,
+    /// the activity is acting on something the user doesn't own
+    Forbidden,
+    InsertErr(InsertErr),
+    DbErr(sqlx::Error),
+    KeyErr(KeyErr),
+    /// the user has no private key to sign the delivery with
+    MissingKey,
+}
+
+impl std::fmt::Display for OutboxErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutboxErr::BadBody(x) => write!(f, "BadBody: {}", x),
+            OutboxErr::Unsupported(x) => write!(f, "Unsupported: {}", x),
+            OutboxErr::NotFound => write!(f, "NotFound"),
+            OutboxErr::Forbidden => write!(f, "Forbidden"),
+            OutboxErr::InsertErr(x) => write!(f, "InsertErr: {}", x),
+            OutboxErr::DbErr(x) => write!(f, "DbErr: {}", x),
+            OutboxErr::KeyErr(x) => write!(f, "KeyErr: {}", x),
+            OutboxErr::MissingKey => write!(f, "MissingKey"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OutboxErr {
+    fn from(value: sqlx::Error) -> Self {
+        OutboxErr::DbErr(value)
+    }
+}
+
+impl From<KeyErr> for OutboxErr {
+    fn from(value: KeyErr) -> Self {
+        OutboxErr::KeyErr(value)
+    }
+}
+
+impl From<InsertErr> for OutboxErr {
+    fn from(value: InsertErr) -> Self {
+        OutboxErr::InsertErr(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxResult {
+    /// id assigned to the activity, used for the `Location` header
+    pub id: Url,
+    /// the activity as it was delivered
+    pub activity: String,
+    /// set when the activity created an object
+    pub obj_id: Option<i64>,
+}
+
+fn get_str<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(|x| x.as_str())
+}
+
+/// the id of a property that can either be a link or an embedded object
+pub fn get_link(value: &Value, key: &str) -> Option<Url> {
+    let link = match value.get(key)? {
+        Value::String(x) => x.as_str(),
+        Value::Object(x) => x.get("id")?.as_str()?,
+        _ => return None,
+    };
+    Url::parse(link).ok()
+}
+
+/// every recipient in the addressing properties of `value`
+pub fn get_recipients(value: &Value) -> Vec<Url> {
+    let mut recipients = Vec::new();
+    for key in ADDRESSING {
+        match value.get(key) {
+            Some(Value::String(x)) => recipients.extend(Url::parse(normalize_public(x)).ok()),
+            Some(Value::Array(x)) => recipients.extend(
+                x.iter()
+                    .filter_map(|x| x.as_str())
+                    .filter_map(|x| Url::parse(normalize_public(x)).ok()),
+            ),
+            _ => {}
+        }
+    }
+    recipients
+}
+
+/// bto and bcc are for delivery only and must never be shown to recipients
+fn strip_hidden_recipients(value: &mut Value) {
+    let Some(map) = value.as_object_mut() else {
+        return;
+    };
+    map.remove("bto");
+    map.remove("bcc");
+    if let Some(object) = map.get_mut("object").and_then(|x| x.as_object_mut()) {
+        object.remove("bto");
+        object.remove("bcc");
+    }
+}
+
+fn is_activity_type(type_field: &str) -> bool {
+    serde_json::from_value::<ActivityType>(Value::String(type_field.to_string())).is_ok()
+}
+
+/// processes a client to server post to a local user's outbox.
+///
+/// bare objects are wrapped in a Create, ids are assigned by the server,
+/// side effects are applied and the result is delivered to remote recipients
+///
+/// https://www.w3.org/TR/activitypub/#client-to-server-interactions
+pub async fn post_outbox(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    uid: i64,
+    preferred_username: &str,
+    body: Value,
+) -> Result<OutboxResult, OutboxErr> {
+    let domain = &cache.state.instance_domain;
+    let user_id = format!("https://{}/users/{}", domain, preferred_username);
+    let followers = format!("{}/followers", &user_id);
+
+    //suspended accounts keep their data but can't federate
+    if is_suspended(&conn.db, preferred_username).await? {
+        return Err(OutboxErr::Forbidden);
+    }
+
+    let Some(type_field) = get_str(&body, "type").map(|x| x.to_string()) else {
+        return Err(OutboxErr::BadBody("missing type".to_string()));
+    };
+
+    let mut activity = match is_activity_type(&type_field) {
+        true => body,
+        false => {
+            //https://www.w3.org/TR/activitypub/#object-without-create
+            let mut activity = json!({
+                "@context": "https://www.w3.org/ns/activitystreams",
+                "type": "Create",
+                "actor": &user_id,
+            });
+            for key in ADDRESSING {
+                if let Some(x) = body.get(key) {
+                    activity[key] = x.clone();
+                }
+            }
+            activity["object"] = body;
+            activity
+        }
+    };
+
+    let Some(map) = activity.as_object_mut() else {
+        return Err(OutboxErr::BadBody("not an object".to_string()));
+    };
+    match map.get("actor").and_then(|x| x.as_str()) {
+        Some(x) if x.ne(&user_id) => return Err(OutboxErr::Forbidden),
+        Some(_) => {}
+        None => {
+            map.insert("actor".to_string(), Value::String(user_id.clone()));
+        }
+    }
+    map.entry("@context").or_insert(Value::String(
+        "https://www.w3.org/ns/activitystreams".to_string(),
+    ));
+
+    let activity_type: ActivityType = serde_json::from_value(activity["type"].clone())
+        .map_err(|x| OutboxErr::BadBody(x.to_string()))?;
+
+    let mut recipients = get_recipients(&activity);
+    //set when the follow has to wait for the target to accept it
+    let mut pending_follow: Option<Url> = None;
+
+    let obj_id = match activity_type {
+        ActivityType::Create => {
+            let obj_id = create(conn, domain, &user_id, &mut activity, &mut recipients).await?;
+            deliver_locally(conn, domain, obj_id, &recipients).await;
+            Some(obj_id)
+        }
+        ActivityType::Follow => {
+            let Some(target) = get_link(&activity, "object") else {
+                return Err(OutboxErr::BadBody("missing object".to_string()));
+            };
+            let local = target
+                .domain()
+                .is_some_and(|x| x.eq_ignore_ascii_case(domain));
+            match local {
+                true => {
+                    let Some(target_ap_id) =
+                        get_ap_actor_id_by_fedi_id(&conn.db, target.as_str()).await?
+                    else {
+                        return Err(OutboxErr::NotFound);
+                    };
+                    let target_actor = get_ap_actor_by_db_id(target_ap_id, conn).await;
+                    match target_actor.manually_approves_followers.unwrap_or(false) {
+                        //recorded once the follow has an id
+                        true => pending_follow = Some(target.clone()),
+                        false => {
+                            insert_follow(&conn.db, &user_id, target.as_str()).await?;
+                            backfill_home_timeline(&conn.db, &user_id, target.as_str()).await?;
+                        }
+                    }
+                }
+                false => {
+                    //the follow is only recorded once the remote actor accepts it
+                    if fetch_actor(&target, cache, conn).await.is_err() {
+                        return Err(OutboxErr::NotFound);
+                    }
+                    pending_follow = Some(target.clone());
+                }
+            }
+            recipients.push(target);
+            None
+        }
+        ActivityType::Like | ActivityType::Announce => {
+            let Some(target) = get_link(&activity, "object") else {
+                return Err(OutboxErr::BadBody("missing object".to_string()));
+            };
+            if let Some(owner) = get_object_owner(conn, cache, &target).await {
+                recipients.push(owner);
+            }
+            None
+        }
+        ActivityType::Update => {
+            let Some(object) = activity.get("object").filter(|x| x.is_object()) else {
+                return Err(OutboxErr::BadBody(
+                    "update must embed the object".to_string(),
+                ));
+            };
+            let Some(target) = get_link(&activity, "object") else {
+                return Err(OutboxErr::BadBody("missing object id".to_string()));
+            };
+
+            if target.as_str().eq(&user_id) {
+                let updated = update_profile(conn, &user_id, object).await?;
+                activity["object"] = serde_json::to_value(updated).unwrap();
+                recipients.push(Url::parse(&followers).unwrap());
+                None
+            } else {
+                let obj_id = check_ownership(conn, &user_id, &target).await?;
+                let updated = update_status(conn, obj_id, &followers, object).await?;
+                let updated = serde_json::to_value(updated).unwrap();
+
+                //bto and bcc are only on what the client sent
+                recipients.extend(get_recipients(object));
+                recipients.extend(get_recipients(&updated));
+                recipients.push(Url::parse(&followers).unwrap());
+                activity["object"] = updated;
+                Some(obj_id)
+            }
+        }
+        ActivityType::Accept | ActivityType::Reject => {
+            let Some(target) = get_link(&activity, "object") else {
+                return Err(OutboxErr::BadBody("missing object".to_string()));
+            };
+            let Some(request) = get_follow_request_by_activity(&conn.db, target.as_str()).await?
+            else {
+                return Err(OutboxErr::NotFound);
+            };
+            if request.target.ne(&user_id) {
+                return Err(OutboxErr::Forbidden);
+            }
+
+            delete_follow_request(&conn.db, &request.actor, &user_id).await?;
+            match activity_type {
+                ActivityType::Accept => {
+                    insert_follow(&conn.db, &request.actor, &user_id).await?;
+                    backfill_home_timeline(&conn.db, &request.actor, &user_id).await?;
+                }
+                _ => {
+                    delete_follow(&conn.db, &request.actor, &user_id).await?;
+                    remove_from_home_timeline(&conn.db, &request.actor, &user_id).await?;
+                }
+            }
+
+            //the follow is embedded so the follower can match it up without fetching it
+            activity["object"] = json!({
+                "id": &request.activity_id,
+                "type": "Follow",
+                "actor": &request.actor,
+                "object": &user_id,
+            });
+            recipients.extend(Url::parse(&request.actor).ok());
+            None
+        }
+        ActivityType::Delete => {
+            let Some(target) = get_link(&activity, "object") else {
+                return Err(OutboxErr::BadBody("missing object".to_string()));
+            };
+            let obj_id = check_ownership(conn, &user_id, &target).await?;
+
+            let former_type = match get_object_by_db_id(obj_id, conn.db.begin().await?).await {
+                Some(DbObject::Object(x)) => serde_json::to_string(&x.type_field).unwrap(),
+                Some(DbObject::Question(_)) => "\"Question\"".to_string(),
+                None => return Err(OutboxErr::NotFound),
+            };
+            delete_object(target.as_str(), &former_type, conn.db.begin().await?).await?;
+
+            recipients.push(Url::parse(&followers).unwrap());
+            None
+        }
+        ActivityType::Undo => {
+            let Some(target) = get_link(&activity, "object") else {
+                return Err(OutboxErr::BadBody("missing object".to_string()));
+            };
+            let Some(stored) = get_activity_by_fedi_id(&conn.db, target.as_str()).await? else {
+                return Err(OutboxErr::NotFound);
+            };
+            if stored.actor.ne(&user_id) {
+                return Err(OutboxErr::Forbidden);
+            }
+
+            let stored_type: ActivityType = serde_json::from_str(&stored.type_field).unwrap();
+            match stored_type {
+                ActivityType::Follow => {
+                    delete_follow_request(&conn.db, &user_id, &stored.object).await?;
+                    delete_follow(&conn.db, &user_id, &stored.object).await?;
+                    remove_from_home_timeline(&conn.db, &user_id, &stored.object).await?;
+                    recipients.extend(Url::parse(&stored.object).ok());
+                }
+                ActivityType::Like => {
+                    delete_like_by_activity(&conn.db, target.as_str()).await?;
+                }
+                ActivityType::Announce => {
+                    delete_announce_by_activity(&conn.db, target.as_str()).await?;
+                }
+                x => return Err(OutboxErr::Unsupported(format!("Undo {:?}", x))),
+            }
+
+            //the undo goes to everyone who saw the original
+            if let Some(body) = &stored.body {
+                let original: Value = serde_json::from_str(body).unwrap();
+                recipients.extend(get_recipients(&original));
+            }
+            if matches!(stored_type, ActivityType::Like | ActivityType::Announce) {
+                if let Ok(object) = Url::parse(&stored.object) {
+                    recipients.extend(get_object_owner(conn, cache, &object).await);
+                }
+            }
+            None
+        }
+        ActivityType::Move => {
+            match get_link(&activity, "object") {
+                Some(x) if x.as_str().eq(&user_id) => {}
+                Some(_) => return Err(OutboxErr::Forbidden),
+                None => return Err(OutboxErr::BadBody("missing object".to_string())),
+            }
+            let Some(target) = get_link(&activity, "target") else {
+                return Err(OutboxErr::BadBody("missing target".to_string()));
+            };
+            if target.as_str().eq(&user_id) {
+                return Err(OutboxErr::BadBody("can't move to itself".to_string()));
+            }
+
+            //the new account has to claim the old one, or anyone could take its followers
+            let Ok(new_actor) = refetch_actor(&target, cache, conn).await else {
+                return Err(OutboxErr::NotFound);
+            };
+            if !new_actor
+                .also_known_as
+                .is_some_and(|x| x.contains(&user_id))
+            {
+                return Err(OutboxErr::BadBody(
+                    "the target doesn't list this account in alsoKnownAs".to_string(),
+                ));
+            }
+
+            set_moved_to(&conn.db, &user_id, target.as_str()).await?;
+            move_followers(conn, cache, &user_id, &target).await?;
+            recipients.push(Url::parse(&followers).unwrap());
+            None
+        }
+        x => return Err(OutboxErr::Unsupported(format!("{:?}", x))),
+    };
+
+    //creates take their id from the object, everything else is stored under the actor
+    let id = match activity_type {
+        ActivityType::Create => {
+            let id = activity["id"].as_str().unwrap().to_string();
+            let act_id = insert_activity(
+                &conn.db,
+                &serde_json::to_string(&activity_type).unwrap(),
+                &user_id,
+                activity["object"]["id"].as_str().unwrap(),
+            )
+            .await?;
+            strip_hidden_recipients(&mut activity);
+            set_activity_body(&conn.db, act_id, &id, &activity.to_string()).await?;
+            id
+        }
+        _ => {
+            let object = get_link(&activity, "object").unwrap();
+            let act_id = insert_activity(
+                &conn.db,
+                &serde_json::to_string(&activity_type).unwrap(),
+                &user_id,
+                object.as_str(),
+            )
+            .await?;
+            let id = format!("{}/activities/{}", &user_id, act_id);
+            activity["id"] = Value::String(id.clone());
+
+            match activity_type {
+                ActivityType::Like => insert_like(&conn.db, &user_id, object.as_str(), &id).await?,
+                ActivityType::Follow => {
+                    if let Some(target) = &pending_follow {
+                        insert_follow_request(&conn.db, &user_id, target.as_str(), &id).await?
+                    }
+                }
+                ActivityType::Announce => {
+                    insert_announce(&conn.db, &user_id, object.as_str(), &id).await?
+                }
+                _ => {}
+            }
+
+            strip_hidden_recipients(&mut activity);
+            set_activity_body(&conn.db, act_id, &id, &activity.to_string()).await?;
+            id
+        }
+    };
+
+    let activity_str = activity.to_string();
+
+    let Some(key) = get_private_key(&conn.db, &cache.kek, KeyOwner::User(uid)).await? else {
+        return Err(OutboxErr::MissingKey);
+    };
+    let inboxes = get_delivery_inboxes(conn, cache, &user_id, &followers, &recipients).await;
+    deliver(&activity_str, &user_id, &key, &inboxes).await;
+
+    Ok(OutboxResult {
+        id: Url::parse(&id).unwrap(),
+        activity: activity_str,
+        obj_id,
+    })
+}
+
+/// points the local follows of an account that moved at the account it moved to. remote
+/// accounts are also sent a `Follow` from each follower, they only send posts to followers
+/// they know about
+///
+/// https://docs.joinmastodon.org/spec/activitypub/#Move
+pub async fn move_followers(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    old: &str,
+    new: &Url,
+) -> Result<(), OutboxErr> {
+    let domain = &cache.state.instance_domain;
+    let followers = move_local_follows(&conn.db, old, new.as_str(), domain).await?;
+    let local = new.domain().is_some_and(|x| x.eq_ignore_ascii_case(domain));
+
+    for follower in followers {
+        if local {
+            backfill_home_timeline(&conn.db, &follower, new.as_str()).await?;
+            continue;
+        }
+        let Some(ap_user_id) = get_ap_actor_id_by_fedi_id(&conn.db, &follower).await? else {
+            continue;
+        };
+        let actor = get_ap_actor_by_db_id(ap_user_id, conn).await;
+        let Some(uid) = get_uid_from_internal(&conn.db, &actor.preferred_username).await? else {
+            continue;
+        };
+        let follow = json!({
+            "type": "Follow",
+            "object": new.as_str(),
+        });
+        //post_outbox calls this for moves so the future has to be boxed
+        let result = Box::pin(post_outbox(
+            conn,
+            cache,
+            uid,
+            &actor.preferred_username,
+            follow,
+        ))
+        .await;
+        if let Err(x) = result {
+            eprintln!("failed to follow {} for {}: {}", new, follower, x);
+        }
+    }
+
+    Ok(())
+}
+
+/// deletes a local account. the actor and everything it made are replaced with tombstones,
+/// then a `Delete` of the actor is sent to every inbox we know of, not just the followers,
+/// so any server that has seen the account drops it. returns how many inboxes that was
+///
+/// https://www.w3.org/TR/activitypub/#delete-activity-outbox
+pub async fn delete_account(
+    conn: &Data<DbConn>,
+    cache: &Cache,
+    uid: i64,
+    preferred_username: &str,
+) -> Result<usize, OutboxErr> {
+    let domain = &cache.state.instance_domain;
+    let user_id = format!("https://{}/users/{}", domain, preferred_username);
+
+    let Some(ap_user_id) = get_actor_id_from_internal(&conn.db, preferred_username).await? else {
+        return Err(OutboxErr::NotFound);
+    };
+    let actor = get_ap_actor_by_db_id(ap_user_id, conn).await;
+    //the key goes with the account so the delete has to be signed before it's gone
+    let Some(key) = get_private_key(&conn.db, &cache.kek, KeyOwner::User(uid)).await? else {
+        return Err(OutboxErr::NotFound);
+    };
+
+    let activity = json!({
+        "@context": "https://www.w3.org/ns/activitystreams",
+        "id": format!("{}#delete", &user_id),
+        "type": "Delete",
+        "actor": &user_id,
+        "to": [PUBLIC_COLLECTION],
+        "object": &user_id,
+    });
+    let inboxes = get_known_inboxes(&conn.db, domain).await?;
+
+    let mut transaction = conn.db.begin().await?;
+    tombstone_actor_objects(&mut *transaction, ap_user_id).await?;
+    insert_tombstone(
+        &mut *transaction,
+        &user_id,
+        &serde_json::to_string(&actor.type_field).unwrap(),
+    )
+    .await?;
+    delete_internal_user(&mut *transaction, uid).await?;
+    transaction.commit().await?;
+
+    deliver(&activity.to_string(), &user_id, &key, &inboxes).await;
+    Ok(inboxes.len())
+}
+
+/// applies an update to the user's own actor. like other client updates only the
+/// properties that are present are replaced, returns the whole updated actor
+async fn update_profile(
+    conn: &Data<DbConn>,
+    user_id: &str,
+    object: &Value,
+) -> Result<Actor, OutboxErr> {
+    let Some(ap_user_id) = get_ap_actor_id_by_fedi_id(&conn.db, user_id).await? else {
+        return Err(OutboxErr::NotFound);
+    };
+    let current = get_ap_actor_by_db_id(ap_user_id, conn).await;
+
+    let mut merged = serde_json::to_value(&current).unwrap();
+    for key in PROFILE_FIELDS {
+        if let Some(x) = object.get(key) {
+            merged[key] = x.clone();
+        }
+    }
+    let updated: Actor =
+        serde_json::from_value(merged).map_err(|x| OutboxErr::BadBody(x.to_string()))?;
+
+    update_actor_profile(&conn.db, &updated).await?;
+    Ok(updated)
+}
+
+/// applies an update to one of the user's own objects. only the properties that are
+/// present are replaced, returns the whole updated object
+async fn update_status(
+    conn: &Data<DbConn>,
+    obj_id: i64,
+    followers: &str,
+    object: &Value,
+) -> Result<ObjectWrapper, OutboxErr> {
+    let Some(DbObject::Object(current)) = get_object_by_db_id(obj_id, conn.db.begin().await?).await
+    else {
+        return Err(OutboxErr::NotFound);
+    };
+
+    let mut merged = serde_json::to_value(&current).unwrap();
+    for key in OBJECT_FIELDS {
+        if let Some(x) = object.get(key) {
+            merged[key] = x.clone();
+        }
+    }
+    let mut updated: ObjectWrapper =
+        serde_json::from_value(merged).map_err(|x| OutboxErr::BadBody(x.to_string()))?;
+    updated.object = updated.object.updated_milis(now_milis());
+
+    let mut transaction = conn.db.begin().await?;
+    update_object(
+        &mut *transaction,
+        updated.object.id.as_str(),
+        &updated.object,
+    )
+    .await?;
+    if object.get("to").is_some() || object.get("cc").is_some() {
+        update_object_recipients(&mut transaction, obj_id, &updated.object, followers).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(updated)
+}
+
+/// stores the object of a create and replaces it in the activity with the stored version
+async fn create(
+    conn: &Data<DbConn>,
+    domain: &str,
+    user_id: &str,
+    activity: &mut Value,
+    recipients: &mut Vec<Url>,
+) -> Result<i64, OutboxErr> {
+    let mut activity_addressing = Map::new();
+    for key in ADDRESSING {
+        if let Some(x) = activity.get(key) {
+            activity_addressing.insert(key.to_string(), x.clone());
+        }
+    }
+
+    let Some(object) = activity.get_mut("object").and_then(|x| x.as_object_mut()) else {
+        return Err(OutboxErr::BadBody(
+            "create must embed the object".to_string(),
+        ));
+    };
+    if object
+        .get("type")
+        .and_then(|x| x.as_str())
+        .is_some_and(is_activity_type)
+    {
+        return Err(OutboxErr::BadBody("can't create an activity".to_string()));
+    }
+
+    object.insert("id".to_string(), Value::String(TEMP_ID.to_string()));
+    object.insert(
+        "attributedTo".to_string(),
+        Value::String(user_id.to_string()),
+    );
+
+    //https://www.w3.org/TR/activitypub/#create-activity-outbox
+    //addressing on the activity and the object should match
+    let mut addressing = Map::new();
+    for key in ADDRESSING {
+        let value = match (object.get(key), activity_addressing.get(key)) {
+            (Some(x), _) => x.clone(),
+            (None, Some(x)) => x.clone(),
+            (None, None) => continue,
+        };
+        addressing.insert(key.to_string(), value);
+    }
+
+    let object = activity.get_mut("object").unwrap();
+    for (key, value) in &addressing {
+        object[key] = value.clone();
+    }
+    let wrapper: ObjectWrapper =
+        serde_json::from_value(object.clone()).map_err(|x| OutboxErr::BadBody(x.to_string()))?;
+
+    let obj_id =
+        create_new_object(&DbObject::Object(wrapper), conn.db.begin().await?, domain).await?;
+
+    let Some(DbObject::Object(stored)) = get_object_by_db_id(obj_id, conn.db.begin().await?).await
+    else {
+        return Err(OutboxErr::NotFound);
+    };
+    let mut stored = serde_json::to_value(&stored).unwrap();
+    for (key, value) in &addressing {
+        stored[key] = value.clone();
+        activity[key] = value.clone();
+    }
+
+    recipients.extend(get_recipients(&stored));
+    activity["id"] = Value::String(format!("{}/activity", stored["id"].as_str().unwrap()));
+    activity["object"] = stored;
+
+    Ok(obj_id)
+}
+
+/// finds the db id of a local object and makes sure it belongs to `user_id`
+async fn check_ownership(
+    conn: &Data<DbConn>,
+    user_id: &str,
+    target: &Url,
+) -> Result<i64, OutboxErr> {
+    let Some(meta) = get_object_meta_by_fedi_id(&conn.db, target.as_str()).await? else {
+        return Err(OutboxErr::NotFound);
+    };
+    let Some(ap_user_id) = get_ap_actor_id_by_fedi_id(&conn.db, user_id).await? else {
+        return Err(OutboxErr::NotFound);
+    };
+    if meta.ap_user_id != ap_user_id {
+        return Err(OutboxErr::Forbidden);
+    }
+    Ok(meta.obj_id)
+}
+
+/// the actor an object is attributed to, fetching it if we don't have it
+async fn get_object_owner(conn: &Data<DbConn>, cache: &Cache, object: &Url) -> Option<Url> {
+    if let Some(x) = get_object_meta_by_fedi_id(&conn.db, object.as_str())
+        .await
+        .unwrap()
+    {
+        let owner = match get_object_by_db_id(x.obj_id, conn.db.begin().await.unwrap()).await? {
+            DbObject::Object(x) => x.object.get_attributed_to().cloned(),
+            DbObject::Question(_) => None,
+        };
+        return owner;
+    }
+
+    if object
+        .domain()
+        .is_some_and(|x| x.eq_ignore_ascii_case(&cache.state.instance_domain))
+    {
+        return None;
+    }
+
+    let fetched = fetch_object(object, cache, conn).await.ok()?;
+    fetched.get_owner().cloned()
+}
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, time::SystemTime};
 
 use actix_web::{
+    http::header::HeaderMap,
     web::{self, Data},
     HttpRequest,
 };
@@ -39,15 +40,18 @@ pub enum RequestVerificationError {
     NoMessageSignature,
     BadMessageSignature,
     NoSignatureKey,
+    BadSignatureKey,
     NoSignature,
     SignatureIncorrectBase64,
     ActorFetchFailed(String),
     ActorFetchBodyFailed,
     ActorDeserializeFailed,
+    BadActorPublicKey,
     NoSignatureHeaders,
     SignatureVerifyFailed,
     NoDate,
     MissingSignedHeaderField(String),
+    BadSignedHeaderField(String),
     BodyDeserializeErr,
     ForgedAttribution,
     KeyOwnerDoesNotMatch,
@@ -70,8 +74,13 @@ pub async fn post_to_inbox(
 
     let digest_base64 = &generate_digest(activity.as_bytes());
 
+    let inbox_path = match Url::parse(to_inbox) {
+        Ok(x) => x.path().to_string(),
+        Err(_) => "/inbox".to_string(),
+    };
+
     //string to be signed
-    let signed_string = format!("(request-target): post /inbox\nhost: {to_domain}\ndate: {date}\ndigest: SHA-256={digest_base64}");
+    let signed_string = format!("(request-target): post {inbox_path}\nhost: {to_domain}\ndate: {date}\ndigest: SHA-256={digest_base64}");
     let mut signer = openssl::sign::Signer::new(MessageDigest::sha256(), &keypair).unwrap();
     signer.update(signed_string.as_bytes()).unwrap();
     let signature = openssl::base64::encode_block(&signer.sign_to_vec().unwrap());
@@ -94,6 +103,7 @@ pub async fn post_to_inbox(
         .header("Date", date)
         .header("Signature", header)
         .header("Digest", "SHA-256=".to_owned() + digest_base64)
+        .header("Content-Type", "application/activity+json")
         .body(activity.to_string());
 
     dbg!(&client);
@@ -101,7 +111,15 @@ pub async fn post_to_inbox(
     let res = client.send().await;
     dbg!(&res);
 
-    let response = res.unwrap().text().await;
+    let res = match res {
+        Ok(x) => x,
+        Err(x) => {
+            println!("delivery to {to_inbox} failed: {x}");
+            return;
+        }
+    };
+
+    let response = res.text().await;
 
     dbg!(&response);
 
@@ -153,6 +171,31 @@ pub async fn verify_incoming(
         return Err(RequestVerificationError::DigestDoesNotMatch);
     }
 
+    let actor = verify_signature(cache, request_headers, "post", path, instance_domain).await?;
+
+    if let Some(x) = object.get_owner() {
+        if actor.get_id().domain().ne(&x.domain()) {
+            println!(
+                "KeyOwnerDoesNotMatch, \nobject owner: {} \nactor: {}",
+                x.as_str(),
+                actor.get_id()
+            );
+            return Err(RequestVerificationError::KeyOwnerDoesNotMatch);
+        }
+    }
+
+    Ok(body)
+}
+
+/// checks the http signature of a request, `method` is lowercase and `path` includes the query.
+/// returns the actor that signed it
+async fn verify_signature(
+    cache: &Cache,
+    request_headers: &HeaderMap,
+    method: &str,
+    path: &str,
+    instance_domain: &str,
+) -> Result<Box<Actor>, RequestVerificationError> {
     //get the signature header
 
     let Some(signature_header) = request_headers.get("Signature") else {
@@ -188,8 +231,12 @@ pub async fn verify_incoming(
 
     // dbg!(&signature);
 
+    let Ok(key_id) = Url::parse(&key_id) else {
+        return Err(RequestVerificationError::BadSignatureKey);
+    };
+
     let fetched = authorized_fetch(
-        &Url::parse(&key_id).unwrap(),
+        &key_id,
         &cache.instance_actor.item.key_id,
         &cache.instance_actor.item.private_key,
     )
@@ -226,19 +273,11 @@ pub async fn verify_incoming(
     //     return Err(RequestVerificationError::ActorDeserializeFailed);
     // };
 
-    if let Some(x) = object.get_owner() {
-        if actor.get_id().domain().ne(&x.domain()) {
-            println!(
-                "KeyOwnerDoesNotMatch, \nobject owner: {} \nactor: {}",
-                x.as_str(),
-                actor.get_id()
-            );
-            return Err(RequestVerificationError::KeyOwnerDoesNotMatch);
-        }
-    }
-
-    let key =
-        openssl::rsa::Rsa::public_key_from_pem(actor.public_key.public_key_pem.as_bytes()).unwrap();
+    let Ok(key) =
+        openssl::rsa::Rsa::public_key_from_pem(actor.public_key.public_key_pem.as_bytes())
+    else {
+        return Err(RequestVerificationError::BadActorPublicKey);
+    };
 
     let Some(headers) = signature_header.get("headers") else {
         return Err(RequestVerificationError::NoSignatureHeaders);
@@ -250,39 +289,64 @@ pub async fn verify_incoming(
 
     //generate a sign string of the actual request's headers with the real header values mentoned in the provided sign string
 
-    let comparison_string: Vec<String> = headers
-        .replace('"', "")
-        .split(' ')
-        .filter_map(|signed_header_name| match signed_header_name {
-            "(request-target)" => Some(format!("(request-target): post {path}")),
-            "host" => Some(format!("host: {instance_domain}")),
+    let mut comparison_string: Vec<String> = Vec::new();
+    for signed_header_name in headers.replace('"', "").split(' ') {
+        match signed_header_name {
+            "(request-target)" => {
+                comparison_string.push(format!("(request-target): {method} {path}"))
+            }
+            "host" => comparison_string.push(format!("host: {instance_domain}")),
             _ => {
-                let value = request_headers.get(signed_header_name)?;
-
-                let value = String::from_utf8(value.as_bytes().to_vec()).unwrap();
-                let x = format!("{signed_header_name}: {value}",);
-                dbg!(&x);
-                Some(x)
+                let Some(value) = request_headers.get(signed_header_name) else {
+                    return Err(RequestVerificationError::MissingSignedHeaderField(
+                        signed_header_name.to_string(),
+                    ));
+                };
+                let Ok(value) = value.to_str() else {
+                    return Err(RequestVerificationError::BadSignedHeaderField(
+                        signed_header_name.to_string(),
+                    ));
+                };
+                comparison_string.push(format!("{signed_header_name}: {value}"));
             }
-        })
-        .collect();
+        }
+    }
 
     let comparison_string = comparison_string.join("\n");
-    dbg!(&comparison_string);
 
-    let pubkey = openssl::pkey::PKey::from_rsa(key).unwrap();
-
-    let mut verifier =
-        openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), &pubkey).unwrap();
-    let input = &comparison_string;
-    verifier.update(input.as_bytes()).unwrap();
+    let Ok(signature) = openssl::base64::decode_block(&signature) else {
+        return Err(RequestVerificationError::SignatureIncorrectBase64);
+    };
 
-    let signature = openssl::base64::decode_block(&signature).unwrap();
-    let accepted = verifier.verify(&signature).unwrap();
+    let accepted = openssl::pkey::PKey::from_rsa(key)
+        .and_then(|pubkey| {
+            let mut verifier =
+                openssl::sign::Verifier::new(openssl::hash::MessageDigest::sha256(), &pubkey)?;
+            verifier.update(comparison_string.as_bytes())?;
+            verifier.verify(&signature)
+        })
+        .unwrap_or(false);
 
     if !accepted {
         return Err(RequestVerificationError::SignatureVerifyFailed);
     }
 
-    Ok(body)
+    Ok(actor)
+}
+
+/// verifies a signed GET, used for fetching objects that aren't public.
+/// returns the id of the actor that signed the request
+pub async fn verify_get(
+    cache: &Cache,
+    request: &HttpRequest,
+    instance_domain: &str,
+) -> Result<Url, RequestVerificationError> {
+    let path = match request.uri().path_and_query() {
+        Some(x) => x.as_str().to_string(),
+        None => request.path().to_string(),
+    };
+
+    let actor = verify_signature(cache, request.headers(), "get", &path, instance_domain).await?;
+
+    Ok(actor.id)
 }
@@ -0,0 +1,188 @@
+use std::fmt::Display;
+
+use url::{form_urlencoded, Url};
+
+use crate::{
+    api::webfinger::{Jrd, REL_SELF},
+    media::proxy::{fetch_checked, ProxyErr},
+};
+
+#[derive(Debug)]
+pub enum WebfingerErr {
+    /// not something we're willing to fetch
+    InvalidUrl,
+    RequestErr(reqwest::Error),
+    /// the remote server didn't return a 2xx
+    BadStatus(u16),
+    DeserializationErr(serde_json::Error),
+    /// the response doesn't link to an activitypub actor
+    NoActor,
+}
+
+impl Display for WebfingerErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebfingerErr::InvalidUrl => write!(f, "InvalidUrl"),
+            WebfingerErr::RequestErr(x) => write!(f, "RequestErr: {}", x),
+            WebfingerErr::BadStatus(x) => write!(f, "BadStatus: {}", x),
+            WebfingerErr::DeserializationErr(x) => write!(f, "DeserializationErr: {}", x),
+            WebfingerErr::NoActor => write!(f, "NoActor"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for WebfingerErr {
+    fn from(value: reqwest::Error) -> Self {
+        WebfingerErr::RequestErr(value)
+    }
+}
+
+impl From<ProxyErr> for WebfingerErr {
+    fn from(value: ProxyErr) -> Self {
+        match value {
+            ProxyErr::RequestErr(x) => WebfingerErr::RequestErr(x),
+            ProxyErr::BadStatus(x) => WebfingerErr::BadStatus(x),
+            _ => WebfingerErr::InvalidUrl,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebfingerResult {
+    /// `user@domain` as the domain knows it, this might differ in case from what was asked
+    pub handle: String,
+    pub actor: Url,
+}
+
+/// activitypub actors are served as either of these
+///
+/// https://www.w3.org/TR/activitypub/#retrieving-objects
+fn is_activitypub_type(media_type: &str) -> bool {
+    let media_type = media_type.trim();
+    media_type.eq("application/activity+json")
+        || (media_type.starts_with("application/ld+json")
+            && media_type.contains("https://www.w3.org/ns/activitystreams"))
+}
+
+/// webfinger is https only and the same checks as the media proxy keep it
+/// from being pointed at the server's own network
+async fn fetch_jrd(url: &Url, local_domain: &str) -> Result<Jrd, WebfingerErr> {
+    let res = fetch_checked(
+        url,
+        local_domain,
+        true,
+        Some("application/jrd+json, application/json"),
+    )
+    .await?;
+    if !res.status().is_success() {
+        return Err(WebfingerErr::BadStatus(res.status().as_u16()));
+    }
+
+    serde_json::from_str(&res.text().await?).map_err(WebfingerErr::DeserializationErr)
+}
+
+/// the value of `name` in an xml start tag
+fn xml_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    for quote in ['"', '\''] {
+        let start = format!("{name}={quote}");
+        if let Some((_, rest)) = tag.split_once(&start) {
+            return rest.split(quote).next();
+        }
+    }
+    None
+}
+
+/// the lrdd template from a host-meta XRD document, there's no xml parser
+/// in the tree and host-meta is simple enough to pick apart by hand
+///
+/// https://www.rfc-editor.org/rfc/rfc6415#section-3
+fn lrdd_template(xrd: &str) -> Option<String> {
+    xrd.split("<Link").skip(1).find_map(|x| {
+        let tag = x.split('>').next()?;
+        if xml_attribute(tag, "rel")? != "lrdd" {
+            return None;
+        }
+        let template = xml_attribute(tag, "template")?.replace("&amp;", "&");
+        template.contains("{uri}").then_some(template)
+    })
+}
+
+/// some domains serve webfinger from another path, they say where with host-meta.
+/// the template has to stay on the domain that was asked
+async fn fetch_host_meta_jrd(
+    domain: &str,
+    resource: &str,
+    local_domain: &str,
+) -> Result<Jrd, WebfingerErr> {
+    let Ok(url) = Url::parse(&format!("https://{domain}/.well-known/host-meta")) else {
+        return Err(WebfingerErr::InvalidUrl);
+    };
+    let res = fetch_checked(&url, local_domain, true, Some("application/xrd+xml")).await?;
+    if !res.status().is_success() {
+        return Err(WebfingerErr::BadStatus(res.status().as_u16()));
+    }
+    let Some(template) = lrdd_template(&res.text().await?) else {
+        return Err(WebfingerErr::NoActor);
+    };
+
+    let resource: String = form_urlencoded::byte_serialize(resource.as_bytes()).collect();
+    let Ok(url) = Url::parse(&template.replace("{uri}", &resource)) else {
+        return Err(WebfingerErr::InvalidUrl);
+    };
+    if !url
+        .host_str()
+        .is_some_and(|x| x.eq_ignore_ascii_case(domain))
+    {
+        return Err(WebfingerErr::InvalidUrl);
+    }
+    fetch_jrd(&url, local_domain).await
+}
+
+/// asks `domain` for the id of the actor behind `acct:preferred_username@domain`,
+/// falling back to the host-meta lrdd template if the domain doesn't answer itself.
+/// `local_domain` is never asked
+///
+/// https://www.rfc-editor.org/rfc/rfc7033
+pub async fn webfinger_lookup(
+    preferred_username: &str,
+    domain: &str,
+    local_domain: &str,
+) -> Result<WebfingerResult, WebfingerErr> {
+    let resource = format!("acct:{preferred_username}@{domain}");
+
+    let mut url = Url::parse(&format!("https://{domain}/.well-known/webfinger"))
+        .map_err(|_| WebfingerErr::InvalidUrl)?;
+    url.query_pairs_mut().append_pair("resource", &resource);
+
+    let jrd = match fetch_jrd(&url, local_domain).await {
+        Ok(x) => x,
+        Err(WebfingerErr::InvalidUrl) => return Err(WebfingerErr::InvalidUrl),
+        Err(x) => match fetch_host_meta_jrd(domain, &resource, local_domain).await {
+            Ok(x) => x,
+            //the original error says more about what went wrong
+            Err(_) => return Err(x),
+        },
+    };
+
+    let Some(actor) = jrd
+        .links
+        .into_iter()
+        .filter(|x| x.rel.eq(REL_SELF))
+        .filter(|x| x.type_field.as_deref().is_some_and(is_activitypub_type))
+        .find_map(|x| x.href.and_then(|x| Url::parse(&x).ok()))
+    else {
+        return Err(WebfingerErr::NoActor);
+    };
+
+    //only trust the subject for handles on the domain we asked
+    let requested = format!("{preferred_username}@{domain}");
+    let handle = jrd
+        .subject
+        .as_deref()
+        .and_then(|x| x.strip_prefix("acct:"))
+        .filter(|x| x.eq_ignore_ascii_case(&requested))
+        .unwrap_or(&requested)
+        .to_string();
+
+    Ok(WebfingerResult { handle, actor })
+}